@@ -2,8 +2,11 @@
 
 use crate::{
     bitcoin::{
-        absolute, key::Secp256k1, transaction, Address, Amount, Network, OutPoint, ScriptBuf,
-        Transaction, TxIn, TxOut, Txid,
+        absolute,
+        key::Secp256k1,
+        secp256k1::{PublicKey, SecretKey},
+        transaction, Address, Amount, Network, NetworkKind, OutPoint, ScriptBuf, Transaction, TxIn,
+        TxOut, Txid,
     },
     chain::{
         keychain_txout::{self},
@@ -11,6 +14,7 @@ use crate::{
     },
     locked_outpoints,
     miniscript::descriptor::{Descriptor, DescriptorPublicKey},
+    silent_payments::{self, SilentPaymentAddress, SilentPaymentOutput},
     ChangeSet, WalletPersister,
 };
 
@@ -164,6 +168,33 @@ where
         outpoints: [(outpoint, true)].into(),
     };
 
+    let secp = Secp256k1::new();
+    let sp_key = |byte| SecretKey::from_slice(&[byte; 32]).unwrap();
+    let silent_payments_changeset = silent_payments::ChangeSet {
+        keys: Some((
+            PublicKey::from_secret_key(&secp, &sp_key(1)),
+            PublicKey::from_secret_key(&secp, &sp_key(2)),
+        )),
+        labels: [0, 1].into(),
+        outputs: [(
+            OutPoint::new(tx1.compute_txid(), 0),
+            SilentPaymentOutput {
+                tweak: sp_key(3),
+                label: None,
+            },
+        )]
+        .into(),
+        sent: [(
+            spk_at_index(&descriptor, 5),
+            SilentPaymentAddress::new(
+                PublicKey::from_secret_key(&secp, &sp_key(5)),
+                PublicKey::from_secret_key(&secp, &sp_key(6)),
+                NetworkKind::Test,
+            ),
+        )]
+        .into(),
+    };
+
     let mut changeset = ChangeSet {
         descriptor: Some(descriptor.clone()),
         change_descriptor: Some(change_descriptor.clone()),
@@ -172,6 +203,7 @@ where
         tx_graph: tx_graph_changeset,
         indexer: keychain_txout_changeset,
         locked_outpoints: locked_outpoints_changeset,
+        silent_payments: silent_payments_changeset,
     };
 
     // persist and load
@@ -223,6 +255,28 @@ where
         outpoints: [(outpoint, true)].into(),
     };
 
+    let silent_payments_changeset = silent_payments::ChangeSet {
+        keys: None,
+        labels: [7].into(),
+        outputs: [(
+            OutPoint::new(tx2.compute_txid(), 0),
+            SilentPaymentOutput {
+                tweak: sp_key(4),
+                label: Some(7),
+            },
+        )]
+        .into(),
+        sent: [(
+            spk_at_index(&descriptor, 6),
+            SilentPaymentAddress::new(
+                PublicKey::from_secret_key(&secp, &sp_key(7)),
+                PublicKey::from_secret_key(&secp, &sp_key(8)),
+                NetworkKind::Main,
+            ),
+        )]
+        .into(),
+    };
+
     let changeset_new = ChangeSet {
         descriptor: None,
         change_descriptor: None,
@@ -231,6 +285,7 @@ where
         tx_graph: tx_graph_changeset,
         indexer: keychain_txout_changeset,
         locked_outpoints: locked_outpoints_changeset,
+        silent_payments: silent_payments_changeset,
     };
 
     // persist, load and check if same as merged
@@ -0,0 +1,652 @@
+//! PSBT version 2
+//!
+//! Support for [BIP370] PSBTs, where the unsigned transaction is replaced by per-input and
+//! per-output fields so that inputs and outputs can be added after the PSBT is created.
+//!
+//! A [`PsbtV2`] wraps a `rust-bitcoin` [`Psbt`] and dereferences to it, so it can be passed to
+//! [`Wallet::sign`] and [`Wallet::finalize_psbt`] like a version 0 PSBT.
+//!
+//! [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+//! [`Wallet::sign`]: crate::Wallet::sign
+//! [`Wallet::finalize_psbt`]: crate::Wallet::finalize_psbt
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{BitOr, Deref, DerefMut};
+use core::str::FromStr;
+
+use bitcoin::base64::prelude::{Engine as _, BASE64_STANDARD};
+use bitcoin::consensus::encode::{deserialize_partial, serialize, VarInt};
+use bitcoin::hashes::Hash;
+use bitcoin::psbt::{self, raw, Psbt};
+use bitcoin::{
+    absolute, transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+};
+
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const PSBT_GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+const PSBT_GLOBAL_VERSION: u8 = 0xFB;
+
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+const PSBT_OUT_SCRIPT: u8 = 0x04;
+
+/// The version number of [BIP370] PSBTs.
+///
+/// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+pub const PSBT_VERSION_2: u32 = 2;
+
+/// Flags of the `PSBT_GLOBAL_TX_MODIFIABLE` field, telling which parts of a [`PsbtV2`] can
+/// still be changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TxModifiable(u8);
+
+impl TxModifiable {
+    /// Neither inputs nor outputs can be added
+    pub const NONE: Self = Self(0);
+    /// Inputs can be added
+    pub const INPUTS: Self = Self(1);
+    /// Outputs can be added
+    pub const OUTPUTS: Self = Self(1 << 1);
+    /// One of the signatures uses `SIGHASH_SINGLE`
+    pub const SIGHASH_SINGLE: Self = Self(1 << 2);
+
+    /// Create the flags from their byte representation.
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// The byte representation of the flags.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether all the flags of `other` are set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for TxModifiable {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A [BIP370] (version 2) PSBT.
+///
+/// The PSBT is kept as a version 0 [`Psbt`] whose unsigned transaction is rebuilt from the
+/// per-input and per-output fields, and is accessible through [`Deref`]. The version 2 fields
+/// are only written by [`serialize`].
+///
+/// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+/// [`serialize`]: Self::serialize
+#[derive(Debug, Clone, PartialEq)]
+pub struct PsbtV2 {
+    psbt: Psbt,
+    fallback_lock_time: Option<absolute::LockTime>,
+    modifiable: TxModifiable,
+}
+
+impl PsbtV2 {
+    /// Convert a version 0 PSBT, using the lock time of its unsigned transaction as fallback
+    /// lock time.
+    ///
+    /// The inputs and outputs of the PSBT can't be modified unless allowed with
+    /// [`set_modifiable`].
+    ///
+    /// [`set_modifiable`]: Self::set_modifiable
+    pub fn from_v0(psbt: Psbt) -> Self {
+        let fallback_lock_time = Some(psbt.unsigned_tx.lock_time);
+        Self {
+            psbt,
+            fallback_lock_time,
+            modifiable: TxModifiable::NONE,
+        }
+    }
+
+    /// Convert into a version 0 PSBT.
+    pub fn into_v0(mut self) -> Psbt {
+        for input in &mut self.psbt.inputs {
+            let required = required_lock_time_fields(input);
+            input.unknown.retain(|key, _| !required.contains_key(key));
+        }
+        self.psbt
+    }
+
+    /// The lock time used when no input requires one.
+    pub fn fallback_lock_time(&self) -> Option<absolute::LockTime> {
+        self.fallback_lock_time
+    }
+
+    /// The `PSBT_GLOBAL_TX_MODIFIABLE` flags.
+    ///
+    /// The flags set with [`set_modifiable`] are cleared by the signatures already in the PSBT:
+    /// a signature not using `SIGHASH_ANYONECANPAY` forbids adding inputs, and a signature not
+    /// using `SIGHASH_NONE` forbids adding outputs.
+    ///
+    /// [`set_modifiable`]: Self::set_modifiable
+    pub fn modifiable(&self) -> TxModifiable {
+        let mut modifiable = self.modifiable;
+        for sighash_type in self.psbt.inputs.iter().flat_map(signature_sighash_types) {
+            if sighash_type & 0x80 == 0 {
+                modifiable.remove(TxModifiable::INPUTS);
+            }
+            match sighash_type & 0x1f {
+                0x02 => {}
+                0x03 => {
+                    modifiable.remove(TxModifiable::OUTPUTS);
+                    modifiable = modifiable | TxModifiable::SIGHASH_SINGLE;
+                }
+                _ => modifiable.remove(TxModifiable::OUTPUTS),
+            }
+        }
+        modifiable
+    }
+
+    /// Set the `PSBT_GLOBAL_TX_MODIFIABLE` flags.
+    pub fn set_modifiable(&mut self, modifiable: TxModifiable) -> &mut Self {
+        self.modifiable = modifiable;
+        self
+    }
+
+    /// Add an input spending `previous_output`, optionally requiring a minimum lock time.
+    ///
+    /// The lock time of the transaction is recomputed as described in [BIP370].
+    ///
+    /// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+    pub fn add_input(
+        &mut self,
+        previous_output: OutPoint,
+        sequence: Sequence,
+        required_lock_time: Option<absolute::LockTime>,
+        mut input: psbt::Input,
+    ) -> Result<&mut Self, PsbtV2Error> {
+        if !self.modifiable().contains(TxModifiable::INPUTS) {
+            return Err(PsbtV2Error::NotModifiable);
+        }
+        if let Some(lock_time) = required_lock_time {
+            let type_value = match lock_time {
+                absolute::LockTime::Blocks(_) => PSBT_IN_REQUIRED_HEIGHT_LOCKTIME,
+                absolute::LockTime::Seconds(_) => PSBT_IN_REQUIRED_TIME_LOCKTIME,
+            };
+            input.unknown.insert(
+                raw::Key {
+                    type_value,
+                    key: Vec::new(),
+                },
+                lock_time.to_consensus_u32().to_le_bytes().to_vec(),
+            );
+        }
+
+        let mut inputs = self.psbt.inputs.clone();
+        inputs.push(input);
+        let lock_time = compute_lock_time(&inputs, self.fallback_lock_time)?;
+        // Signatures commit to the lock time.
+        if lock_time != self.psbt.unsigned_tx.lock_time
+            && self
+                .psbt
+                .inputs
+                .iter()
+                .any(|input| signature_sighash_types(input).next().is_some())
+        {
+            return Err(PsbtV2Error::LockTimeConflict);
+        }
+
+        self.psbt.inputs = inputs;
+        self.psbt.unsigned_tx.lock_time = lock_time;
+        self.psbt.unsigned_tx.input.push(TxIn {
+            previous_output,
+            sequence,
+            ..Default::default()
+        });
+        Ok(self)
+    }
+
+    /// Add an output.
+    pub fn add_output(
+        &mut self,
+        txout: TxOut,
+        output: psbt::Output,
+    ) -> Result<&mut Self, PsbtV2Error> {
+        if !self.modifiable().contains(TxModifiable::OUTPUTS) {
+            return Err(PsbtV2Error::NotModifiable);
+        }
+        self.psbt.unsigned_tx.output.push(txout);
+        self.psbt.outputs.push(output);
+        Ok(self)
+    }
+
+    /// Serialize as defined in [BIP370].
+    ///
+    /// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+    pub fn serialize(&self) -> Vec<u8> {
+        let tx = &self.psbt.unsigned_tx;
+        let v0 = self.psbt.serialize();
+        let mut bytes = &v0[PSBT_MAGIC.len()..];
+        let global = read_map(&mut bytes).expect("valid PSBT");
+
+        let mut ret = PSBT_MAGIC.to_vec();
+
+        let mut v2_global = vec![
+            (
+                vec![PSBT_GLOBAL_TX_VERSION],
+                tx.version.0.to_le_bytes().to_vec(),
+            ),
+            (
+                vec![PSBT_GLOBAL_INPUT_COUNT],
+                serialize(&VarInt(tx.input.len() as u64)),
+            ),
+            (
+                vec![PSBT_GLOBAL_OUTPUT_COUNT],
+                serialize(&VarInt(tx.output.len() as u64)),
+            ),
+            (
+                vec![PSBT_GLOBAL_TX_MODIFIABLE],
+                vec![self.modifiable().bits()],
+            ),
+        ];
+        if let Some(lock_time) = self.fallback_lock_time {
+            v2_global.push((
+                vec![PSBT_GLOBAL_FALLBACK_LOCKTIME],
+                lock_time.to_consensus_u32().to_le_bytes().to_vec(),
+            ));
+        }
+        v2_global.extend(
+            global.into_iter().filter(|(key, _)| {
+                key[0] != PSBT_GLOBAL_UNSIGNED_TX && key[0] != PSBT_GLOBAL_VERSION
+            }),
+        );
+        v2_global.push((
+            vec![PSBT_GLOBAL_VERSION],
+            PSBT_VERSION_2.to_le_bytes().to_vec(),
+        ));
+        write_map(&mut ret, &v2_global);
+
+        for txin in &tx.input {
+            let mut map = read_map(&mut bytes).expect("valid PSBT");
+            map.push((
+                vec![PSBT_IN_PREVIOUS_TXID],
+                txin.previous_output.txid.to_byte_array().to_vec(),
+            ));
+            map.push((
+                vec![PSBT_IN_OUTPUT_INDEX],
+                txin.previous_output.vout.to_le_bytes().to_vec(),
+            ));
+            map.push((
+                vec![PSBT_IN_SEQUENCE],
+                txin.sequence.to_consensus_u32().to_le_bytes().to_vec(),
+            ));
+            write_map(&mut ret, &map);
+        }
+
+        for txout in &tx.output {
+            let mut map = read_map(&mut bytes).expect("valid PSBT");
+            map.push((
+                vec![PSBT_OUT_AMOUNT],
+                txout.value.to_sat().to_le_bytes().to_vec(),
+            ));
+            map.push((vec![PSBT_OUT_SCRIPT], txout.script_pubkey.to_bytes()));
+            write_map(&mut ret, &map);
+        }
+
+        ret
+    }
+
+    /// Deserialize a [BIP370] PSBT.
+    ///
+    /// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+    pub fn deserialize(mut bytes: &[u8]) -> Result<Self, PsbtV2Error> {
+        bytes = bytes
+            .strip_prefix(PSBT_MAGIC)
+            .ok_or(PsbtV2Error::InvalidMagic)?;
+
+        let mut global = read_map(&mut bytes)?;
+        let version = take_field(&mut global, PSBT_GLOBAL_VERSION)
+            .map(|value| read_u32(&value, "PSBT_GLOBAL_VERSION"))
+            .transpose()?
+            .unwrap_or(0);
+        if version != PSBT_VERSION_2 {
+            return Err(PsbtV2Error::UnsupportedVersion(version));
+        }
+        if take_field(&mut global, PSBT_GLOBAL_UNSIGNED_TX).is_some() {
+            return Err(PsbtV2Error::InvalidField("PSBT_GLOBAL_UNSIGNED_TX"));
+        }
+        let tx_version = take_field(&mut global, PSBT_GLOBAL_TX_VERSION)
+            .ok_or(PsbtV2Error::MissingField("PSBT_GLOBAL_TX_VERSION"))
+            .and_then(|value| read_u32(&value, "PSBT_GLOBAL_TX_VERSION"))?;
+        let fallback_lock_time = take_field(&mut global, PSBT_GLOBAL_FALLBACK_LOCKTIME)
+            .map(|value| read_u32(&value, "PSBT_GLOBAL_FALLBACK_LOCKTIME"))
+            .transpose()?
+            .map(absolute::LockTime::from_consensus);
+        let input_count = take_field(&mut global, PSBT_GLOBAL_INPUT_COUNT)
+            .ok_or(PsbtV2Error::MissingField("PSBT_GLOBAL_INPUT_COUNT"))
+            .and_then(|value| read_count(&value, "PSBT_GLOBAL_INPUT_COUNT"))?;
+        let output_count = take_field(&mut global, PSBT_GLOBAL_OUTPUT_COUNT)
+            .ok_or(PsbtV2Error::MissingField("PSBT_GLOBAL_OUTPUT_COUNT"))
+            .and_then(|value| read_count(&value, "PSBT_GLOBAL_OUTPUT_COUNT"))?;
+        let modifiable = match take_field(&mut global, PSBT_GLOBAL_TX_MODIFIABLE) {
+            Some(value) if value.len() == 1 => TxModifiable::from_bits(value[0]),
+            Some(_) => return Err(PsbtV2Error::InvalidField("PSBT_GLOBAL_TX_MODIFIABLE")),
+            None => TxModifiable::NONE,
+        };
+
+        let mut input_maps = Vec::new();
+        let mut txins = Vec::new();
+        for _ in 0..input_count {
+            let mut map = read_map(&mut bytes)?;
+            let txid = take_field(&mut map, PSBT_IN_PREVIOUS_TXID)
+                .ok_or(PsbtV2Error::MissingField("PSBT_IN_PREVIOUS_TXID"))
+                .and_then(|value| {
+                    Txid::from_slice(&value)
+                        .map_err(|_| PsbtV2Error::InvalidField("PSBT_IN_PREVIOUS_TXID"))
+                })?;
+            let vout = take_field(&mut map, PSBT_IN_OUTPUT_INDEX)
+                .ok_or(PsbtV2Error::MissingField("PSBT_IN_OUTPUT_INDEX"))
+                .and_then(|value| read_u32(&value, "PSBT_IN_OUTPUT_INDEX"))?;
+            let sequence = take_field(&mut map, PSBT_IN_SEQUENCE)
+                .map(|value| read_u32(&value, "PSBT_IN_SEQUENCE"))
+                .transpose()?
+                .map_or(Sequence::MAX, Sequence);
+            txins.push(TxIn {
+                previous_output: OutPoint::new(txid, vout),
+                sequence,
+                ..Default::default()
+            });
+            input_maps.push(map);
+        }
+
+        let mut output_maps = Vec::new();
+        let mut txouts = Vec::new();
+        for _ in 0..output_count {
+            let mut map = read_map(&mut bytes)?;
+            let value = take_field(&mut map, PSBT_OUT_AMOUNT)
+                .ok_or(PsbtV2Error::MissingField("PSBT_OUT_AMOUNT"))
+                .and_then(|value| {
+                    <[u8; 8]>::try_from(value.as_slice())
+                        .map(|value| Amount::from_sat(u64::from_le_bytes(value)))
+                        .map_err(|_| PsbtV2Error::InvalidField("PSBT_OUT_AMOUNT"))
+                })?;
+            let script_pubkey = take_field(&mut map, PSBT_OUT_SCRIPT)
+                .map(ScriptBuf::from_bytes)
+                .ok_or(PsbtV2Error::MissingField("PSBT_OUT_SCRIPT"))?;
+            txouts.push(TxOut {
+                value,
+                script_pubkey,
+            });
+            output_maps.push(map);
+        }
+
+        // Build the equivalent version 0 PSBT and let `rust-bitcoin` parse the common fields.
+        let unsigned_tx = Transaction {
+            version: transaction::Version(tx_version as i32),
+            lock_time: absolute::LockTime::ZERO,
+            input: txins,
+            output: txouts,
+        };
+        let mut v0 = PSBT_MAGIC.to_vec();
+        global.insert(0, (vec![PSBT_GLOBAL_UNSIGNED_TX], serialize(&unsigned_tx)));
+        write_map(&mut v0, &global);
+        for map in input_maps.iter().chain(&output_maps) {
+            write_map(&mut v0, map);
+        }
+        let mut psbt = Psbt::deserialize(&v0).map_err(PsbtV2Error::Psbt)?;
+        psbt.unsigned_tx.lock_time = compute_lock_time(&psbt.inputs, fallback_lock_time)?;
+
+        Ok(Self {
+            psbt,
+            fallback_lock_time,
+            modifiable,
+        })
+    }
+}
+
+impl From<Psbt> for PsbtV2 {
+    fn from(psbt: Psbt) -> Self {
+        Self::from_v0(psbt)
+    }
+}
+
+impl Deref for PsbtV2 {
+    type Target = Psbt;
+
+    fn deref(&self) -> &Psbt {
+        &self.psbt
+    }
+}
+
+impl DerefMut for PsbtV2 {
+    fn deref_mut(&mut self) -> &mut Psbt {
+        &mut self.psbt
+    }
+}
+
+impl fmt::Display for PsbtV2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", BASE64_STANDARD.encode(self.serialize()))
+    }
+}
+
+impl FromStr for PsbtV2 {
+    type Err = PsbtV2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = BASE64_STANDARD
+            .decode(s)
+            .map_err(|_| PsbtV2Error::InvalidBase64)?;
+        Self::deserialize(&bytes)
+    }
+}
+
+/// Error returned when parsing or modifying a [`PsbtV2`].
+#[derive(Debug)]
+pub enum PsbtV2Error {
+    /// The PSBT doesn't start with the PSBT magic bytes
+    InvalidMagic,
+    /// The PSBT is not valid base64
+    InvalidBase64,
+    /// The PSBT ends unexpectedly
+    UnexpectedEof,
+    /// The PSBT is not a version 2 PSBT
+    UnsupportedVersion(u32),
+    /// A required field is missing
+    MissingField(&'static str),
+    /// A field has an invalid value, or is not allowed in version 2 PSBTs
+    InvalidField(&'static str),
+    /// Some inputs require a height-based lock time and others a time-based one, or adding an
+    /// input would change the lock time of signed inputs
+    LockTimeConflict,
+    /// The inputs or outputs of the PSBT can't be modified
+    NotModifiable,
+    /// Error parsing the fields shared with version 0 PSBTs
+    Psbt(psbt::Error),
+}
+
+impl fmt::Display for PsbtV2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "Invalid PSBT magic bytes"),
+            Self::InvalidBase64 => write!(f, "Invalid PSBT base64 encoding"),
+            Self::UnexpectedEof => write!(f, "Unexpected end of PSBT"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported PSBT version: {version}")
+            }
+            Self::MissingField(field) => write!(f, "Missing PSBT field: {field}"),
+            Self::InvalidField(field) => write!(f, "Invalid PSBT field: {field}"),
+            Self::LockTimeConflict => write!(f, "Conflicting input lock time requirements"),
+            Self::NotModifiable => write!(f, "PSBT is not modifiable"),
+            Self::Psbt(err) => write!(f, "Invalid PSBT: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for PsbtV2Error {}
+
+/// A PSBT map as a list of raw keys (key type and key data) and values.
+type Map = Vec<(Vec<u8>, Vec<u8>)>;
+
+fn read_bytes(bytes: &mut &[u8]) -> Result<Vec<u8>, PsbtV2Error> {
+    let (VarInt(len), consumed) =
+        deserialize_partial::<VarInt>(bytes).map_err(|_| PsbtV2Error::UnexpectedEof)?;
+    let data = bytes
+        .get(consumed..)
+        .and_then(|rest| rest.get(..len as usize))
+        .ok_or(PsbtV2Error::UnexpectedEof)?;
+    let data = data.to_vec();
+    *bytes = &bytes[consumed + data.len()..];
+    Ok(data)
+}
+
+fn read_map(bytes: &mut &[u8]) -> Result<Map, PsbtV2Error> {
+    let mut map = Vec::new();
+    loop {
+        let key = read_bytes(bytes)?;
+        if key.is_empty() {
+            return Ok(map);
+        }
+        let value = read_bytes(bytes)?;
+        map.push((key, value));
+    }
+}
+
+fn write_map(out: &mut Vec<u8>, map: &Map) {
+    for (key, value) in map {
+        out.extend(serialize(&VarInt(key.len() as u64)));
+        out.extend(key);
+        out.extend(serialize(&VarInt(value.len() as u64)));
+        out.extend(value);
+    }
+    out.push(0x00);
+}
+
+/// Remove the value of the field of type `type_value`, without key data, from `map`.
+fn take_field(map: &mut Map, type_value: u8) -> Option<Vec<u8>> {
+    let position = map.iter().position(|(key, _)| key[..] == [type_value])?;
+    Some(map.remove(position).1)
+}
+
+fn read_u32(value: &[u8], field: &'static str) -> Result<u32, PsbtV2Error> {
+    <[u8; 4]>::try_from(value)
+        .map(u32::from_le_bytes)
+        .map_err(|_| PsbtV2Error::InvalidField(field))
+}
+
+fn read_count(value: &[u8], field: &'static str) -> Result<u64, PsbtV2Error> {
+    match deserialize_partial::<VarInt>(value) {
+        Ok((VarInt(count), consumed)) if consumed == value.len() => Ok(count),
+        _ => Err(PsbtV2Error::InvalidField(field)),
+    }
+}
+
+/// The lock time required by `input`, as the pair of its time-based and height-based
+/// requirements.
+///
+/// Fails if a requirement is malformed or out of the range of its lock time kind.
+fn required_lock_time(input: &psbt::Input) -> Result<(Option<u32>, Option<u32>), PsbtV2Error> {
+    let field = |type_value, name, is_valid: fn(u32) -> bool| {
+        input
+            .unknown
+            .get(&raw::Key {
+                type_value,
+                key: Vec::new(),
+            })
+            .map(|value| {
+                read_u32(value, name).and_then(|lock_time| {
+                    if is_valid(lock_time) {
+                        Ok(lock_time)
+                    } else {
+                        Err(PsbtV2Error::InvalidField(name))
+                    }
+                })
+            })
+            .transpose()
+    };
+    Ok((
+        field(
+            PSBT_IN_REQUIRED_TIME_LOCKTIME,
+            "PSBT_IN_REQUIRED_TIME_LOCKTIME",
+            |lock_time| lock_time >= absolute::LOCK_TIME_THRESHOLD,
+        )?,
+        field(
+            PSBT_IN_REQUIRED_HEIGHT_LOCKTIME,
+            "PSBT_IN_REQUIRED_HEIGHT_LOCKTIME",
+            |lock_time| lock_time < absolute::LOCK_TIME_THRESHOLD,
+        )?,
+    ))
+}
+
+/// The required lock time fields of `input`, which are kept when the input is finalized.
+pub(crate) fn required_lock_time_fields(input: &psbt::Input) -> BTreeMap<raw::Key, Vec<u8>> {
+    input
+        .unknown
+        .iter()
+        .filter(|(key, _)| {
+            key.type_value == PSBT_IN_REQUIRED_TIME_LOCKTIME
+                || key.type_value == PSBT_IN_REQUIRED_HEIGHT_LOCKTIME
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// Compute the lock time of the transaction as described in BIP370.
+fn compute_lock_time(
+    inputs: &[psbt::Input],
+    fallback: Option<absolute::LockTime>,
+) -> Result<absolute::LockTime, PsbtV2Error> {
+    let required: Vec<(Option<u32>, Option<u32>)> = inputs
+        .iter()
+        .map(required_lock_time)
+        .filter(|required| !matches!(required, Ok((None, None))))
+        .collect::<Result<_, _>>()?;
+    if required.is_empty() {
+        return Ok(fallback.unwrap_or(absolute::LockTime::ZERO));
+    }
+    // Prefer a height-based lock time when every input allows it.
+    let heights: Option<Vec<u32>> = required.iter().map(|(_, height)| *height).collect();
+    let times: Option<Vec<u32>> = required.iter().map(|(time, _)| *time).collect();
+    let lock_time = match (heights, times) {
+        (Some(heights), _) => heights.into_iter().max(),
+        (None, Some(times)) => times.into_iter().max(),
+        (None, None) => return Err(PsbtV2Error::LockTimeConflict),
+    };
+    Ok(absolute::LockTime::from_consensus(
+        lock_time.expect("at least one requirement"),
+    ))
+}
+
+/// Sighash types of the signatures of `input`. Finalized inputs are assumed to be signed with
+/// `SIGHASH_ALL`.
+fn signature_sighash_types(input: &psbt::Input) -> impl Iterator<Item = u32> + '_ {
+    let finalized = input.final_script_sig.is_some() || input.final_script_witness.is_some();
+    input
+        .partial_sigs
+        .values()
+        .map(|sig| sig.sighash_type.to_u32())
+        .chain(input.tap_key_sig.iter().map(|sig| sig.sighash_type as u32))
+        .chain(
+            input
+                .tap_script_sigs
+                .values()
+                .map(|sig| sig.sighash_type as u32),
+        )
+        .chain(finalized.then_some(0x01))
+}
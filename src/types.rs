@@ -10,15 +10,18 @@
 // licenses.
 
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use chain::{ChainPosition, ConfirmationBlockTime};
 use core::convert::AsRef;
 use core::fmt;
 
 use bitcoin::transaction::{OutPoint, Sequence, TxOut};
-use bitcoin::{psbt, Weight};
+use bitcoin::{psbt, Amount, Weight};
 
 use serde::{Deserialize, Serialize};
 
+use crate::wallet::silent_payments::SilentPaymentOutput;
+
 /// Types of keychains
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub enum KeychainKind {
@@ -75,6 +78,26 @@ pub struct LocalOutput {
     pub chain_position: ChainPosition<ConfirmationBlockTime>,
 }
 
+/// An output received by the silent payment address of a [`Wallet`].
+///
+/// Silent payment outputs don't belong to a keychain of the wallet: they are spent with its
+/// spend key, tweaked by [`SilentPaymentOutput::tweak`].
+///
+/// [`Wallet`]: crate::Wallet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SilentPaymentUtxo {
+    /// Reference to a transaction output
+    pub outpoint: OutPoint,
+    /// Transaction output
+    pub txout: TxOut,
+    /// Whether this UTXO is spent or not
+    pub is_spent: bool,
+    /// The tweak and label of the output
+    pub output: SilentPaymentOutput,
+    /// The position of the output in the blockchain.
+    pub chain_position: ChainPosition<ConfirmationBlockTime>,
+}
+
 /// A [`Utxo`] with its `satisfaction_weight`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WeightedUtxo {
@@ -86,6 +109,26 @@ pub struct WeightedUtxo {
     pub satisfaction_weight: Weight,
     /// The UTXO
     pub utxo: Utxo,
+    /// The fee needed to bring the unconfirmed ancestors of the UTXO up to the target fee rate.
+    ///
+    /// Spending an output of a transaction that pays a low fee rate lowers the fee rate miners
+    /// get from the new transaction, as they have to mine its ancestors along with it. Coin
+    /// selection adds this fee to the cost of spending the UTXO, like Bitcoin Core's "bump fee".
+    /// The wallet sets it when creating a transaction, for the UTXOs whose ancestors it knows.
+    /// It is zero for UTXOs created with [`WeightedUtxo::new`].
+    pub bump_fee: Amount,
+}
+
+impl WeightedUtxo {
+    /// Create a new `WeightedUtxo` spending `utxo` with the given `satisfaction_weight` and no
+    /// `bump_fee`.
+    pub fn new(satisfaction_weight: Weight, utxo: Utxo) -> Self {
+        Self {
+            satisfaction_weight,
+            utxo,
+            bump_fee: Amount::ZERO,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -93,6 +136,8 @@ pub struct WeightedUtxo {
 pub enum Utxo {
     /// A UTXO owned by the local wallet.
     Local(LocalOutput),
+    /// A silent payment output received by the local wallet.
+    SilentPayment(SilentPaymentUtxo),
     /// A UTXO owned by another wallet.
     Foreign {
         /// The location of the output.
@@ -110,6 +155,7 @@ impl Utxo {
     pub fn outpoint(&self) -> OutPoint {
         match &self {
             Utxo::Local(local) => local.outpoint,
+            Utxo::SilentPayment(output) => output.outpoint,
             Utxo::Foreign { outpoint, .. } => *outpoint,
         }
     }
@@ -118,6 +164,7 @@ impl Utxo {
     pub fn txout(&self) -> &TxOut {
         match &self {
             Utxo::Local(local) => &local.txout,
+            Utxo::SilentPayment(output) => &output.txout,
             Utxo::Foreign {
                 outpoint,
                 psbt_input,
@@ -135,12 +182,26 @@ impl Utxo {
     /// Get the sequence number if an explicit sequence number has to be set for this input.
     pub fn sequence(&self) -> Option<Sequence> {
         match self {
-            Utxo::Local(_) => None,
+            Utxo::Local(_) | Utxo::SilentPayment(_) => None,
             Utxo::Foreign { sequence, .. } => Some(*sequence),
         }
     }
 }
 
+/// The largest amount a wallet can send to a single output, returned by
+/// [`Wallet::max_spendable`].
+///
+/// [`Wallet::max_spendable`]: crate::Wallet::max_spendable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaxSpendable {
+    /// Amount received by the output
+    pub amount: Amount,
+    /// Fee paid by the transaction
+    pub fee: Amount,
+    /// UTXOs spent by the transaction
+    pub utxos: Vec<Utxo>,
+}
+
 /// Index out of bounds error.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct IndexOutOfBoundsError {
@@ -5,6 +5,7 @@ use miniscript::{Descriptor, DescriptorPublicKey};
 use serde::{Deserialize, Serialize};
 
 use crate::locked_outpoints;
+use crate::silent_payments;
 
 type IndexedTxGraphChangeSet =
     indexed_tx_graph::ChangeSet<ConfirmationBlockTime, keychain_txout::ChangeSet>;
@@ -150,6 +151,9 @@ pub struct ChangeSet {
     /// Changes to locked outpoints.
     #[serde(default)]
     pub locked_outpoints: locked_outpoints::ChangeSet,
+    /// Changes to the silent payments received by the wallet.
+    #[serde(default)]
+    pub silent_payments: silent_payments::ChangeSet,
 }
 
 impl Merge for ChangeSet {
@@ -180,6 +184,7 @@ impl Merge for ChangeSet {
 
         // merge locked outpoints
         self.locked_outpoints.merge(other.locked_outpoints);
+        self.silent_payments.merge(other.silent_payments);
 
         Merge::merge(&mut self.local_chain, other.local_chain);
         Merge::merge(&mut self.tx_graph, other.tx_graph);
@@ -194,6 +199,7 @@ impl Merge for ChangeSet {
             && self.tx_graph.is_empty()
             && self.indexer.is_empty()
             && self.locked_outpoints.is_empty()
+            && self.silent_payments.is_empty()
     }
 }
 
@@ -205,6 +211,14 @@ impl ChangeSet {
     pub const WALLET_TABLE_NAME: &'static str = "bdk_wallet";
     /// Name of table to store wallet locked outpoints.
     pub const WALLET_OUTPOINT_LOCK_TABLE_NAME: &'static str = "bdk_wallet_locked_outpoints";
+    /// Name of table to store wallet silent payment keys.
+    pub const WALLET_SP_KEYS_TABLE_NAME: &'static str = "bdk_wallet_silent_payment_keys";
+    /// Name of table to store wallet silent payment labels.
+    pub const WALLET_SP_LABELS_TABLE_NAME: &'static str = "bdk_wallet_silent_payment_labels";
+    /// Name of table to store wallet silent payment outputs.
+    pub const WALLET_SP_OUTPUTS_TABLE_NAME: &'static str = "bdk_wallet_silent_payment_outputs";
+    /// Name of table to store the silent payment addresses paid by the wallet.
+    pub const WALLET_SP_SENT_TABLE_NAME: &'static str = "bdk_wallet_silent_payment_sent";
 
     /// Get v0 sqlite [ChangeSet] schema
     pub fn schema_v0() -> alloc::string::String {
@@ -231,12 +245,54 @@ impl ChangeSet {
         )
     }
 
+    /// Get v2 sqlite [`ChangeSet`] schema. Schema v2 adds tables for the silent payment keys,
+    /// labels and received outputs.
+    pub fn schema_v2() -> alloc::string::String {
+        format!(
+            "CREATE TABLE {} ( \
+                id INTEGER PRIMARY KEY NOT NULL CHECK (id = 0), \
+                scan_key TEXT NOT NULL, \
+                spend_key TEXT NOT NULL \
+                ) STRICT; \
+            CREATE TABLE {} ( \
+                label INTEGER PRIMARY KEY NOT NULL \
+                ) STRICT; \
+            CREATE TABLE {} ( \
+                txid TEXT NOT NULL, \
+                vout INTEGER NOT NULL, \
+                tweak TEXT NOT NULL, \
+                label INTEGER, \
+                PRIMARY KEY(txid, vout) \
+                ) STRICT;",
+            Self::WALLET_SP_KEYS_TABLE_NAME,
+            Self::WALLET_SP_LABELS_TABLE_NAME,
+            Self::WALLET_SP_OUTPUTS_TABLE_NAME,
+        )
+    }
+
+    /// Get v3 sqlite [`ChangeSet`] schema. Schema v3 adds a table for the silent payment
+    /// addresses paid by the wallet.
+    pub fn schema_v3() -> alloc::string::String {
+        format!(
+            "CREATE TABLE {} ( \
+                script_pubkey BLOB PRIMARY KEY NOT NULL, \
+                address TEXT NOT NULL \
+                ) STRICT;",
+            Self::WALLET_SP_SENT_TABLE_NAME,
+        )
+    }
+
     /// Initialize sqlite tables for wallet tables.
     pub fn init_sqlite_tables(db_tx: &chain::rusqlite::Transaction) -> chain::rusqlite::Result<()> {
         crate::rusqlite_impl::migrate_schema(
             db_tx,
             Self::WALLET_SCHEMA_NAME,
-            &[&Self::schema_v0(), &Self::schema_v1()],
+            &[
+                &Self::schema_v0(),
+                &Self::schema_v1(),
+                &Self::schema_v2(),
+                &Self::schema_v3(),
+            ],
         )?;
 
         bdk_chain::local_chain::ChangeSet::init_sqlite_tables(db_tx)?;
@@ -293,6 +349,89 @@ impl ChangeSet {
             locked_outpoints.insert(outpoint, true);
         }
 
+        // Select silent payment keys, labels and outputs.
+        fn parse_key<K: core::str::FromStr<Err = bitcoin::secp256k1::Error>>(
+            index: usize,
+            key: alloc::string::String,
+        ) -> chain::rusqlite::Result<K> {
+            key.parse().map_err(|err| {
+                chain::rusqlite::Error::FromSqlConversionFailure(
+                    index,
+                    chain::rusqlite::types::Type::Text,
+                    alloc::boxed::Box::new(err),
+                )
+            })
+        }
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT scan_key, spend_key FROM {}",
+            Self::WALLET_SP_KEYS_TABLE_NAME,
+        ))?;
+        changeset.silent_payments.keys = stmt
+            .query_row([], |row| {
+                Ok((
+                    parse_key(0, row.get("scan_key")?)?,
+                    parse_key(1, row.get("spend_key")?)?,
+                ))
+            })
+            .optional()?;
+
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT label FROM {}",
+            Self::WALLET_SP_LABELS_TABLE_NAME,
+        ))?;
+        for label in stmt.query_map([], |row| row.get::<_, u32>("label"))? {
+            changeset.silent_payments.labels.insert(label?);
+        }
+
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT txid, vout, tweak, label FROM {}",
+            Self::WALLET_SP_OUTPUTS_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, Impl<Txid>>("txid")?,
+                row.get::<_, u32>("vout")?,
+                silent_payments::SilentPaymentOutput {
+                    tweak: parse_key(2, row.get("tweak")?)?,
+                    label: row.get("label")?,
+                },
+            ))
+        })?;
+        for row in rows {
+            let (Impl(txid), vout, output) = row?;
+            changeset
+                .silent_payments
+                .outputs
+                .insert(OutPoint::new(txid, vout), output);
+        }
+
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT script_pubkey, address FROM {}",
+            Self::WALLET_SP_SENT_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            let address: alloc::string::String = row.get("address")?;
+            Ok((
+                row.get::<_, Impl<bitcoin::ScriptBuf>>("script_pubkey")?,
+                address
+                    .parse::<silent_payments::SilentPaymentAddress>()
+                    .map_err(|err| {
+                        chain::rusqlite::Error::FromSqlConversionFailure(
+                            1,
+                            chain::rusqlite::types::Type::Text,
+                            alloc::boxed::Box::new(err),
+                        )
+                    })?,
+            ))
+        })?;
+        for row in rows {
+            let (Impl(script_pubkey), address) = row?;
+            changeset
+                .silent_payments
+                .sent
+                .insert(script_pubkey, address);
+        }
+
         changeset.local_chain = local_chain::ChangeSet::from_sqlite(db_tx)?;
         changeset.tx_graph = tx_graph::ChangeSet::<_>::from_sqlite(db_tx)?;
         changeset.indexer = keychain_txout::ChangeSet::from_sqlite(db_tx)?;
@@ -305,6 +444,7 @@ impl ChangeSet {
         &self,
         db_tx: &chain::rusqlite::Transaction,
     ) -> chain::rusqlite::Result<()> {
+        use alloc::string::ToString;
         use chain::rusqlite::named_params;
         use chain::Impl;
 
@@ -365,6 +505,48 @@ impl ChangeSet {
             }
         }
 
+        // Insert silent payment keys, labels and outputs.
+        let mut stmt = db_tx.prepare_cached(&format!(
+            "INSERT OR IGNORE INTO {}(id, scan_key, spend_key) VALUES(:id, :scan_key, :spend_key)",
+            Self::WALLET_SP_KEYS_TABLE_NAME,
+        ))?;
+        if let Some((scan_key, spend_key)) = self.silent_payments.keys {
+            stmt.execute(named_params! {
+                ":id": 0,
+                ":scan_key": scan_key.to_string(),
+                ":spend_key": spend_key.to_string(),
+            })?;
+        }
+        let mut stmt = db_tx.prepare_cached(&format!(
+            "INSERT OR IGNORE INTO {}(label) VALUES(:label)",
+            Self::WALLET_SP_LABELS_TABLE_NAME,
+        ))?;
+        for label in &self.silent_payments.labels {
+            stmt.execute(named_params! { ":label": label })?;
+        }
+        let mut stmt = db_tx.prepare_cached(&format!(
+            "INSERT OR IGNORE INTO {}(txid, vout, tweak, label) VALUES(:txid, :vout, :tweak, :label)",
+            Self::WALLET_SP_OUTPUTS_TABLE_NAME,
+        ))?;
+        for (outpoint, output) in &self.silent_payments.outputs {
+            stmt.execute(named_params! {
+                ":txid": Impl(outpoint.txid),
+                ":vout": outpoint.vout,
+                ":tweak": output.tweak.display_secret().to_string(),
+                ":label": output.label,
+            })?;
+        }
+        let mut stmt = db_tx.prepare_cached(&format!(
+            "INSERT OR IGNORE INTO {}(script_pubkey, address) VALUES(:script_pubkey, :address)",
+            Self::WALLET_SP_SENT_TABLE_NAME,
+        ))?;
+        for (script_pubkey, address) in &self.silent_payments.sent {
+            stmt.execute(named_params! {
+                ":script_pubkey": Impl(script_pubkey.clone()),
+                ":address": address.to_string(),
+            })?;
+        }
+
         self.local_chain.persist_to_sqlite(db_tx)?;
         self.tx_graph.persist_to_sqlite(db_tx)?;
         self.indexer.persist_to_sqlite(db_tx)?;
@@ -417,3 +599,12 @@ impl From<locked_outpoints::ChangeSet> for ChangeSet {
         }
     }
 }
+
+impl From<silent_payments::ChangeSet> for ChangeSet {
+    fn from(silent_payments: silent_payments::ChangeSet) -> Self {
+        Self {
+            silent_payments,
+            ..Default::default()
+        }
+    }
+}
@@ -75,11 +75,11 @@
 //!
 //!         let excess = decide_change(remaining_amount, fee_rate, drain_script);
 //!
-//!         Ok(CoinSelectionResult {
-//!             selected: all_utxos_selected,
-//!             fee_amount: additional_fees,
+//!         Ok(CoinSelectionResult::new(
+//!             all_utxos_selected,
+//!             additional_fees,
 //!             excess,
-//!         })
+//!         ))
 //!     }
 //! }
 //!
@@ -106,10 +106,11 @@ use crate::Utxo;
 use crate::WeightedUtxo;
 use bitcoin::{Amount, FeeRate, SignedAmount};
 
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::vec::Vec;
 use bitcoin::consensus::encode::serialize;
 use bitcoin::TxIn;
-use bitcoin::{Script, Weight};
+use bitcoin::{OutPoint, Script, ScriptBuf, Weight};
 
 use core::convert::TryInto;
 use core::fmt::{self, Formatter};
@@ -173,9 +174,37 @@ pub struct CoinSelectionResult {
     pub fee_amount: Amount,
     /// Remaining amount after deducing fees and outgoing outputs
     pub excess: Excess,
+    /// Total weight of the selected utxos, including their satisfaction
+    ///
+    /// This is filled in from the weights of the UTXOs passed to coin selection by the
+    /// [`Wallet`](super::Wallet) and by the algorithms comparing selections, such as
+    /// [`LowestWasteCoinSelection`]. Implementors of [`CoinSelectionAlgorithm`] can leave it to
+    /// zero.
+    pub input_weight: Weight,
+    /// Names of the coin selection algorithms which made the selection
+    ///
+    /// The algorithm passed to the wallet comes first, followed by the ones it delegated to, as
+    /// in `["BranchAndBoundCoinSelection", "SingleRandomDraw"]` when branch and bound falls back
+    /// to single random draw. Algorithms which don't record their name are left out.
+    pub algorithms: Vec<&'static str>,
 }
 
 impl CoinSelectionResult {
+    /// Create a new result selecting the `selected` UTXOs, paying `fee_amount` for them and
+    /// leaving `excess`.
+    ///
+    /// The [`input_weight`](Self::input_weight) is zero and no
+    /// [`algorithms`](Self::algorithms) are recorded.
+    pub fn new(selected: Vec<Utxo>, fee_amount: Amount, excess: Excess) -> Self {
+        Self {
+            selected,
+            fee_amount,
+            excess,
+            input_weight: Weight::ZERO,
+            algorithms: Vec::new(),
+        }
+    }
+
     /// The total value of the inputs selected.
     pub fn selected_amount(&self) -> Amount {
         self.selected.iter().map(|u| u.txout().value).sum()
@@ -186,11 +215,191 @@ impl CoinSelectionResult {
         self.selected
             .iter()
             .filter_map(|u| match u {
-                Utxo::Local(_) => Some(u.txout().value),
+                Utxo::Local(_) | Utxo::SilentPayment(_) => Some(u.txout().value),
                 _ => None,
             })
             .sum()
     }
+
+    /// The waste of the selection, as defined by Bitcoin Core
+    ///
+    /// The waste is the fee paid for the selected inputs minus the fee their
+    /// [`input_weight`](Self::input_weight) would cost at the `long_term_feerate`, plus the cost
+    /// of creating and later spending the change output if there is one, or the excess given up
+    /// to fees otherwise. `change_spend_weight` is the
+    /// weight of the input spending the change output, including its satisfaction.
+    ///
+    /// A lower waste is better: when fees are higher than `long_term_feerate` the waste favors
+    /// selections with fewer inputs, when they are lower it favors consolidating more inputs.
+    pub fn waste(&self, long_term_feerate: FeeRate, change_spend_weight: Weight) -> SignedAmount {
+        let input_waste = self.fee_amount.to_signed().expect("signed amount")
+            - (long_term_feerate * self.input_weight)
+                .to_signed()
+                .expect("signed amount");
+        let change_waste = match self.excess {
+            Excess::Change { fee, .. } => fee + long_term_feerate * change_spend_weight,
+            Excess::NoChange {
+                remaining_amount, ..
+            } => remaining_amount,
+        };
+        input_waste + change_waste.to_signed().expect("signed amount")
+    }
+
+    // Record the name of the algorithm which made the selection, or delegated it to the ones
+    // already recorded.
+    fn delegated_by(mut self, algorithm: &'static str) -> Self {
+        self.algorithms.insert(0, algorithm);
+        self
+    }
+
+    // Set `input_weight` from the satisfaction weights of the selected UTXOs.
+    pub(crate) fn fill_input_weight(&mut self, satisfaction_weight: impl Fn(OutPoint) -> Weight) {
+        self.input_weight = self
+            .selected
+            .iter()
+            .map(|utxo| {
+                TxIn::default()
+                    .segwit_weight()
+                    .checked_add(satisfaction_weight(utxo.outpoint()))
+                    .expect("`Weight` addition should not cause an integer overflow")
+            })
+            .sum();
+    }
+}
+
+/// Why a UTXO isn't available to coin selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusionReason {
+    /// Only the UTXOs added manually are spent, see
+    /// [`TxBuilder::manually_selected_only`](super::tx_builder::TxBuilder::manually_selected_only)
+    ManuallySelectedOnly,
+    /// The outpoint is locked, see [`Wallet::lock_outpoint`](super::Wallet::lock_outpoint)
+    Locked,
+    /// The output of a coinbase transaction which isn't mature yet
+    Immature,
+    /// The UTXO doesn't satisfy the
+    /// [`ChangeSpendPolicy`](super::tx_builder::ChangeSpendPolicy)
+    ChangePolicy,
+    /// The UTXO was marked as unspendable, see
+    /// [`TxBuilder::add_unspendable`](super::tx_builder::TxBuilder::add_unspendable)
+    Unspendable,
+    /// The UTXO doesn't have enough confirmations: only confirmed UTXOs can be spent when bumping
+    /// the fee or creating a TRUC transaction, and
+    /// [`TxBuilder::exclude_below_confirmations`](super::tx_builder::TxBuilder::exclude_below_confirmations)
+    /// sets a minimum number of confirmations
+    Unconfirmed,
+    /// The spending policy of the descriptor of the UTXO can't be satisfied, for example because
+    /// it requires a policy path which wasn't given, see
+    /// [`TxBuilder::policy_path`](super::tx_builder::TxBuilder::policy_path)
+    Policy,
+    /// Spending the UTXO costs at least its value at the fee rate, so coin selection didn't
+    /// pick it
+    NegativeEffectiveValue,
+}
+
+impl fmt::Display for ExclusionReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ManuallySelectedOnly => write!(f, "only manually selected UTXOs are spent"),
+            Self::Locked => write!(f, "locked"),
+            Self::Immature => write!(f, "immature coinbase output"),
+            Self::ChangePolicy => write!(f, "excluded by the change spend policy"),
+            Self::Unspendable => write!(f, "marked as unspendable"),
+            Self::Unconfirmed => write!(f, "unconfirmed"),
+            Self::Policy => write!(f, "excluded by the spending policy"),
+            Self::NegativeEffectiveValue => {
+                write!(f, "costs at least its value at this fee rate")
+            }
+        }
+    }
+}
+
+/// What happened to a UTXO during coin selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtxoStatus {
+    /// The UTXO is spent by the transaction
+    Selected,
+    /// The UTXO was available to coin selection but not selected
+    NotSelected,
+    /// The UTXO wasn't available to coin selection
+    Excluded(ExclusionReason),
+}
+
+/// A UTXO considered for a transaction, see [`CoinSelectionExplanation`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplainedUtxo {
+    /// The outpoint of the UTXO
+    pub outpoint: OutPoint,
+    /// The value of the UTXO
+    pub value: Amount,
+    /// The value of the UTXO minus the fee for spending it, including the fee for its unconfirmed
+    /// ancestors, at the fee rate of the coin selection
+    pub effective_value: SignedAmount,
+    /// What happened to the UTXO
+    pub status: UtxoStatus,
+}
+
+impl ExplainedUtxo {
+    pub(crate) fn new(weighted_utxo: WeightedUtxo, fee_rate: FeeRate, status: UtxoStatus) -> Self {
+        let output_group = OutputGroup::new(weighted_utxo, fee_rate);
+        Self {
+            outpoint: output_group.weighted_utxo.utxo.outpoint(),
+            value: output_group.weighted_utxo.utxo.txout().value,
+            effective_value: output_group.effective_value,
+            status,
+        }
+    }
+}
+
+/// Explanation of the coin selection of a transaction
+///
+/// Returned by [`TxBuilder::finish_explained`](super::tx_builder::TxBuilder::finish_explained)
+/// to find out why some UTXOs were or weren't spent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoinSelectionExplanation {
+    /// The UTXOs available to coin selection, the ones which must be spent first, followed by the
+    /// unspent outputs of the wallet which weren't available
+    pub utxos: Vec<ExplainedUtxo>,
+    /// The fee rate used for coin selection
+    ///
+    /// This is zero when the fee is subtracted from the recipients or is an absolute amount.
+    pub fee_rate: FeeRate,
+    /// The amount the effective values of the selected UTXOs must reach: the value of the outputs
+    /// and the fees already accumulated from adding them and the transaction’s header
+    pub target_amount: Amount,
+    /// The coin selection algorithms which selected the UTXOs, see
+    /// [`CoinSelectionResult::algorithms`]
+    ///
+    /// This is empty if the coin selection failed or wasn't run.
+    pub algorithms: Vec<&'static str>,
+}
+
+impl Default for CoinSelectionExplanation {
+    fn default() -> Self {
+        Self {
+            utxos: Vec::new(),
+            fee_rate: FeeRate::ZERO,
+            target_amount: Amount::ZERO,
+            algorithms: Vec::new(),
+        }
+    }
+}
+
+impl CoinSelectionExplanation {
+    /// The UTXOs spent by the transaction.
+    pub fn selected(&self) -> impl Iterator<Item = &ExplainedUtxo> {
+        self.utxos
+            .iter()
+            .filter(|utxo| utxo.status == UtxoStatus::Selected)
+    }
+
+    /// The UTXOs which weren't available to coin selection, with the reason.
+    pub fn excluded(&self) -> impl Iterator<Item = (&ExplainedUtxo, ExclusionReason)> {
+        self.utxos.iter().filter_map(|utxo| match utxo.status {
+            UtxoStatus::Excluded(reason) => Some((utxo, reason)),
+            _ => None,
+        })
+    }
 }
 
 /// Trait for generalized coin selection algorithms
@@ -250,7 +459,13 @@ impl CoinSelectionAlgorithm for LargestFirstCoinSelection {
                 .chain(optional_utxos.into_iter().rev().map(|utxo| (false, utxo)))
         };
 
-        select_sorted_utxos(utxos, fee_rate, target_amount, drain_script)
+        select_sorted_utxos(
+            utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            "LargestFirstCoinSelection",
+        )
     }
 }
 
@@ -279,6 +494,7 @@ impl CoinSelectionAlgorithm for OldestFirstCoinSelection {
         let utxos = {
             optional_utxos.sort_unstable_by_key(|wu| match &wu.utxo {
                 Utxo::Local(local) => (false, Some(local.chain_position)),
+                Utxo::SilentPayment(output) => (false, Some(output.chain_position)),
                 Utxo::Foreign { .. } => (true, None),
             });
 
@@ -288,7 +504,13 @@ impl CoinSelectionAlgorithm for OldestFirstCoinSelection {
                 .chain(optional_utxos.into_iter().map(|utxo| (false, utxo)))
         };
 
-        select_sorted_utxos(utxos, fee_rate, target_amount, drain_script)
+        select_sorted_utxos(
+            utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            "OldestFirstCoinSelection",
+        )
     }
 }
 
@@ -298,10 +520,7 @@ impl CoinSelectionAlgorithm for OldestFirstCoinSelection {
 /// - `fee_rate`: required fee rate for the current selection
 /// - `drain_script`: script to consider change creation
 pub fn decide_change(remaining_amount: Amount, fee_rate: FeeRate, drain_script: &Script) -> Excess {
-    // drain_output_len = size(len(script_pubkey)) + len(script_pubkey) + size(output_value)
-    let drain_output_len = serialize(drain_script).len() + 8usize;
-    let change_fee =
-        fee_rate * Weight::from_vb(drain_output_len as u64).expect("overflow occurred");
+    let change_fee = change_fee(fee_rate, drain_script);
     let drain_val = remaining_amount.checked_sub(change_fee).unwrap_or_default();
 
     if drain_val.is_dust(drain_script) {
@@ -319,11 +538,19 @@ pub fn decide_change(remaining_amount: Amount, fee_rate: FeeRate, drain_script:
     }
 }
 
+// The fee for adding a change output with `drain_script` at `fee_rate`.
+fn change_fee(fee_rate: FeeRate, drain_script: &Script) -> Amount {
+    // drain_output_len = size(len(script_pubkey)) + len(script_pubkey) + size(output_value)
+    let drain_output_len = serialize(drain_script).len() + 8usize;
+    fee_rate * Weight::from_vb(drain_output_len as u64).expect("overflow occurred")
+}
+
 fn select_sorted_utxos(
     utxos: impl Iterator<Item = (bool, WeightedUtxo)>,
     fee_rate: FeeRate,
     target_amount: Amount,
     drain_script: &Script,
+    algorithm: &'static str,
 ) -> Result<CoinSelectionResult, InsufficientFunds> {
     let mut selected_amount = Amount::ZERO;
     let mut fee_amount = Amount::ZERO;
@@ -332,11 +559,11 @@ fn select_sorted_utxos(
             (&mut selected_amount, &mut fee_amount),
             |(selected_amount, fee_amount), (must_use, weighted_utxo)| {
                 if must_use || **selected_amount < target_amount + **fee_amount {
-                    **fee_amount += fee_rate
-                        * TxIn::default()
-                            .segwit_weight()
-                            .checked_add(weighted_utxo.satisfaction_weight)
-                            .expect("`Weight` addition should not cause an integer overflow");
+                    let weight = TxIn::default()
+                        .segwit_weight()
+                        .checked_add(weighted_utxo.satisfaction_weight)
+                        .expect("`Weight` addition should not cause an integer overflow");
+                    **fee_amount += fee_rate * weight + weighted_utxo.bump_fee;
                     **selected_amount += weighted_utxo.utxo.txout().value;
                     Some(weighted_utxo.utxo)
                 } else {
@@ -358,18 +585,17 @@ fn select_sorted_utxos(
 
     let excess = decide_change(remaining_amount, fee_rate, drain_script);
 
-    Ok(CoinSelectionResult {
-        selected,
-        fee_amount,
-        excess,
-    })
+    Ok(CoinSelectionResult::new(selected, fee_amount, excess).delegated_by(algorithm))
 }
 
 #[derive(Debug, Clone)]
 // Adds fee information to an UTXO.
 struct OutputGroup {
     weighted_utxo: WeightedUtxo,
-    // Amount of fees for spending a certain utxo, calculated using a certain FeeRate
+    // The weight of the input spending the UTXO, including its satisfaction
+    weight: Weight,
+    // Amount of fees for spending a certain utxo, calculated using a certain FeeRate, plus the
+    // bump fee of its unconfirmed ancestors
     fee: Amount,
     // The effective value of the UTXO, i.e., the utxo value minus the fee for spending it
     effective_value: SignedAmount,
@@ -377,11 +603,11 @@ struct OutputGroup {
 
 impl OutputGroup {
     fn new(weighted_utxo: WeightedUtxo, fee_rate: FeeRate) -> Self {
-        let fee = fee_rate
-            * TxIn::default()
-                .segwit_weight()
-                .checked_add(weighted_utxo.satisfaction_weight)
-                .expect("`Weight` addition should not cause an integer overflow");
+        let weight = TxIn::default()
+            .segwit_weight()
+            .checked_add(weighted_utxo.satisfaction_weight)
+            .expect("`Weight` addition should not cause an integer overflow");
+        let fee = fee_rate * weight + weighted_utxo.bump_fee;
         let effective_value = weighted_utxo
             .utxo
             .txout()
@@ -391,6 +617,7 @@ impl OutputGroup {
             - fee.to_signed().expect("signed amount");
         OutputGroup {
             weighted_utxo,
+            weight,
             fee,
             effective_value,
         }
@@ -524,7 +751,12 @@ impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for BranchAndBoundCoinSe
 
             let excess = decide_change(remaining_amount, fee_rate, drain_script);
 
-            return Ok(calculate_cs_result(vec![], required_ogs, excess));
+            return Ok(calculate_cs_result(
+                vec![],
+                required_ogs,
+                excess,
+                "BranchAndBoundCoinSelection",
+            ));
         }
 
         match self.bnb(
@@ -538,14 +770,17 @@ impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for BranchAndBoundCoinSe
             fee_rate,
         ) {
             Ok(r) => Ok(r),
-            Err(_) => self.fallback_algorithm.coin_select(
-                required_utxos,
-                optional_utxos,
-                fee_rate,
-                target_amount,
-                drain_script,
-                rand,
-            ),
+            Err(_) => self
+                .fallback_algorithm
+                .coin_select(
+                    required_utxos,
+                    optional_utxos,
+                    fee_rate,
+                    target_amount,
+                    drain_script,
+                    rand,
+                )
+                .map(|r| r.delegated_by("BranchAndBoundCoinSelection")),
         }
     }
 }
@@ -670,7 +905,12 @@ impl<Cs> BranchAndBoundCoinSelection<Cs> {
 
         let excess = decide_change(remaining_amount, fee_rate, drain_script);
 
-        Ok(calculate_cs_result(selected_utxos, required_utxos, excess))
+        Ok(calculate_cs_result(
+            selected_utxos,
+            required_utxos,
+            excess,
+            "BranchAndBoundCoinSelection",
+        ))
     }
 }
 
@@ -699,247 +939,1300 @@ impl CoinSelectionAlgorithm for SingleRandomDraw {
         };
 
         // select required UTXOs and then random optional UTXOs.
-        select_sorted_utxos(utxos, fee_rate, target_amount, drain_script)
+        select_sorted_utxos(
+            utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            "SingleRandomDraw",
+        )
     }
 }
 
-fn calculate_cs_result(
-    selected_utxos: Vec<OutputGroup>,
-    required_utxos: Vec<OutputGroup>,
-    excess: Excess,
-) -> CoinSelectionResult {
-    let mut selected = required_utxos;
-    selected.extend(selected_utxos);
-    let fee_amount = selected.iter().map(|u| u.fee).sum();
-    let selected = selected
-        .into_iter()
-        .map(|output_group| output_group.weighted_utxo.utxo)
-        .collect();
+/// CoinGrinder coin selection
+///
+/// Searches for the lightest set of inputs that funds the target amount and a change output,
+/// which minimizes the fees paid when they are high. Code adapted from Bitcoin Core's
+/// implementation.
+///
+/// Unlike [`BranchAndBoundCoinSelection`] the result always has change, of at least `min_change`
+/// and never dust. The search is a branch and bound over the UTXOs sorted by descending effective
+/// value, which stops after a fixed number of tries and returns the lightest selection found so
+/// far. When no selection is found it falls back to `fallback_algorithm`.
+#[derive(Debug, Clone)]
+pub struct CoinGrinderCoinSelection<Cs = SingleRandomDraw> {
+    min_change: Amount,
+    fallback_algorithm: Cs,
+}
 
-    CoinSelectionResult {
-        selected,
-        fee_amount,
-        excess,
+impl<Cs: Default> Default for CoinGrinderCoinSelection<Cs> {
+    fn default() -> Self {
+        Self {
+            min_change: Amount::ZERO,
+            fallback_algorithm: Cs::default(),
+        }
     }
 }
 
-#[cfg_attr(coverage_nightly, coverage(off))]
-#[cfg(test)]
-mod test {
-    use assert_matches::assert_matches;
-    use bitcoin::hashes::Hash;
-    use bitcoin::{psbt, OutPoint, Sequence};
-    use chain::{ChainPosition, ConfirmationBlockTime};
-    use core::str::FromStr;
-    use rand::rngs::StdRng;
-    use std::boxed::Box;
-
-    use bitcoin::{Amount, BlockHash, ScriptBuf, TxIn, TxOut};
+impl<Cs> CoinGrinderCoinSelection<Cs> {
+    /// Create new instance with a `min_change` amount and `fallback_algorithm`.
+    pub fn new(min_change: Amount, fallback_algorithm: Cs) -> Self {
+        Self {
+            min_change,
+            fallback_algorithm,
+        }
+    }
+}
 
-    use super::*;
-    use crate::types::*;
+const COIN_GRINDER_TOTAL_TRIES: usize = 100_000;
 
-    use rand::prelude::SliceRandom;
-    use rand::{thread_rng, Rng, RngCore, SeedableRng};
+impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for CoinGrinderCoinSelection<Cs> {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let required_ogs: Vec<OutputGroup> = required_utxos
+            .iter()
+            .map(|u| OutputGroup::new(u.clone(), fee_rate))
+            .collect();
+        let mut optional_ogs: Vec<OutputGroup> = optional_utxos
+            .iter()
+            .map(|u| OutputGroup::new(u.clone(), fee_rate))
+            .filter(|u| u.effective_value.is_positive())
+            .collect();
+        // Sort by descending effective value, lightest first among equal values
+        optional_ogs.sort_unstable_by(|a, b| {
+            b.effective_value
+                .cmp(&a.effective_value)
+                .then(a.weight.cmp(&b.weight))
+        });
 
-    // signature len (1WU) + signature and sighash (72WU)
-    // + pubkey len (1WU) + pubkey (33WU)
-    const P2WPKH_SATISFACTION_SIZE: usize = 1 + 72 + 1 + 33;
+        let signed_target_amount: SignedAmount = target_amount
+            .try_into()
+            .expect("Bitcoin amount to fit into i64");
+        // The selection must also pay for a change output that isn't dust
+        let change_target = change_fee(fee_rate, drain_script)
+            + self.min_change.max(drain_script.minimal_non_dust());
+        let total_target = signed_target_amount + change_target.to_signed().expect("signed amount");
 
-    const FEE_AMOUNT: Amount = Amount::from_sat(50);
+        let required_value = required_ogs
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
 
-    fn unconfirmed_utxo(value: Amount, index: u32, last_seen: u64) -> WeightedUtxo {
-        utxo(
-            value,
-            index,
-            ChainPosition::Unconfirmed {
-                first_seen: Some(last_seen),
-                last_seen: Some(last_seen),
-            },
-        )
-    }
+        let best = if required_value >= total_target {
+            Some(Vec::new())
+        } else {
+            Self::grind(&optional_ogs, required_value, total_target)
+        };
 
-    fn confirmed_utxo(
-        value: Amount,
-        index: u32,
-        confirmation_height: u32,
-        confirmation_time: u64,
-    ) -> WeightedUtxo {
-        utxo(
-            value,
-            index,
-            ChainPosition::Confirmed {
-                anchor: ConfirmationBlockTime {
-                    block_id: chain::BlockId {
-                        height: confirmation_height,
-                        hash: bitcoin::BlockHash::all_zeros(),
-                    },
-                    confirmation_time,
-                },
-                transitively: None,
-            },
-        )
+        match best {
+            Some(best) => {
+                let selected = best
+                    .into_iter()
+                    .map(|i| optional_ogs[i].clone())
+                    .collect::<Vec<_>>();
+                let selected_value = selected
+                    .iter()
+                    .fold(required_value, |acc, x| acc + x.effective_value);
+                let remaining_amount = (selected_value - signed_target_amount)
+                    .to_unsigned()
+                    .expect("remaining amount can't be negative");
+                let excess = decide_change(remaining_amount, fee_rate, drain_script);
+                Ok(calculate_cs_result(
+                    selected,
+                    required_ogs,
+                    excess,
+                    "CoinGrinderCoinSelection",
+                ))
+            }
+            None => self
+                .fallback_algorithm
+                .coin_select(
+                    required_utxos,
+                    optional_utxos,
+                    fee_rate,
+                    target_amount,
+                    drain_script,
+                    rand,
+                )
+                .map(|r| r.delegated_by("CoinGrinderCoinSelection")),
+        }
     }
+}
 
-    fn foreign_utxo(value: Amount, index: u32) -> WeightedUtxo {
-        assert!(index < 10);
-        let outpoint = OutPoint::from_str(&format!(
-            "000000000000000000000000000000000000000000000000000000000000000{index}:0"
-        ))
-        .unwrap();
-        WeightedUtxo {
-            utxo: Utxo::Foreign {
-                outpoint,
-                sequence: Sequence(0xFFFFFFFD),
-                psbt_input: Box::new(psbt::Input {
-                    witness_utxo: Some(TxOut {
-                        value,
-                        script_pubkey: ScriptBuf::new(),
-                    }),
-                    non_witness_utxo: None,
-                    ..Default::default()
-                }),
-            },
-            satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+impl<Cs> CoinGrinderCoinSelection<Cs> {
+    // Depth first search for the lightest selection of `optional_utxos`, sorted by descending
+    // effective value, reaching `target_amount` from `curr_value`. Returns the indexes of the
+    // selected UTXOs.
+    fn grind(
+        optional_utxos: &[OutputGroup],
+        mut curr_value: SignedAmount,
+        target_amount: SignedAmount,
+    ) -> Option<Vec<usize>> {
+        let len = optional_utxos.len();
+        // lookahead[i] is the total effective value of optional_utxos[i..], and
+        // min_tail_weight[i] the lowest weight among them
+        let mut lookahead = vec![SignedAmount::ZERO; len + 1];
+        let mut min_tail_weight = vec![Weight::MAX; len + 1];
+        for (i, utxo) in optional_utxos.iter().enumerate().rev() {
+            lookahead[i] = lookahead[i + 1] + utxo.effective_value;
+            min_tail_weight[i] = min_tail_weight[i + 1].min(utxo.weight);
+        }
+        if curr_value + lookahead[0] < target_amount {
+            return None;
         }
-    }
 
-    fn utxo(
-        value: Amount,
-        index: u32,
-        chain_position: ChainPosition<ConfirmationBlockTime>,
-    ) -> WeightedUtxo {
-        assert!(index < 10);
-        let outpoint = OutPoint::from_str(&format!(
-            "000000000000000000000000000000000000000000000000000000000000000{index}:0"
-        ))
-        .unwrap();
-        WeightedUtxo {
-            satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
-            utxo: Utxo::Local(LocalOutput {
-                outpoint,
-                txout: TxOut {
-                    value,
-                    script_pubkey: ScriptBuf::new(),
-                },
-                keychain: KeychainKind::External,
-                is_spent: false,
-                derivation_index: 42,
-                chain_position,
-            }),
+        let mut selection: Vec<usize> = Vec::new();
+        let mut curr_weight = Weight::ZERO;
+        // The best selection with its weight and value
+        let mut best: Option<(Vec<usize>, Weight, SignedAmount)> = None;
+        let mut next = 0;
+
+        for _ in 0..COIN_GRINDER_TOTAL_TRIES {
+            // Inclusion branch first
+            let utxo = &optional_utxos[next];
+            selection.push(next);
+            curr_value += utxo.effective_value;
+            curr_weight += utxo.weight;
+
+            let best_weight = best.as_ref().map(|(_, weight, _)| *weight);
+            let explore_deeper = if curr_value + lookahead[next + 1] < target_amount {
+                // Neither this branch nor the ones of the next (smaller) UTXOs can reach the
+                // target, the shift below also cuts them
+                false
+            } else if best_weight.is_some_and(|best_weight| curr_weight > best_weight) {
+                // Heavier than the best selection, the next UTXOs could be lighter
+                false
+            } else if curr_value >= target_amount {
+                // Target reached, adding more UTXOs only makes the selection heavier
+                let is_better = match &best {
+                    Some((_, best_weight, best_value)) => {
+                        curr_weight < *best_weight
+                            || (curr_weight == *best_weight && curr_value < *best_value)
+                    }
+                    None => true,
+                };
+                if is_better {
+                    best = Some((selection.clone(), curr_weight, curr_value));
+                }
+                false
+            } else {
+                // Continue down this branch unless any UTXO makes it heavier than the best
+                best_weight.is_none_or(|best_weight| {
+                    curr_weight + min_tail_weight[next + 1] <= best_weight
+                })
+            };
+
+            if explore_deeper {
+                next += 1;
+                continue;
+            }
+
+            // Shift: replace the last selected UTXO with the next one, walking back while that's
+            // not possible or can't reach the target anymore
+            loop {
+                let Some(last) = selection.pop() else {
+                    // All the branches have been explored
+                    return best.map(|(selection, _, _)| selection);
+                };
+                let last_utxo = &optional_utxos[last];
+                curr_value -= last_utxo.effective_value;
+                curr_weight -= last_utxo.weight;
+                next = last + 1;
+                // Skip the UTXOs equivalent to the one just excluded, they'd lead to the same
+                // selections
+                while next < len
+                    && optional_utxos[next].effective_value == last_utxo.effective_value
+                    && optional_utxos[next].weight == last_utxo.weight
+                {
+                    next += 1;
+                }
+                if next < len && curr_value + lookahead[next] >= target_amount {
+                    break;
+                }
+            }
         }
+
+        best.map(|(selection, _, _)| selection)
     }
+}
 
-    fn get_test_utxos() -> Vec<WeightedUtxo> {
-        vec![
-            unconfirmed_utxo(Amount::from_sat(100_000), 0, 0),
-            unconfirmed_utxo(FEE_AMOUNT - Amount::from_sat(40), 1, 0),
-            unconfirmed_utxo(Amount::from_sat(200_000), 2, 0),
-        ]
+/// Knapsack coin selection
+///
+/// Randomized approximate subset sum solver adapted from Bitcoin Core's knapsack solver, a good
+/// fallback for [`BranchAndBoundCoinSelection`] when there is no changeless solution.
+///
+/// It looks for a selection of the UTXOs smaller than the target plus `min_change` matching the
+/// target exactly, or otherwise leaving a change of about `min_change`, over a bounded number of
+/// random iterations. The smallest UTXO larger than the target plus `min_change` is selected
+/// instead if it leaves less change.
+#[derive(Debug, Clone, Copy)]
+pub struct KnapsackCoinSelection {
+    min_change: Amount,
+}
+
+impl Default for KnapsackCoinSelection {
+    fn default() -> Self {
+        Self {
+            // Bitcoin Core's lower bound of the change target
+            min_change: Amount::from_sat(50_000),
+        }
     }
+}
 
-    fn get_oldest_first_test_utxos() -> Vec<WeightedUtxo> {
-        // ensure utxos are from different tx
-        let utxo1 = confirmed_utxo(Amount::from_sat(120_000), 1, 1, 1231006505);
-        let utxo2 = confirmed_utxo(Amount::from_sat(80_000), 2, 2, 1231006505);
-        let utxo3 = confirmed_utxo(Amount::from_sat(300_000), 3, 3, 1231006505);
-        vec![utxo1, utxo2, utxo3]
+impl KnapsackCoinSelection {
+    /// Create new instance aiming for a change of `min_change`.
+    pub fn new(min_change: Amount) -> Self {
+        Self { min_change }
     }
+}
 
-    fn generate_random_utxos(rng: &mut StdRng, utxos_number: usize) -> Vec<WeightedUtxo> {
-        let mut res = Vec::new();
-        for i in 0..utxos_number {
-            res.push(WeightedUtxo {
-                satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
-                utxo: Utxo::Local(LocalOutput {
-                    outpoint: OutPoint::from_str(&format!(
-                        "ebd9813ecebc57ff8f30797de7c205e3c7498ca950ea4341ee51a685ff2fa30a:{i}"
-                    ))
-                    .unwrap(),
-                    txout: TxOut {
-                        value: Amount::from_sat(rng.gen_range(0..200000000)),
-                        script_pubkey: ScriptBuf::new(),
-                    },
-                    keychain: KeychainKind::External,
-                    is_spent: false,
-                    derivation_index: rng.next_u32(),
-                    chain_position: if rng.gen_bool(0.5) {
-                        ChainPosition::Confirmed {
-                            anchor: ConfirmationBlockTime {
-                                block_id: chain::BlockId {
-                                    height: rng.next_u32(),
-                                    hash: BlockHash::all_zeros(),
-                                },
-                                confirmation_time: rng.next_u64(),
-                            },
-                            transitively: None,
-                        }
+const KNAPSACK_ITERATIONS: usize = 1_000;
+
+impl CoinSelectionAlgorithm for KnapsackCoinSelection {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let required_ogs: Vec<OutputGroup> = required_utxos
+            .into_iter()
+            .map(|u| OutputGroup::new(u, fee_rate))
+            .collect();
+        let mut optional_ogs: Vec<OutputGroup> = optional_utxos
+            .into_iter()
+            .map(|u| OutputGroup::new(u, fee_rate))
+            .filter(|u| u.effective_value.is_positive())
+            .collect();
+
+        let signed_target_amount: SignedAmount = target_amount
+            .try_into()
+            .expect("Bitcoin amount to fit into i64");
+        let required_value = required_ogs
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+
+        let selected = if required_value >= signed_target_amount {
+            Vec::new()
+        } else {
+            let target = (signed_target_amount - required_value)
+                .to_unsigned()
+                .expect("target is higher than the required value");
+            let change_target = self.min_change + change_fee(fee_rate, drain_script);
+            // Assume we spend all the UTXOs we can in case the target can't be reached
+            let (utxo_fees, utxo_value) = required_ogs.iter().chain(optional_ogs.iter()).fold(
+                (Amount::ZERO, Amount::ZERO),
+                |(fees, value), utxo| {
+                    (
+                        fees + utxo.fee,
+                        value + utxo.weighted_utxo.utxo.txout().value,
+                    )
+                },
+            );
+
+            shuffle_slice(&mut optional_ogs, rand);
+            Self::knapsack(optional_ogs, target, change_target, rand).ok_or(InsufficientFunds {
+                needed: target_amount + utxo_fees,
+                available: utxo_value,
+            })?
+        };
+
+        let selected_value = selected
+            .iter()
+            .fold(required_value, |acc, x| acc + x.effective_value);
+        let remaining_amount = (selected_value - signed_target_amount)
+            .to_unsigned()
+            .expect("remaining amount can't be negative");
+        let excess = decide_change(remaining_amount, fee_rate, drain_script);
+
+        Ok(calculate_cs_result(
+            selected,
+            required_ogs,
+            excess,
+            "KnapsackCoinSelection",
+        ))
+    }
+}
+
+impl KnapsackCoinSelection {
+    // Select among `utxos`, in random order and with positive effective values, the UTXOs
+    // reaching `target` with a change of about `change_target`.
+    fn knapsack<R: RngCore>(
+        utxos: Vec<OutputGroup>,
+        target: Amount,
+        change_target: Amount,
+        rand: &mut R,
+    ) -> Option<Vec<OutputGroup>> {
+        let value = |utxo: &OutputGroup| {
+            utxo.effective_value
+                .to_unsigned()
+                .expect("positive effective value")
+        };
+
+        // The UTXOs lower than the target plus change, and the smallest larger one
+        let mut applicable = Vec::new();
+        let mut total_lower = Amount::ZERO;
+        let mut lowest_larger: Option<OutputGroup> = None;
+        for utxo in utxos {
+            let utxo_value = value(&utxo);
+            if utxo_value == target {
+                return Some(vec![utxo]);
+            } else if utxo_value < target + change_target {
+                total_lower += utxo_value;
+                applicable.push(utxo);
+            } else if lowest_larger
+                .as_ref()
+                .is_none_or(|lowest_larger| utxo_value < value(lowest_larger))
+            {
+                lowest_larger = Some(utxo);
+            }
+        }
+
+        if total_lower == target {
+            return Some(applicable);
+        }
+        if total_lower < target {
+            return lowest_larger.map(|utxo| vec![utxo]);
+        }
+
+        // Look for an exact match first, and then for a selection with enough change
+        applicable.sort_by_key(|utxo| core::cmp::Reverse(utxo.effective_value));
+        let values = applicable.iter().map(value).collect::<Vec<_>>();
+        let (mut best, mut best_value) =
+            Self::approximate_best_subset(&values, total_lower, target, rand);
+        if best_value != target && total_lower >= target + change_target {
+            (best, best_value) =
+                Self::approximate_best_subset(&values, total_lower, target + change_target, rand);
+        }
+
+        if let Some(lowest_larger) = lowest_larger {
+            if (best_value != target && best_value < target + change_target)
+                || value(&lowest_larger) <= best_value
+            {
+                return Some(vec![lowest_larger]);
+            }
+        }
+
+        Some(
+            applicable
+                .into_iter()
+                .zip(best)
+                .filter_map(|(utxo, is_in_best)| is_in_best.then_some(utxo))
+                .collect(),
+        )
+    }
+
+    // Randomly look for the subset of `values`, sorted in descending order and summing up to
+    // `total`, closest to `target` without being lower.
+    fn approximate_best_subset<R: RngCore>(
+        values: &[Amount],
+        total: Amount,
+        target: Amount,
+        rand: &mut R,
+    ) -> (Vec<bool>, Amount) {
+        let mut best = vec![true; values.len()];
+        let mut best_value = total;
+
+        for _ in 0..KNAPSACK_ITERATIONS {
+            if best_value == target {
+                break;
+            }
+            let mut included = vec![false; values.len()];
+            let mut included_value = Amount::ZERO;
+            let mut reached_target = false;
+            // Include random UTXOs in the first pass, and the remaining ones in the second
+            for pass in 0..2 {
+                if reached_target {
+                    break;
+                }
+                for (i, value) in values.iter().enumerate() {
+                    let include = if pass == 0 {
+                        rand.next_u32() & 1 == 1
                     } else {
-                        ChainPosition::Unconfirmed {
-                            first_seen: Some(1),
-                            last_seen: Some(1),
+                        !included[i]
+                    };
+                    if !include {
+                        continue;
+                    }
+                    included_value += *value;
+                    included[i] = true;
+                    if included_value >= target {
+                        reached_target = true;
+                        if included_value < best_value {
+                            best_value = included_value;
+                            best.clone_from(&included);
                         }
-                    },
-                }),
-            });
+                        // Try to get closer to the target without this UTXO
+                        included_value -= *value;
+                        included[i] = false;
+                    }
+                }
+            }
         }
-        res
+
+        (best, best_value)
+    }
+}
+
+/// Avoid partial spends of reused addresses
+///
+/// When an address received several payments, spending only some of them links the history of
+/// the address without any benefit. This wraps another coin selection algorithm so that the
+/// wallet's UTXOs are grouped by script pubkey and whole groups are selected, like Bitcoin Core's
+/// `-avoidpartialspends`.
+///
+/// Each group is passed to the wrapped algorithm as a single UTXO with the total value, weight
+/// and bump fee of the group. A group with a required UTXO is required. Foreign UTXOs are never
+/// grouped.
+///
+/// The UTXOs of a script pubkey with more than 100 of them are split into groups of 100. Like
+/// Bitcoin Core, the last group of such a script is left out when it's partial and has no required
+/// UTXO: its UTXOs can be spent once the full groups are.
+#[derive(Debug, Clone, Default)]
+pub struct AvoidPartialSpendsCoinSelection<Cs = DefaultCoinSelectionAlgorithm> {
+    coin_selection: Cs,
+}
+
+impl<Cs> AvoidPartialSpendsCoinSelection<Cs> {
+    /// Create new instance grouping the UTXOs passed to `coin_selection`.
+    pub fn new(coin_selection: Cs) -> Self {
+        Self { coin_selection }
     }
+}
 
-    fn generate_same_value_utxos(utxos_value: Amount, utxos_number: usize) -> Vec<WeightedUtxo> {
-        (0..utxos_number)
-            .map(|i| WeightedUtxo {
-                satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
-                utxo: Utxo::Local(LocalOutput {
-                    outpoint: OutPoint::from_str(&format!(
-                        "ebd9813ecebc57ff8f30797de7c205e3c7498ca950ea4341ee51a685ff2fa30a:{i}"
-                    ))
-                    .unwrap(),
-                    txout: TxOut {
-                        value: utxos_value,
-                        script_pubkey: ScriptBuf::new(),
-                    },
-                    keychain: KeychainKind::External,
-                    is_spent: false,
-                    derivation_index: 42,
-                    chain_position: ChainPosition::Unconfirmed {
-                        first_seen: Some(1),
-                        last_seen: Some(1),
-                    },
-                }),
+// Maximum number of UTXOs in a group of `AvoidPartialSpendsCoinSelection`
+const OUTPUT_GROUP_MAX_ENTRIES: usize = 100;
+
+impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for AvoidPartialSpendsCoinSelection<Cs> {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        // Group the UTXOs by script pubkey, remembering if a group has a required UTXO
+        let mut groups: Vec<(bool, Vec<WeightedUtxo>)> = Vec::new();
+        // The index of the last group of each script pubkey, and its number of groups
+        let mut group_indexes: BTreeMap<ScriptBuf, (usize, usize)> = BTreeMap::new();
+        let utxos = required_utxos
+            .into_iter()
+            .map(|utxo| (true, utxo))
+            .chain(optional_utxos.into_iter().map(|utxo| (false, utxo)));
+        for (must_use, weighted_utxo) in utxos {
+            let Utxo::Local(local) = &weighted_utxo.utxo else {
+                groups.push((must_use, vec![weighted_utxo]));
+                continue;
+            };
+            match group_indexes.get(&local.txout.script_pubkey) {
+                Some(&(index, _)) if groups[index].1.len() < OUTPUT_GROUP_MAX_ENTRIES => {
+                    groups[index].0 |= must_use;
+                    groups[index].1.push(weighted_utxo);
+                }
+                last => {
+                    let count = last.map_or(0, |&(_, count)| count) + 1;
+                    group_indexes.insert(local.txout.script_pubkey.clone(), (groups.len(), count));
+                    groups.push((must_use, vec![weighted_utxo]));
+                }
+            }
+        }
+
+        // Leave out the partial last group of the script pubkeys with full groups
+        let partial_groups: BTreeSet<usize> = group_indexes
+            .into_values()
+            .filter(|&(index, count)| {
+                let (must_use, group) = &groups[index];
+                count > 1 && !must_use && group.len() < OUTPUT_GROUP_MAX_ENTRIES
             })
-            .collect()
+            .map(|(index, _)| index)
+            .collect();
+
+        // Merge every group into the UTXO of its first entry
+        let mut grouped_utxos: BTreeMap<OutPoint, Vec<Utxo>> = BTreeMap::new();
+        let mut required_utxos = Vec::new();
+        let mut optional_utxos = Vec::new();
+        for (index, (must_use, mut group)) in groups.into_iter().enumerate() {
+            if partial_groups.contains(&index) {
+                continue;
+            }
+            let mut weighted_utxo = group.remove(0);
+            if !group.is_empty() {
+                if let Utxo::Local(local) = &mut weighted_utxo.utxo {
+                    for entry in &group {
+                        local.txout.value += entry.utxo.txout().value;
+                        weighted_utxo.satisfaction_weight += TxIn::default()
+                            .segwit_weight()
+                            .checked_add(entry.satisfaction_weight)
+                            .expect("`Weight` addition should not cause an integer overflow");
+                        weighted_utxo.bump_fee += entry.bump_fee;
+                    }
+                }
+                grouped_utxos.insert(
+                    weighted_utxo.utxo.outpoint(),
+                    group.into_iter().map(|entry| entry.utxo).collect(),
+                );
+            }
+            if must_use {
+                required_utxos.push(weighted_utxo);
+            } else {
+                optional_utxos.push(weighted_utxo);
+            }
+        }
+
+        let mut result = self.coin_selection.coin_select(
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )?;
+
+        // Replace each selected group with its UTXOs
+        result.selected = result
+            .selected
+            .into_iter()
+            .flat_map(|utxo| {
+                let group = grouped_utxos.remove(&utxo.outpoint()).unwrap_or_default();
+                let utxo = match utxo {
+                    Utxo::Local(mut local) if !group.is_empty() => {
+                        local.txout.value -= group.iter().map(|u| u.txout().value).sum();
+                        Utxo::Local(local)
+                    }
+                    utxo => utxo,
+                };
+                core::iter::once(utxo).chain(group)
+            })
+            .collect();
+
+        Ok(result.delegated_by("AvoidPartialSpendsCoinSelection"))
     }
+}
 
-    fn sum_random_utxos(mut rng: &mut StdRng, utxos: &mut [WeightedUtxo]) -> Amount {
-        let utxos_picked_len = rng.gen_range(2..utxos.len() / 2);
-        utxos.shuffle(&mut rng);
-        utxos[..utxos_picked_len]
-            .iter()
-            .map(|u| u.utxo.txout().value)
-            .sum()
+/// A set of coin selection algorithms run by [`LowestWasteCoinSelection`]
+///
+/// This is implemented for tuples of up to six [`CoinSelectionAlgorithm`]s.
+pub trait CoinSelectionAlgorithms: core::fmt::Debug {
+    /// Run every algorithm on the same UTXOs and return their results, in order
+    ///
+    /// See [`CoinSelectionAlgorithm::coin_select`] for the meaning of the arguments.
+    fn coin_select_all<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Vec<Result<CoinSelectionResult, InsufficientFunds>>;
+}
+
+macro_rules! impl_coin_selection_algorithms {
+    ($($name:ident),+) => {
+        impl<$($name: CoinSelectionAlgorithm),+> CoinSelectionAlgorithms for ($($name,)+) {
+            fn coin_select_all<R: RngCore>(
+                &self,
+                required_utxos: Vec<WeightedUtxo>,
+                optional_utxos: Vec<WeightedUtxo>,
+                fee_rate: FeeRate,
+                target_amount: Amount,
+                drain_script: &Script,
+                rand: &mut R,
+            ) -> Vec<Result<CoinSelectionResult, InsufficientFunds>> {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                vec![$($name.coin_select(
+                    required_utxos.clone(),
+                    optional_utxos.clone(),
+                    fee_rate,
+                    target_amount,
+                    drain_script,
+                    rand,
+                )),+]
+            }
+        }
+    };
+}
+
+impl_coin_selection_algorithms!(A);
+impl_coin_selection_algorithms!(A, B);
+impl_coin_selection_algorithms!(A, B, C);
+impl_coin_selection_algorithms!(A, B, C, D);
+impl_coin_selection_algorithms!(A, B, C, D, E);
+impl_coin_selection_algorithms!(A, B, C, D, E, F);
+
+/// Run several coin selection algorithms and keep the result with the lowest waste
+///
+/// The waste of each result is computed with [`CoinSelectionResult::waste`], like Bitcoin Core
+/// does to choose between the results of its coin selection algorithms. Ties are broken with
+/// the random number generator passed to [`coin_select`], so that a seeded generator always picks
+/// the same result.
+///
+/// If every algorithm fails, the error of the first one is returned.
+///
+/// ```
+/// # use bitcoin::*;
+/// # use bdk_wallet::coin_selection::*;
+/// let coin_selection = LowestWasteCoinSelection::new(
+///     (
+///         BranchAndBoundCoinSelection::<SingleRandomDraw>::default(),
+///         LargestFirstCoinSelection,
+///         OldestFirstCoinSelection,
+///     ),
+///     FeeRate::from_sat_per_vb_u32(10),
+///     // P2WPKH input spending the change
+///     Weight::from_wu(272),
+/// );
+/// ```
+///
+/// [`coin_select`]: CoinSelectionAlgorithm::coin_select
+#[derive(Debug, Clone)]
+pub struct LowestWasteCoinSelection<Cs> {
+    algorithms: Cs,
+    long_term_feerate: FeeRate,
+    change_spend_weight: Weight,
+}
+
+impl<Cs: Default> Default for LowestWasteCoinSelection<Cs> {
+    fn default() -> Self {
+        Self {
+            algorithms: Cs::default(),
+            // Bitcoin Core's default consolidation fee rate
+            long_term_feerate: FeeRate::from_sat_per_vb_u32(10),
+            // P2WPKH input -> outpoint, sequence and empty script sig (41 bytes)
+            // + witness (1 + 1 + 72 + 1 + 33 WU)
+            change_spend_weight: Weight::from_wu(41 * 4 + 108),
+        }
+    }
+}
+
+impl<Cs> LowestWasteCoinSelection<Cs> {
+    /// Create new instance running `algorithms`, computing the waste of their results with
+    /// `long_term_feerate` and the `change_spend_weight` of the input spending the change.
+    pub fn new(algorithms: Cs, long_term_feerate: FeeRate, change_spend_weight: Weight) -> Self {
+        Self {
+            algorithms,
+            long_term_feerate,
+            change_spend_weight,
+        }
     }
+}
+
+impl<Cs: CoinSelectionAlgorithms> CoinSelectionAlgorithm for LowestWasteCoinSelection<Cs> {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let satisfaction_weights = required_utxos
+            .iter()
+            .chain(&optional_utxos)
+            .map(|wutxo| (wutxo.utxo.outpoint(), wutxo.satisfaction_weight))
+            .collect::<BTreeMap<_, _>>();
+        let mut error = None;
+        let mut best = Vec::new();
+        let mut best_waste = None;
+        for result in self.algorithms.coin_select_all(
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        ) {
+            let mut result = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    error.get_or_insert(e);
+                    continue;
+                }
+            };
+            result.fill_input_weight(|outpoint| {
+                satisfaction_weights
+                    .get(&outpoint)
+                    .copied()
+                    .unwrap_or(Weight::ZERO)
+            });
+            let waste = result.waste(self.long_term_feerate, self.change_spend_weight);
+            match best_waste {
+                Some(best_waste) if waste > best_waste => {}
+                Some(best_waste) if waste == best_waste => best.push(result),
+                _ => {
+                    best_waste = Some(waste);
+                    best = vec![result];
+                }
+            }
+        }
+
+        let result = if best.len() > 1 {
+            let index = (rand.next_u64() % best.len() as u64) as usize;
+            Some(best.swap_remove(index))
+        } else {
+            best.pop()
+        };
+        result
+            .map(|result| result.delegated_by("LowestWasteCoinSelection"))
+            .ok_or_else(|| error.expect("at least one algorithm is run"))
+    }
+}
+
+fn calculate_cs_result(
+    selected_utxos: Vec<OutputGroup>,
+    required_utxos: Vec<OutputGroup>,
+    excess: Excess,
+    algorithm: &'static str,
+) -> CoinSelectionResult {
+    let mut selected = required_utxos;
+    selected.extend(selected_utxos);
+    let fee_amount = selected.iter().map(|u| u.fee).sum();
+    let selected = selected
+        .into_iter()
+        .map(|output_group| output_group.weighted_utxo.utxo)
+        .collect();
+
+    CoinSelectionResult::new(selected, fee_amount, excess).delegated_by(algorithm)
+}
+
+#[cfg_attr(coverage_nightly, coverage(off))]
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{psbt, OutPoint, Sequence};
+    use chain::{ChainPosition, ConfirmationBlockTime};
+    use core::str::FromStr;
+    use rand::rngs::StdRng;
+    use std::boxed::Box;
+
+    use bitcoin::{Amount, BlockHash, ScriptBuf, TxIn, TxOut};
+
+    use super::*;
+    use crate::types::*;
+
+    use rand::prelude::SliceRandom;
+    use rand::{thread_rng, Rng, RngCore, SeedableRng};
+
+    // signature len (1WU) + signature and sighash (72WU)
+    // + pubkey len (1WU) + pubkey (33WU)
+    const P2WPKH_SATISFACTION_SIZE: usize = 1 + 72 + 1 + 33;
+
+    const FEE_AMOUNT: Amount = Amount::from_sat(50);
+
+    fn unconfirmed_utxo(value: Amount, index: u32, last_seen: u64) -> WeightedUtxo {
+        utxo(
+            value,
+            index,
+            ChainPosition::Unconfirmed {
+                first_seen: Some(last_seen),
+                last_seen: Some(last_seen),
+            },
+        )
+    }
+
+    fn confirmed_utxo(
+        value: Amount,
+        index: u32,
+        confirmation_height: u32,
+        confirmation_time: u64,
+    ) -> WeightedUtxo {
+        utxo(
+            value,
+            index,
+            ChainPosition::Confirmed {
+                anchor: ConfirmationBlockTime {
+                    block_id: chain::BlockId {
+                        height: confirmation_height,
+                        hash: bitcoin::BlockHash::all_zeros(),
+                    },
+                    confirmation_time,
+                },
+                transitively: None,
+            },
+        )
+    }
+
+    fn foreign_utxo(value: Amount, index: u32) -> WeightedUtxo {
+        assert!(index < 10);
+        let outpoint = OutPoint::from_str(&format!(
+            "000000000000000000000000000000000000000000000000000000000000000{index}:0"
+        ))
+        .unwrap();
+        WeightedUtxo {
+            utxo: Utxo::Foreign {
+                outpoint,
+                sequence: Sequence(0xFFFFFFFD),
+                psbt_input: Box::new(psbt::Input {
+                    witness_utxo: Some(TxOut {
+                        value,
+                        script_pubkey: ScriptBuf::new(),
+                    }),
+                    non_witness_utxo: None,
+                    ..Default::default()
+                }),
+            },
+            satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+            bump_fee: Amount::ZERO,
+        }
+    }
+
+    fn utxo(
+        value: Amount,
+        index: u32,
+        chain_position: ChainPosition<ConfirmationBlockTime>,
+    ) -> WeightedUtxo {
+        assert!(index < 10);
+        let outpoint = OutPoint::from_str(&format!(
+            "000000000000000000000000000000000000000000000000000000000000000{index}:0"
+        ))
+        .unwrap();
+        WeightedUtxo {
+            satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+            bump_fee: Amount::ZERO,
+            utxo: Utxo::Local(LocalOutput {
+                outpoint,
+                txout: TxOut {
+                    value,
+                    script_pubkey: ScriptBuf::new(),
+                },
+                keychain: KeychainKind::External,
+                is_spent: false,
+                derivation_index: 42,
+                chain_position,
+            }),
+        }
+    }
+
+    fn get_test_utxos() -> Vec<WeightedUtxo> {
+        vec![
+            unconfirmed_utxo(Amount::from_sat(100_000), 0, 0),
+            unconfirmed_utxo(FEE_AMOUNT - Amount::from_sat(40), 1, 0),
+            unconfirmed_utxo(Amount::from_sat(200_000), 2, 0),
+        ]
+    }
+
+    fn get_oldest_first_test_utxos() -> Vec<WeightedUtxo> {
+        // ensure utxos are from different tx
+        let utxo1 = confirmed_utxo(Amount::from_sat(120_000), 1, 1, 1231006505);
+        let utxo2 = confirmed_utxo(Amount::from_sat(80_000), 2, 2, 1231006505);
+        let utxo3 = confirmed_utxo(Amount::from_sat(300_000), 3, 3, 1231006505);
+        vec![utxo1, utxo2, utxo3]
+    }
+
+    fn generate_random_utxos(rng: &mut StdRng, utxos_number: usize) -> Vec<WeightedUtxo> {
+        let mut res = Vec::new();
+        for i in 0..utxos_number {
+            res.push(WeightedUtxo {
+                satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+                bump_fee: Amount::ZERO,
+                utxo: Utxo::Local(LocalOutput {
+                    outpoint: OutPoint::from_str(&format!(
+                        "ebd9813ecebc57ff8f30797de7c205e3c7498ca950ea4341ee51a685ff2fa30a:{i}"
+                    ))
+                    .unwrap(),
+                    txout: TxOut {
+                        value: Amount::from_sat(rng.gen_range(0..200000000)),
+                        script_pubkey: ScriptBuf::new(),
+                    },
+                    keychain: KeychainKind::External,
+                    is_spent: false,
+                    derivation_index: rng.next_u32(),
+                    chain_position: if rng.gen_bool(0.5) {
+                        ChainPosition::Confirmed {
+                            anchor: ConfirmationBlockTime {
+                                block_id: chain::BlockId {
+                                    height: rng.next_u32(),
+                                    hash: BlockHash::all_zeros(),
+                                },
+                                confirmation_time: rng.next_u64(),
+                            },
+                            transitively: None,
+                        }
+                    } else {
+                        ChainPosition::Unconfirmed {
+                            first_seen: Some(1),
+                            last_seen: Some(1),
+                        }
+                    },
+                }),
+            });
+        }
+        res
+    }
+
+    fn generate_same_value_utxos(utxos_value: Amount, utxos_number: usize) -> Vec<WeightedUtxo> {
+        (0..utxos_number)
+            .map(|i| WeightedUtxo {
+                satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+                bump_fee: Amount::ZERO,
+                utxo: Utxo::Local(LocalOutput {
+                    outpoint: OutPoint::from_str(&format!(
+                        "ebd9813ecebc57ff8f30797de7c205e3c7498ca950ea4341ee51a685ff2fa30a:{i}"
+                    ))
+                    .unwrap(),
+                    txout: TxOut {
+                        value: utxos_value,
+                        script_pubkey: ScriptBuf::new(),
+                    },
+                    keychain: KeychainKind::External,
+                    is_spent: false,
+                    derivation_index: 42,
+                    chain_position: ChainPosition::Unconfirmed {
+                        first_seen: Some(1),
+                        last_seen: Some(1),
+                    },
+                }),
+            })
+            .collect()
+    }
+
+    fn sum_random_utxos(mut rng: &mut StdRng, utxos: &mut [WeightedUtxo]) -> Amount {
+        let utxos_picked_len = rng.gen_range(2..utxos.len() / 2);
+        utxos.shuffle(&mut rng);
+        utxos[..utxos_picked_len]
+            .iter()
+            .map(|u| u.utxo.txout().value)
+            .sum()
+    }
+
+    fn calc_target_amount(utxos: &[WeightedUtxo], fee_rate: FeeRate) -> Amount {
+        utxos
+            .iter()
+            .cloned()
+            .map(|utxo| OutputGroup::new(utxo, fee_rate).effective_value)
+            .sum::<SignedAmount>()
+            .to_unsigned()
+            .expect("unsigned amount")
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_success() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                utxos,
+                vec![],
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_use_all() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                utxos,
+                vec![],
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_bump_fee() {
+        let utxos = get_test_utxos()
+            .into_iter()
+            .map(|utxo| WeightedUtxo {
+                bump_fee: Amount::from_sat(100),
+                ..utxo
+            })
+            .collect();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                utxos,
+                vec![],
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.fee_amount, Amount::from_sat(204 + 300));
+    }
+
+    #[test]
+    fn test_output_group_bump_fee() {
+        let utxo = unconfirmed_utxo(Amount::from_sat(100_000), 0, 0);
+        let fee_rate = FeeRate::from_sat_per_vb_u32(5);
+        let group = OutputGroup::new(utxo.clone(), fee_rate);
+        let bumped_group = OutputGroup::new(
+            WeightedUtxo {
+                bump_fee: Amount::from_sat(1_000),
+                ..utxo
+            },
+            fee_rate,
+        );
+
+        assert_eq!(bumped_group.fee, group.fee + Amount::from_sat(1_000));
+        assert_eq!(
+            bumped_group.effective_value,
+            group.effective_value - SignedAmount::from_sat(1_000)
+        );
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_use_only_necessary() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                vec![],
+                utxos,
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(68));
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_insufficient_funds() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(500_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection.coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_u32(1),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_insufficient_funds_high_fees() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection.coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_u32(1000),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_success() {
+        let utxos = get_oldest_first_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(180_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                vec![],
+                utxos,
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(136));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_use_all() {
+        let utxos = get_oldest_first_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                utxos,
+                vec![],
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(500_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_uses_all_optional_with_foreign_utxo_locals_sorted_first() {
+        let utxos = get_oldest_first_test_utxos();
+        let mut all_utxos = vec![foreign_utxo(Amount::from_sat(120_000), 1)];
+        all_utxos.extend_from_slice(&utxos);
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(619_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                vec![],
+                all_utxos,
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 4);
+        assert_eq!(result.selected_amount(), Amount::from_sat(620_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(272));
+        assert!(matches!(result.selected[3], Utxo::Foreign { .. }));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_uses_only_all_optional_local_utxos_not_a_single_foreign() {
+        let utxos = get_oldest_first_test_utxos();
+        let mut all_utxos = vec![foreign_utxo(Amount::from_sat(120_000), 1)];
+        all_utxos.extend_from_slice(&utxos);
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(499_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                vec![],
+                all_utxos,
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(500_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+        assert!(result
+            .selected
+            .iter()
+            .all(|utxo| matches!(utxo, Utxo::Local(..))));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_use_only_necessary() {
+        let utxos = get_oldest_first_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                vec![],
+                utxos,
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(120_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(68));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_insufficient_funds() {
+        let utxos = get_oldest_first_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(600_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection.coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_u32(1),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_insufficient_funds_high_fees() {
+        let utxos = get_oldest_first_test_utxos();
 
-    fn calc_target_amount(utxos: &[WeightedUtxo], fee_rate: FeeRate) -> Amount {
-        utxos
-            .iter()
-            .cloned()
-            .map(|utxo| OutputGroup::new(utxo, fee_rate).effective_value)
-            .sum::<SignedAmount>()
-            .to_unsigned()
-            .expect("unsigned amount")
+        let target_amount =
+            utxos.iter().map(|wu| wu.utxo.txout().value).sum::<Amount>() - Amount::from_sat(50);
+        let drain_script = ScriptBuf::default();
+
+        let result = OldestFirstCoinSelection.coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_u32(1000),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
     }
 
     #[test]
-    fn test_largest_first_coin_selection_success() {
-        let utxos = get_test_utxos();
+    fn test_bnb_coin_selection_success() {
+        // In this case bnb won't find a suitable match and single random draw will
+        // select three outputs
+        let utxos = generate_same_value_utxos(Amount::from_sat(100_000), 20);
         let drain_script = ScriptBuf::default();
         let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
 
-        let result = LargestFirstCoinSelection
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
             .coin_select(
-                utxos,
                 vec![],
+                utxos,
                 FeeRate::from_sat_per_vb_u32(1),
                 target_amount,
                 &drain_script,
@@ -948,20 +2241,20 @@ mod test {
             .unwrap();
 
         assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
         assert_eq!(result.fee_amount, Amount::from_sat(204));
     }
 
     #[test]
-    fn test_largest_first_coin_selection_use_all() {
+    fn test_bnb_coin_selection_required_are_enough() {
         let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
         let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
 
-        let result = LargestFirstCoinSelection
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
             .coin_select(
+                utxos.clone(),
                 utxos,
-                vec![],
                 FeeRate::from_sat_per_vb_u32(1),
                 target_amount,
                 &drain_script,
@@ -974,372 +2267,587 @@ mod test {
         assert_eq!(result.fee_amount, Amount::from_sat(204));
     }
 
-    #[test]
-    fn test_largest_first_coin_selection_use_only_necessary() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+    #[test]
+    fn test_bnb_coin_selection_optional_are_enough() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let fee_rate = FeeRate::BROADCAST_MIN;
+        // first and third utxo's effective value
+        let target_amount = calc_target_amount(&[utxos[0].clone(), utxos[2].clone()], fee_rate);
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                vec![],
+                utxos,
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300000));
+        assert_eq!(result.fee_amount, Amount::from_sat(136));
+    }
+
+    #[test]
+    fn test_single_random_draw_function_success() {
+        let seed = [0; 32];
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let mut utxos = generate_random_utxos(&mut rng, 300);
+        let target_amount = sum_random_utxos(&mut rng, &mut utxos) + FEE_AMOUNT;
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        let drain_script = ScriptBuf::default();
+
+        let result = SingleRandomDraw.coin_select(
+            vec![],
+            utxos,
+            fee_rate,
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+
+        assert!(
+            matches!(result, Ok(CoinSelectionResult {selected, fee_amount, ..})
+                if selected.iter().map(|u| u.txout().value).sum::<Amount>() > target_amount
+                && fee_amount == Amount::from_sat(selected.len() as u64 * 68)
+            )
+        );
+    }
+
+    #[test]
+    fn test_single_random_draw_function_error() {
+        let seed = [0; 32];
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+        // 100_000, 10, 200_000
+        let utxos = get_test_utxos();
+        let target_amount = Amount::from_sat(300_000) + FEE_AMOUNT;
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        let drain_script = ScriptBuf::default();
+
+        let result = SingleRandomDraw.coin_select(
+            vec![],
+            utxos,
+            fee_rate,
+            target_amount,
+            &drain_script,
+            &mut rng,
+        );
+
+        assert!(matches!(result, Err(InsufficientFunds {needed, available})
+                if needed == Amount::from_sat(300_254) && available == Amount::from_sat(300_010)));
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_required_not_enough() {
+        let utxos = get_test_utxos();
+
+        let required = vec![utxos[0].clone()];
+        let mut optional = utxos[1..].to_vec();
+        optional.push(utxo(
+            Amount::from_sat(500_000),
+            3,
+            ChainPosition::<ConfirmationBlockTime>::Unconfirmed {
+                first_seen: Some(1),
+                last_seen: Some(1),
+            },
+        ));
+
+        // Defensive assertions, for sanity and in case someone changes the test utxos vector.
+        let amount = required
+            .iter()
+            .map(|u| u.utxo.txout().value)
+            .sum::<Amount>();
+        assert_eq!(amount, Amount::from_sat(100_000));
+        let amount = optional
+            .iter()
+            .map(|u| u.utxo.txout().value)
+            .sum::<Amount>();
+        assert!(amount > Amount::from_sat(150_000));
+        let drain_script = ScriptBuf::default();
+
+        let fee_rate = FeeRate::BROADCAST_MIN;
+        // first and third utxo's effective value
+        let target_amount = calc_target_amount(&[utxos[0].clone(), utxos[2].clone()], fee_rate);
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                required,
+                optional,
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(136));
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_insufficient_funds() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(500_000) + FEE_AMOUNT;
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_u32(1),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_insufficient_funds_high_fees() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_u32(1000),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_check_fee_rate() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let fee_rate = FeeRate::BROADCAST_MIN;
+        // first utxo's effective value
+        let target_amount = calc_target_amount(&utxos[0..1], fee_rate);
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                vec![],
+                utxos,
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
+        let input_weight =
+            TxIn::default().segwit_weight().to_wu() + P2WPKH_SATISFACTION_SIZE as u64;
+        // the final fee rate should be exactly the same as the fee rate given
+        let result_feerate = result.fee_amount / Weight::from_wu(input_weight);
+        assert_eq!(result_feerate, fee_rate);
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_exact_match() {
+        let seed = [0; 32];
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+        for _i in 0..200 {
+            let mut optional_utxos = generate_random_utxos(&mut rng, 16);
+            let target_amount = sum_random_utxos(&mut rng, &mut optional_utxos);
+            let drain_script = ScriptBuf::default();
+            let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+                .coin_select(
+                    vec![],
+                    optional_utxos,
+                    FeeRate::ZERO,
+                    target_amount,
+                    &drain_script,
+                    &mut thread_rng(),
+                )
+                .unwrap();
+            assert_eq!(result.selected_amount(), target_amount);
+        }
+    }
+
+    #[test]
+    fn test_bnb_function_no_exact_match() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        let utxos: Vec<OutputGroup> = get_test_utxos()
+            .into_iter()
+            .map(|u| OutputGroup::new(u, fee_rate))
+            .collect();
+
+        let curr_available_value = utxos
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
 
-        let result = LargestFirstCoinSelection
-            .coin_select(
-                vec![],
-                utxos,
-                FeeRate::from_sat_per_vb_u32(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
+        let size_of_change = 31;
+        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
+            .to_signed()
             .unwrap();
 
-        assert_eq!(result.selected.len(), 1);
-        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(68));
-    }
-
-    #[test]
-    fn test_largest_first_coin_selection_insufficient_funds() {
-        let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(500_000) + FEE_AMOUNT;
-
-        let result = LargestFirstCoinSelection.coin_select(
+        let target_amount = SignedAmount::from_sat(20_000) + FEE_AMOUNT.to_signed().unwrap();
+        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw).bnb(
             vec![],
             utxos,
-            FeeRate::from_sat_per_vb_u32(1),
+            SignedAmount::ZERO,
+            curr_available_value,
             target_amount,
+            cost_of_change,
             &drain_script,
-            &mut thread_rng(),
+            fee_rate,
         );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
+        assert!(matches!(result, Err(BnbError::NoExactMatch)));
     }
 
     #[test]
-    fn test_largest_first_coin_selection_insufficient_funds_high_fees() {
-        let utxos = get_test_utxos();
+    fn test_bnb_function_tries_exceeded() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        let utxos: Vec<OutputGroup> = generate_same_value_utxos(Amount::from_sat(100_000), 100_000)
+            .into_iter()
+            .map(|u| OutputGroup::new(u, fee_rate))
+            .collect();
+
+        let curr_available_value = utxos
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+
+        let size_of_change = 31;
+        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
+            .to_signed()
+            .unwrap();
+        let target_amount = SignedAmount::from_sat(20_000) + FEE_AMOUNT.to_signed().unwrap();
+
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
 
-        let result = LargestFirstCoinSelection.coin_select(
+        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw).bnb(
             vec![],
             utxos,
-            FeeRate::from_sat_per_vb_u32(1000),
+            SignedAmount::ZERO,
+            curr_available_value,
             target_amount,
+            cost_of_change,
             &drain_script,
-            &mut thread_rng(),
+            fee_rate,
         );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
+        assert!(matches!(result, Err(BnbError::TotalTriesExceeded)));
     }
 
+    // The match won't be exact but still in the range
     #[test]
-    fn test_oldest_first_coin_selection_success() {
-        let utxos = get_oldest_first_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(180_000) + FEE_AMOUNT;
-
-        let result = OldestFirstCoinSelection
-            .coin_select(
-                vec![],
-                utxos,
-                FeeRate::from_sat_per_vb_u32(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
+    fn test_bnb_function_almost_exact_match_with_fees() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        let size_of_change = 31;
+        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
+            .to_signed()
             .unwrap();
 
-        assert_eq!(result.selected.len(), 2);
-        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(136));
-    }
+        let utxos: Vec<_> = generate_same_value_utxos(Amount::from_sat(50_000), 10)
+            .into_iter()
+            .map(|u| OutputGroup::new(u, fee_rate))
+            .collect();
 
-    #[test]
-    fn test_oldest_first_coin_selection_use_all() {
-        let utxos = get_oldest_first_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+        let curr_value = SignedAmount::ZERO;
 
-        let result = OldestFirstCoinSelection
-            .coin_select(
-                utxos,
-                vec![],
-                FeeRate::from_sat_per_vb_u32(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
-            .unwrap();
+        let curr_available_value = utxos
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(500_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
-    }
+        // 2*(value of 1 utxo)  - 2*(1 utxo fees with 1.0sat/vbyte fee rate) -
+        // cost_of_change + 5.
+        let target_amount = 2 * 50_000 - 2 * 67 - cost_of_change.to_sat() + 5;
+        let target_amount = SignedAmount::from_sat(target_amount);
 
-    #[test]
-    fn test_oldest_first_coin_selection_uses_all_optional_with_foreign_utxo_locals_sorted_first() {
-        let utxos = get_oldest_first_test_utxos();
-        let mut all_utxos = vec![foreign_utxo(Amount::from_sat(120_000), 1)];
-        all_utxos.extend_from_slice(&utxos);
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(619_000) + FEE_AMOUNT;
 
-        let result = OldestFirstCoinSelection
-            .coin_select(
+        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw)
+            .bnb(
                 vec![],
-                all_utxos,
-                FeeRate::from_sat_per_vb_u32(1),
+                utxos,
+                curr_value,
+                curr_available_value,
                 target_amount,
+                cost_of_change,
                 &drain_script,
-                &mut thread_rng(),
+                fee_rate,
             )
             .unwrap();
-
-        assert_eq!(result.selected.len(), 4);
-        assert_eq!(result.selected_amount(), Amount::from_sat(620_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(272));
-        assert!(matches!(result.selected[3], Utxo::Foreign { .. }));
+        assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(136));
     }
 
+    // TODO: bnb() function should be optimized, and this test should be done with more utxos
     #[test]
-    fn test_oldest_first_coin_selection_uses_only_all_optional_local_utxos_not_a_single_foreign() {
-        let utxos = get_oldest_first_test_utxos();
-        let mut all_utxos = vec![foreign_utxo(Amount::from_sat(120_000), 1)];
-        all_utxos.extend_from_slice(&utxos);
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(499_000) + FEE_AMOUNT;
-
-        let result = OldestFirstCoinSelection
-            .coin_select(
-                vec![],
-                all_utxos,
-                FeeRate::from_sat_per_vb_u32(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
-            .unwrap();
+    fn test_bnb_function_exact_match_more_utxos() {
+        let seed = [0; 32];
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let fee_rate = FeeRate::ZERO;
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(500_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
-        assert!(result
-            .selected
-            .iter()
-            .all(|utxo| matches!(utxo, Utxo::Local(..))));
-    }
+        for _ in 0..200 {
+            let optional_utxos: Vec<_> = generate_random_utxos(&mut rng, 40)
+                .into_iter()
+                .map(|u| OutputGroup::new(u, fee_rate))
+                .collect();
 
-    #[test]
-    fn test_oldest_first_coin_selection_use_only_necessary() {
-        let utxos = get_oldest_first_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+            let curr_value = SignedAmount::ZERO;
 
-        let result = OldestFirstCoinSelection
-            .coin_select(
-                vec![],
-                utxos,
-                FeeRate::from_sat_per_vb_u32(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
-            .unwrap();
+            let curr_available_value = optional_utxos
+                .iter()
+                .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
 
-        assert_eq!(result.selected.len(), 1);
-        assert_eq!(result.selected_amount(), Amount::from_sat(120_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(68));
-    }
+            let target_amount =
+                optional_utxos[3].effective_value + optional_utxos[23].effective_value;
 
-    #[test]
-    fn test_oldest_first_coin_selection_insufficient_funds() {
-        let utxos = get_oldest_first_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(600_000) + FEE_AMOUNT;
+            let drain_script = ScriptBuf::default();
 
-        let result = OldestFirstCoinSelection.coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_u32(1),
-            target_amount,
-            &drain_script,
-            &mut thread_rng(),
-        );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
+            let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+                .bnb(
+                    vec![],
+                    optional_utxos,
+                    curr_value,
+                    curr_available_value,
+                    target_amount,
+                    SignedAmount::ZERO,
+                    &drain_script,
+                    fee_rate,
+                )
+                .unwrap();
+            assert_eq!(
+                result.selected_amount(),
+                target_amount.to_unsigned().unwrap()
+            );
+        }
     }
 
     #[test]
-    fn test_oldest_first_coin_selection_insufficient_funds_high_fees() {
-        let utxos = get_oldest_first_test_utxos();
-
-        let target_amount =
-            utxos.iter().map(|wu| wu.utxo.txout().value).sum::<Amount>() - Amount::from_sat(50);
+    fn test_bnb_exclude_negative_effective_value() {
+        let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
 
-        let result = OldestFirstCoinSelection.coin_select(
+        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
             vec![],
             utxos,
-            FeeRate::from_sat_per_vb_u32(1000),
-            target_amount,
+            FeeRate::from_sat_per_vb_u32(10),
+            Amount::from_sat(500_000),
             &drain_script,
             &mut thread_rng(),
         );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
+
+        assert_matches!(
+            selection,
+            Err(InsufficientFunds {
+                available,
+                ..
+            }) if available.to_sat() == 300_000
+        );
     }
 
     #[test]
-    fn test_bnb_coin_selection_success() {
-        // In this case bnb won't find a suitable match and single random draw will
-        // select three outputs
-        let utxos = generate_same_value_utxos(Amount::from_sat(100_000), 20);
+    fn test_bnb_include_negative_effective_value_when_required() {
+        let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
 
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
-            .coin_select(
-                vec![],
-                utxos,
-                FeeRate::from_sat_per_vb_u32(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
-            .unwrap();
+        let (required, optional) = utxos.into_iter().partition(
+            |u| matches!(u, WeightedUtxo { utxo, .. } if utxo.txout().value.to_sat() < 1000),
+        );
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
+        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+            required,
+            optional,
+            FeeRate::from_sat_per_vb_u32(10),
+            Amount::from_sat(500_000),
+            &drain_script,
+            &mut thread_rng(),
+        );
+
+        assert_matches!(
+            selection,
+            Err(InsufficientFunds {
+                available,
+                ..
+            }) if available.to_sat() == 300_010
+        );
     }
 
     #[test]
-    fn test_bnb_coin_selection_required_are_enough() {
+    fn test_bnb_sum_of_effective_value_negative() {
         let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
 
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
-            .coin_select(
-                utxos.clone(),
-                utxos,
-                FeeRate::from_sat_per_vb_u32(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
-            .unwrap();
+        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+            utxos,
+            vec![],
+            FeeRate::from_sat_per_vb_u32(10_000),
+            Amount::from_sat(500_000),
+            &drain_script,
+            &mut thread_rng(),
+        );
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
+        assert_matches!(
+            selection,
+            Err(InsufficientFunds {
+                available,
+                ..
+            }) if available.to_sat() == 300_010
+        );
     }
 
     #[test]
-    fn test_bnb_coin_selection_optional_are_enough() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let fee_rate = FeeRate::BROADCAST_MIN;
-        // first and third utxo's effective value
-        let target_amount = calc_target_amount(&[utxos[0].clone(), utxos[2].clone()], fee_rate);
-
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+    fn test_bnb_fallback_algorithm() {
+        // utxo value
+        // 120k + 80k + 300k
+        let optional_utxos = get_oldest_first_test_utxos();
+        let feerate = FeeRate::BROADCAST_MIN;
+        let target_amount = Amount::from_sat(190_000);
+        let drain_script = ScriptBuf::new();
+        // bnb won't find exact match and should select oldest first
+        let bnb_with_oldest_first =
+            BranchAndBoundCoinSelection::new(8 + 1 + 22, OldestFirstCoinSelection);
+        let res = bnb_with_oldest_first
             .coin_select(
                 vec![],
-                utxos,
-                fee_rate,
+                optional_utxos,
+                feerate,
                 target_amount,
                 &drain_script,
                 &mut thread_rng(),
             )
             .unwrap();
-
-        assert_eq!(result.selected.len(), 2);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300000));
-        assert_eq!(result.fee_amount, Amount::from_sat(136));
+        assert_eq!(res.selected_amount(), Amount::from_sat(200_000));
+        assert_eq!(
+            res.algorithms,
+            ["BranchAndBoundCoinSelection", "OldestFirstCoinSelection"]
+        );
     }
 
     #[test]
-    fn test_single_random_draw_function_success() {
-        let seed = [0; 32];
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
-        let mut utxos = generate_random_utxos(&mut rng, 300);
-        let target_amount = sum_random_utxos(&mut rng, &mut utxos) + FEE_AMOUNT;
-        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
-        let drain_script = ScriptBuf::default();
-
-        let result = SingleRandomDraw.coin_select(
-            vec![],
-            utxos,
-            fee_rate,
-            target_amount,
-            &drain_script,
-            &mut thread_rng(),
-        );
+    fn test_deterministic_coin_selection_picks_same_utxos() {
+        enum CoinSelectionAlgo {
+            BranchAndBound,
+            OldestFirst,
+            LargestFirst,
+        }
 
-        assert!(
-            matches!(result, Ok(CoinSelectionResult {selected, fee_amount, ..})
-                if selected.iter().map(|u| u.txout().value).sum::<Amount>() > target_amount
-                && fee_amount == Amount::from_sat(selected.len() as u64 * 68)
-            )
-        );
-    }
+        struct TestCase<'a> {
+            name: &'a str,
+            coin_selection_algo: CoinSelectionAlgo,
+            exp_vouts: &'a [u32],
+        }
 
-    #[test]
-    fn test_single_random_draw_function_error() {
-        let seed = [0; 32];
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let test_cases = [
+            TestCase {
+                name: "branch and bound",
+                coin_selection_algo: CoinSelectionAlgo::BranchAndBound,
+                // note: we expect these to be sorted largest first, which indicates
+                // BnB succeeded with no fallback
+                exp_vouts: &[29, 28, 27],
+            },
+            TestCase {
+                name: "oldest first",
+                coin_selection_algo: CoinSelectionAlgo::OldestFirst,
+                exp_vouts: &[0, 1, 2],
+            },
+            TestCase {
+                name: "largest first",
+                coin_selection_algo: CoinSelectionAlgo::LargestFirst,
+                exp_vouts: &[29, 28, 27],
+            },
+        ];
 
-        // 100_000, 10, 200_000
-        let utxos = get_test_utxos();
-        let target_amount = Amount::from_sat(300_000) + FEE_AMOUNT;
+        let optional = generate_same_value_utxos(Amount::from_sat(100_000), 30);
         let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        let target_amount = calc_target_amount(&optional[0..3], fee_rate);
+        assert_eq!(target_amount, Amount::from_sat(299_796));
         let drain_script = ScriptBuf::default();
 
-        let result = SingleRandomDraw.coin_select(
-            vec![],
-            utxos,
-            fee_rate,
-            target_amount,
-            &drain_script,
-            &mut rng,
-        );
-
-        assert!(matches!(result, Err(InsufficientFunds {needed, available})
-                if needed == Amount::from_sat(300_254) && available == Amount::from_sat(300_010)));
-    }
+        for tc in test_cases {
+            let optional = optional.clone();
 
-    #[test]
-    fn test_bnb_coin_selection_required_not_enough() {
-        let utxos = get_test_utxos();
+            let result = match tc.coin_selection_algo {
+                CoinSelectionAlgo::BranchAndBound => {
+                    BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+                        vec![],
+                        optional,
+                        fee_rate,
+                        target_amount,
+                        &drain_script,
+                        &mut thread_rng(),
+                    )
+                }
+                CoinSelectionAlgo::OldestFirst => OldestFirstCoinSelection.coin_select(
+                    vec![],
+                    optional,
+                    fee_rate,
+                    target_amount,
+                    &drain_script,
+                    &mut thread_rng(),
+                ),
+                CoinSelectionAlgo::LargestFirst => LargestFirstCoinSelection.coin_select(
+                    vec![],
+                    optional,
+                    fee_rate,
+                    target_amount,
+                    &drain_script,
+                    &mut thread_rng(),
+                ),
+            };
 
-        let required = vec![utxos[0].clone()];
-        let mut optional = utxos[1..].to_vec();
-        optional.push(utxo(
-            Amount::from_sat(500_000),
-            3,
-            ChainPosition::<ConfirmationBlockTime>::Unconfirmed {
-                first_seen: Some(1),
-                last_seen: Some(1),
-            },
-        ));
+            assert!(result.is_ok(), "coin_select failed {}", tc.name);
+            let result = result.unwrap();
+            assert!(matches!(result.excess, Excess::NoChange { .. },));
+            assert_eq!(
+                result.selected.len(),
+                3,
+                "wrong selected len for {}",
+                tc.name
+            );
+            assert_eq!(
+                result.selected_amount(),
+                Amount::from_sat(300_000),
+                "wrong selected amount for {}",
+                tc.name
+            );
+            assert_eq!(
+                result.fee_amount,
+                Amount::from_sat(204),
+                "wrong fee amount for {}",
+                tc.name
+            );
+            let vouts = result
+                .selected
+                .iter()
+                .map(|utxo| utxo.outpoint().vout)
+                .collect::<Vec<u32>>();
+            assert_eq!(vouts, tc.exp_vouts, "wrong selected vouts for {}", tc.name);
+        }
+    }
 
-        // Defensive assertions, for sanity and in case someone changes the test utxos vector.
-        let amount = required
-            .iter()
-            .map(|u| u.utxo.txout().value)
-            .sum::<Amount>();
-        assert_eq!(amount, Amount::from_sat(100_000));
-        let amount = optional
-            .iter()
-            .map(|u| u.utxo.txout().value)
-            .sum::<Amount>();
-        assert!(amount > Amount::from_sat(150_000));
+    #[test]
+    fn test_coin_grinder_lightest_selection() {
+        let heavy = WeightedUtxo {
+            satisfaction_weight: Weight::from_wu(2_000),
+            ..confirmed_utxo(Amount::from_sat(150_000), 1, 1, 1231006505)
+        };
+        let optional = vec![
+            heavy,
+            confirmed_utxo(Amount::from_sat(60_000), 2, 2, 1231006505),
+            confirmed_utxo(Amount::from_sat(60_000), 3, 3, 1231006505),
+        ];
         let drain_script = ScriptBuf::default();
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        let target_amount = Amount::from_sat(100_000);
 
-        let fee_rate = FeeRate::BROADCAST_MIN;
-        // first and third utxo's effective value
-        let target_amount = calc_target_amount(&[utxos[0].clone(), utxos[2].clone()], fee_rate);
-
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+        // The largest UTXO is enough, but the two light ones weigh less
+        let result = CoinGrinderCoinSelection::<SingleRandomDraw>::default()
             .coin_select(
-                required,
+                vec![],
                 optional,
                 fee_rate,
                 target_amount,
@@ -1347,445 +2855,480 @@ mod test {
                 &mut thread_rng(),
             )
             .unwrap();
-
         assert_eq!(result.selected.len(), 2);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(136));
+        assert_eq!(result.selected_amount(), Amount::from_sat(120_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(2 * 680));
+        assert_matches!(result.excess, Excess::Change { .. });
     }
 
     #[test]
-    fn test_bnb_coin_selection_insufficient_funds() {
-        let utxos = get_test_utxos();
+    fn test_coin_grinder_fewer_inputs() {
+        let optional = vec![
+            confirmed_utxo(Amount::from_sat(40_000), 1, 1, 1231006505),
+            confirmed_utxo(Amount::from_sat(40_000), 2, 2, 1231006505),
+            confirmed_utxo(Amount::from_sat(130_000), 3, 3, 1231006505),
+            confirmed_utxo(Amount::from_sat(40_000), 4, 4, 1231006505),
+            confirmed_utxo(Amount::from_sat(60_000), 5, 5, 1231006505),
+        ];
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(500_000) + FEE_AMOUNT;
 
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_u32(1),
-            target_amount,
-            &drain_script,
-            &mut thread_rng(),
-        );
+        let result = CoinGrinderCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                vec![],
+                optional.clone(),
+                FeeRate::from_sat_per_vb_u32(20),
+                Amount::from_sat(110_000),
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(130_000));
 
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
+        // The change must be at least `min_change`
+        let result = CoinGrinderCoinSelection::new(Amount::from_sat(30_000), SingleRandomDraw)
+            .coin_select(
+                vec![],
+                optional,
+                FeeRate::from_sat_per_vb_u32(20),
+                Amount::from_sat(110_000),
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(170_000));
+        assert_matches!(result.excess, Excess::Change { amount, .. } if amount >= Amount::from_sat(30_000));
     }
 
     #[test]
-    fn test_bnb_coin_selection_insufficient_funds_high_fees() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
-
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_u32(1000),
-            target_amount,
-            &drain_script,
-            &mut thread_rng(),
-        );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    fn test_coin_grinder_required() {
+        let utxos = get_oldest_first_test_utxos();
+        let (required, optional) = utxos
+            .into_iter()
+            .partition(|u| u.utxo.txout().value == Amount::from_sat(80_000));
+        let result = CoinGrinderCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                required,
+                optional,
+                FeeRate::from_sat_per_vb_u32(5),
+                Amount::from_sat(150_000),
+                &ScriptBuf::default(),
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
     }
 
     #[test]
-    fn test_bnb_coin_selection_check_fee_rate() {
-        let utxos = get_test_utxos();
+    fn test_coin_grinder_fallback() {
+        // 120k + 80k + 300k
+        let optional = get_oldest_first_test_utxos();
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
         let drain_script = ScriptBuf::default();
-        let fee_rate = FeeRate::BROADCAST_MIN;
-        // first utxo's effective value
-        let target_amount = calc_target_amount(&utxos[0..1], fee_rate);
-
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+        // No change output fits, the fallback selects all the UTXOs
+        let target_amount = calc_target_amount(&optional, fee_rate);
+        let result = CoinGrinderCoinSelection::new(Amount::ZERO, LargestFirstCoinSelection)
             .coin_select(
                 vec![],
-                utxos,
+                optional.clone(),
                 fee_rate,
                 target_amount,
                 &drain_script,
                 &mut thread_rng(),
             )
             .unwrap();
+        assert_eq!(result.selected.len(), 3);
+        assert_matches!(result.excess, Excess::NoChange { .. });
 
-        assert_eq!(result.selected.len(), 1);
-        assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
-        let input_weight =
-            TxIn::default().segwit_weight().to_wu() + P2WPKH_SATISFACTION_SIZE as u64;
-        // the final fee rate should be exactly the same as the fee rate given
-        let result_feerate = result.fee_amount / Weight::from_wu(input_weight);
-        assert_eq!(result_feerate, fee_rate);
+        let result = CoinGrinderCoinSelection::<SingleRandomDraw>::default().coin_select(
+            vec![],
+            optional,
+            fee_rate,
+            Amount::from_sat(600_000),
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert_matches!(
+            result,
+            Err(InsufficientFunds { available, .. }) if available == Amount::from_sat(500_000)
+        );
     }
 
     #[test]
-    fn test_bnb_coin_selection_exact_match() {
+    fn test_coin_grinder_many_utxos() {
         let seed = [0; 32];
         let mut rng: StdRng = SeedableRng::from_seed(seed);
-
-        for _i in 0..200 {
-            let mut optional_utxos = generate_random_utxos(&mut rng, 16);
-            let target_amount = sum_random_utxos(&mut rng, &mut optional_utxos);
-            let drain_script = ScriptBuf::default();
-            let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
-                .coin_select(
-                    vec![],
-                    optional_utxos,
-                    FeeRate::ZERO,
-                    target_amount,
-                    &drain_script,
-                    &mut thread_rng(),
-                )
-                .unwrap();
-            assert_eq!(result.selected_amount(), target_amount);
-        }
-    }
-
-    #[test]
-    fn test_bnb_function_no_exact_match() {
+        let optional = generate_random_utxos(&mut rng, 1_000);
+        let target_amount = Amount::from_sat(1_000_000_000);
         let fee_rate = FeeRate::from_sat_per_vb_u32(10);
-        let utxos: Vec<OutputGroup> = get_test_utxos()
-            .into_iter()
-            .map(|u| OutputGroup::new(u, fee_rate))
-            .collect();
-
-        let curr_available_value = utxos
-            .iter()
-            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
 
-        let size_of_change = 31;
-        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
-            .to_signed()
+        let result = CoinGrinderCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                vec![],
+                optional,
+                fee_rate,
+                target_amount,
+                &ScriptBuf::default(),
+                &mut rng,
+            )
             .unwrap();
+        assert!(result.selected_amount() > target_amount + result.fee_amount);
+    }
 
-        let drain_script = ScriptBuf::default();
-        let target_amount = SignedAmount::from_sat(20_000) + FEE_AMOUNT.to_signed().unwrap();
-        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw).bnb(
+    fn knapsack_select(
+        coin_selection: impl CoinSelectionAlgorithm,
+        values: &[u64],
+        target_amount: u64,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let optional = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| unconfirmed_utxo(Amount::from_sat(*value), i as u32, 0))
+            .collect();
+        coin_selection.coin_select(
             vec![],
-            utxos,
-            SignedAmount::ZERO,
-            curr_available_value,
-            target_amount,
-            cost_of_change,
-            &drain_script,
-            fee_rate,
-        );
-        assert!(matches!(result, Err(BnbError::NoExactMatch)));
+            optional,
+            FeeRate::ZERO,
+            Amount::from_sat(target_amount),
+            &ScriptBuf::default(),
+            &mut StdRng::seed_from_u64(42),
+        )
     }
 
     #[test]
-    fn test_bnb_function_tries_exceeded() {
-        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
-        let utxos: Vec<OutputGroup> = generate_same_value_utxos(Amount::from_sat(100_000), 100_000)
-            .into_iter()
-            .map(|u| OutputGroup::new(u, fee_rate))
-            .collect();
+    fn test_knapsack_exact_match() {
+        let coin_selection = KnapsackCoinSelection::default();
+        let result =
+            knapsack_select(coin_selection, &[5_000, 3_000, 2_000, 7_000], 10_000).unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(10_000));
+        assert_matches!(result.excess, Excess::NoChange { remaining_amount, .. } if remaining_amount == Amount::ZERO);
+
+        // A single UTXO or all the smaller UTXOs matching the target
+        let result = knapsack_select(coin_selection, &[5_000, 10_000, 70_000], 10_000).unwrap();
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(10_000));
+        let result =
+            knapsack_select(coin_selection, &[5_000, 2_000, 3_000, 70_000], 10_000).unwrap();
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(10_000));
+    }
 
-        let curr_available_value = utxos
-            .iter()
-            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+    #[test]
+    fn test_knapsack_min_change() {
+        let coin_selection = KnapsackCoinSelection::new(Amount::from_sat(10_000));
 
-        let size_of_change = 31;
-        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
-            .to_signed()
-            .unwrap();
-        let target_amount = SignedAmount::from_sat(20_000) + FEE_AMOUNT.to_signed().unwrap();
+        // 20k + 30k is closest to the target but leaves too little change
+        let result = knapsack_select(coin_selection, &[20_000, 30_000, 40_000], 45_000).unwrap();
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(60_000));
 
-        let drain_script = ScriptBuf::default();
+        // A single larger UTXO leaving less change is preferred
+        let result =
+            knapsack_select(coin_selection, &[20_000, 30_000, 40_000, 58_000], 45_000).unwrap();
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(58_000));
+
+        // Without enough smaller UTXOs the smallest larger one is selected
+        let result =
+            knapsack_select(coin_selection, &[1_000, 2_000, 100_000, 80_000], 5_000).unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(80_000));
+
+        // With a lower change target 20k + 30k is enough
+        let result = knapsack_select(
+            KnapsackCoinSelection::new(Amount::from_sat(1_000)),
+            &[20_000, 30_000, 40_000],
+            45_000,
+        )
+        .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(50_000));
+    }
 
-        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw).bnb(
-            vec![],
-            utxos,
-            SignedAmount::ZERO,
-            curr_available_value,
-            target_amount,
-            cost_of_change,
-            &drain_script,
-            fee_rate,
+    #[test]
+    fn test_knapsack_insufficient_funds() {
+        let result = knapsack_select(KnapsackCoinSelection::default(), &[1_000, 2_000], 5_000);
+        assert_matches!(
+            result,
+            Err(InsufficientFunds { needed, available })
+                if needed == Amount::from_sat(5_000) && available == Amount::from_sat(3_000)
         );
-        assert!(matches!(result, Err(BnbError::TotalTriesExceeded)));
     }
 
-    // The match won't be exact but still in the range
     #[test]
-    fn test_bnb_function_almost_exact_match_with_fees() {
+    fn test_knapsack_bnb_fallback() {
+        // 120k + 80k + 300k
+        let optional = get_oldest_first_test_utxos();
         let fee_rate = FeeRate::from_sat_per_vb_u32(1);
-        let size_of_change = 31;
-        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
-            .to_signed()
-            .unwrap();
-
-        let utxos: Vec<_> = generate_same_value_utxos(Amount::from_sat(50_000), 10)
-            .into_iter()
-            .map(|u| OutputGroup::new(u, fee_rate))
-            .collect();
-
-        let curr_value = SignedAmount::ZERO;
+        let target_amount = Amount::from_sat(190_000);
 
-        let curr_available_value = utxos
-            .iter()
-            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+        // BnB finds no exact match, the knapsack aims for a change of 50k
+        let result = BranchAndBoundCoinSelection::<KnapsackCoinSelection>::default()
+            .coin_select(
+                vec![],
+                optional,
+                fee_rate,
+                target_amount,
+                &ScriptBuf::default(),
+                &mut StdRng::seed_from_u64(42),
+            )
+            .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
+        assert_matches!(result.excess, Excess::Change { .. });
+    }
 
-        // 2*(value of 1 utxo)  - 2*(1 utxo fees with 1.0sat/vbyte fee rate) -
-        // cost_of_change + 5.
-        let target_amount = 2 * 50_000 - 2 * 67 - cost_of_change.to_sat() + 5;
-        let target_amount = SignedAmount::from_sat(target_amount);
+    fn with_script_pubkey(mut weighted_utxo: WeightedUtxo, script_pubkey: &str) -> WeightedUtxo {
+        if let Utxo::Local(local) = &mut weighted_utxo.utxo {
+            local.txout.script_pubkey = ScriptBuf::from_hex(script_pubkey).unwrap();
+        }
+        weighted_utxo
+    }
 
+    #[test]
+    fn test_avoid_partial_spends() {
+        let reused = "0014b7eb7c3b3e6f1a1c2a8d5ee0c1d9e8f3a2b4c6d8";
+        let other = "0014c8fc8d4c4f7f2b2d3b9e6ff1d2eaf9f4b3c5d7e9";
+        let optional = vec![
+            with_script_pubkey(unconfirmed_utxo(Amount::from_sat(100_000), 0, 0), reused),
+            with_script_pubkey(unconfirmed_utxo(Amount::from_sat(20_000), 1, 0), reused),
+            with_script_pubkey(unconfirmed_utxo(Amount::from_sat(50_000), 2, 0), other),
+        ];
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
         let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(90_000);
 
-        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw)
-            .bnb(
+        let result = LargestFirstCoinSelection
+            .coin_select(
                 vec![],
-                utxos,
-                curr_value,
-                curr_available_value,
+                optional.clone(),
+                fee_rate,
                 target_amount,
-                cost_of_change,
                 &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected.len(), 1);
+
+        // The whole group of the reused address is spent
+        let result = AvoidPartialSpendsCoinSelection::new(LargestFirstCoinSelection)
+            .coin_select(
+                vec![],
+                optional,
                 fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
             )
             .unwrap();
-        assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(136));
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected[0].txout().value, Amount::from_sat(100_000));
+        assert_eq!(result.selected[1].txout().value, Amount::from_sat(20_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(2 * 68));
     }
 
-    // TODO: bnb() function should be optimized, and this test should be done with more utxos
     #[test]
-    fn test_bnb_function_exact_match_more_utxos() {
-        let seed = [0; 32];
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
-        let fee_rate = FeeRate::ZERO;
-
-        for _ in 0..200 {
-            let optional_utxos: Vec<_> = generate_random_utxos(&mut rng, 40)
-                .into_iter()
-                .map(|u| OutputGroup::new(u, fee_rate))
-                .collect();
-
-            let curr_value = SignedAmount::ZERO;
-
-            let curr_available_value = optional_utxos
-                .iter()
-                .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
-
-            let target_amount =
-                optional_utxos[3].effective_value + optional_utxos[23].effective_value;
-
-            let drain_script = ScriptBuf::default();
+    fn test_avoid_partial_spends_required() {
+        let reused = "0014b7eb7c3b3e6f1a1c2a8d5ee0c1d9e8f3a2b4c6d8";
+        let other = "0014c8fc8d4c4f7f2b2d3b9e6ff1d2eaf9f4b3c5d7e9";
+        let required = vec![with_script_pubkey(
+            unconfirmed_utxo(Amount::from_sat(20_000), 1, 0),
+            reused,
+        )];
+        let optional = vec![
+            with_script_pubkey(unconfirmed_utxo(Amount::from_sat(50_000), 2, 0), other),
+            with_script_pubkey(unconfirmed_utxo(Amount::from_sat(10_000), 0, 0), reused),
+        ];
 
-            let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
-                .bnb(
-                    vec![],
-                    optional_utxos,
-                    curr_value,
-                    curr_available_value,
-                    target_amount,
-                    SignedAmount::ZERO,
-                    &drain_script,
-                    fee_rate,
-                )
-                .unwrap();
-            assert_eq!(
-                result.selected_amount(),
-                target_amount.to_unsigned().unwrap()
-            );
-        }
+        let result = AvoidPartialSpendsCoinSelection::new(OldestFirstCoinSelection)
+            .coin_select(
+                required,
+                optional,
+                FeeRate::from_sat_per_vb_u32(1),
+                Amount::from_sat(25_000),
+                &ScriptBuf::default(),
+                &mut thread_rng(),
+            )
+            .unwrap();
+        let mut values = result
+            .selected
+            .iter()
+            .map(|utxo| utxo.txout().value.to_sat())
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, [10_000, 20_000]);
     }
 
     #[test]
-    fn test_bnb_exclude_negative_effective_value() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
+    fn test_avoid_partial_spends_max_entries() {
+        // All the UTXOs have the same script pubkey
+        let optional = generate_same_value_utxos(Amount::from_sat(1_000), 150);
 
-        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+        let result = AvoidPartialSpendsCoinSelection::new(LargestFirstCoinSelection)
+            .coin_select(
+                vec![],
+                optional,
+                FeeRate::from_sat_per_vb_u32(1),
+                Amount::from_sat(10_000),
+                &ScriptBuf::default(),
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected.len(), OUTPUT_GROUP_MAX_ENTRIES);
+        assert!(result
+            .selected
+            .iter()
+            .all(|utxo| utxo.txout().value == Amount::from_sat(1_000)));
+
+        // The 50 UTXOs left after the full group are not available
+        let optional = generate_same_value_utxos(Amount::from_sat(1_000), 150);
+        let result = AvoidPartialSpendsCoinSelection::new(LargestFirstCoinSelection).coin_select(
             vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_u32(10),
-            Amount::from_sat(500_000),
-            &drain_script,
+            optional,
+            FeeRate::from_sat_per_vb_u32(1),
+            Amount::from_sat(120_000),
+            &ScriptBuf::default(),
             &mut thread_rng(),
         );
-
-        assert_matches!(
-            selection,
-            Err(InsufficientFunds {
-                available,
-                ..
-            }) if available.to_sat() == 300_000
-        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
     }
 
     #[test]
-    fn test_bnb_include_negative_effective_value_when_required() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-
-        let (required, optional) = utxos.into_iter().partition(
-            |u| matches!(u, WeightedUtxo { utxo, .. } if utxo.txout().value.to_sat() < 1000),
+    fn test_waste() {
+        let long_term_feerate = FeeRate::from_sat_per_vb_u32(10);
+        let change_spend_weight = Weight::from_wu(272);
+        let mut result = CoinSelectionResult::new(
+            vec![],
+            Amount::from_sat(68),
+            Excess::Change {
+                amount: Amount::from_sat(50_000),
+                fee: Amount::from_sat(31),
+            },
         );
-
-        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            required,
-            optional,
-            FeeRate::from_sat_per_vb_u32(10),
-            Amount::from_sat(500_000),
-            &drain_script,
-            &mut thread_rng(),
+        result.input_weight = Weight::from_wu(272);
+        // 68 - 680 + 31 + 680
+        assert_eq!(
+            result.waste(long_term_feerate, change_spend_weight),
+            SignedAmount::from_sat(99)
         );
 
-        assert_matches!(
-            selection,
-            Err(InsufficientFunds {
-                available,
-                ..
-            }) if available.to_sat() == 300_010
+        let result = CoinSelectionResult {
+            excess: Excess::NoChange {
+                dust_threshold: Amount::from_sat(294),
+                remaining_amount: Amount::from_sat(100),
+                change_fee: Amount::from_sat(31),
+            },
+            ..result
+        };
+        // 68 - 680 + 100
+        assert_eq!(
+            result.waste(long_term_feerate, change_spend_weight),
+            SignedAmount::from_sat(-512)
         );
     }
 
     #[test]
-    fn test_bnb_sum_of_effective_value_negative() {
-        let utxos = get_test_utxos();
+    fn test_lowest_waste_coin_selection() {
+        // 120k + 80k + 300k, oldest first
+        let utxos = get_oldest_first_test_utxos();
+        let target_amount = Amount::from_sat(150_000);
         let drain_script = ScriptBuf::default();
+        let change_spend_weight = Weight::from_wu(272);
 
-        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            utxos,
-            vec![],
-            FeeRate::from_sat_per_vb_u32(10_000),
-            Amount::from_sat(500_000),
-            &drain_script,
-            &mut thread_rng(),
+        // Low fees compared to the long term fee rate favor spending more inputs
+        let coin_selection = LowestWasteCoinSelection::new(
+            (LargestFirstCoinSelection, OldestFirstCoinSelection),
+            FeeRate::from_sat_per_vb_u32(10),
+            change_spend_weight,
         );
-
-        assert_matches!(
-            selection,
-            Err(InsufficientFunds {
-                available,
-                ..
-            }) if available.to_sat() == 300_010
+        let result = coin_selection
+            .coin_select(
+                vec![],
+                utxos.clone(),
+                FeeRate::from_sat_per_vb_u32(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
+        assert_eq!(
+            result.algorithms,
+            ["LowestWasteCoinSelection", "OldestFirstCoinSelection"]
         );
-    }
 
-    #[test]
-    fn test_bnb_fallback_algorithm() {
-        // utxo value
-        // 120k + 80k + 300k
-        let optional_utxos = get_oldest_first_test_utxos();
-        let feerate = FeeRate::BROADCAST_MIN;
-        let target_amount = Amount::from_sat(190_000);
-        let drain_script = ScriptBuf::new();
-        // bnb won't find exact match and should select oldest first
-        let bnb_with_oldest_first =
-            BranchAndBoundCoinSelection::new(8 + 1 + 22, OldestFirstCoinSelection);
-        let res = bnb_with_oldest_first
+        // High fees favor spending fewer inputs
+        let coin_selection = LowestWasteCoinSelection::new(
+            (OldestFirstCoinSelection, LargestFirstCoinSelection),
+            FeeRate::from_sat_per_vb_u32(1),
+            change_spend_weight,
+        );
+        let result = coin_selection
             .coin_select(
                 vec![],
-                optional_utxos,
-                feerate,
+                utxos,
+                FeeRate::from_sat_per_vb_u32(20),
                 target_amount,
                 &drain_script,
                 &mut thread_rng(),
             )
             .unwrap();
-        assert_eq!(res.selected_amount(), Amount::from_sat(200_000));
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
     }
 
     #[test]
-    fn test_deterministic_coin_selection_picks_same_utxos() {
-        enum CoinSelectionAlgo {
-            BranchAndBound,
-            OldestFirst,
-            LargestFirst,
-        }
-
-        struct TestCase<'a> {
-            name: &'a str,
-            coin_selection_algo: CoinSelectionAlgo,
-            exp_vouts: &'a [u32],
-        }
-
-        let test_cases = [
-            TestCase {
-                name: "branch and bound",
-                coin_selection_algo: CoinSelectionAlgo::BranchAndBound,
-                // note: we expect these to be sorted largest first, which indicates
-                // BnB succeeded with no fallback
-                exp_vouts: &[29, 28, 27],
-            },
-            TestCase {
-                name: "oldest first",
-                coin_selection_algo: CoinSelectionAlgo::OldestFirst,
-                exp_vouts: &[0, 1, 2],
-            },
-            TestCase {
-                name: "largest first",
-                coin_selection_algo: CoinSelectionAlgo::LargestFirst,
-                exp_vouts: &[29, 28, 27],
-            },
-        ];
-
-        let optional = generate_same_value_utxos(Amount::from_sat(100_000), 30);
+    fn test_lowest_waste_coin_selection_ties() {
+        let utxos = generate_same_value_utxos(Amount::from_sat(100_000), 30);
         let fee_rate = FeeRate::from_sat_per_vb_u32(1);
-        let target_amount = calc_target_amount(&optional[0..3], fee_rate);
-        assert_eq!(target_amount, Amount::from_sat(299_796));
+        let target_amount = calc_target_amount(&utxos[0..3], fee_rate);
         let drain_script = ScriptBuf::default();
-
-        for tc in test_cases {
-            let optional = optional.clone();
-
-            let result = match tc.coin_selection_algo {
-                CoinSelectionAlgo::BranchAndBound => {
-                    BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-                        vec![],
-                        optional,
-                        fee_rate,
-                        target_amount,
-                        &drain_script,
-                        &mut thread_rng(),
-                    )
-                }
-                CoinSelectionAlgo::OldestFirst => OldestFirstCoinSelection.coin_select(
-                    vec![],
-                    optional,
-                    fee_rate,
-                    target_amount,
-                    &drain_script,
-                    &mut thread_rng(),
-                ),
-                CoinSelectionAlgo::LargestFirst => LargestFirstCoinSelection.coin_select(
+        // All the random draws select three utxos and have the same waste
+        let coin_selection = LowestWasteCoinSelection::<(
+            SingleRandomDraw,
+            SingleRandomDraw,
+            SingleRandomDraw,
+        )>::default();
+
+        let select = |seed| {
+            coin_selection
+                .coin_select(
                     vec![],
-                    optional,
+                    utxos.clone(),
                     fee_rate,
                     target_amount,
                     &drain_script,
-                    &mut thread_rng(),
-                ),
-            };
-
-            assert!(result.is_ok(), "coin_select failed {}", tc.name);
-            let result = result.unwrap();
-            assert!(matches!(result.excess, Excess::NoChange { .. },));
-            assert_eq!(
-                result.selected.len(),
-                3,
-                "wrong selected len for {}",
-                tc.name
-            );
-            assert_eq!(
-                result.selected_amount(),
-                Amount::from_sat(300_000),
-                "wrong selected amount for {}",
-                tc.name
-            );
-            assert_eq!(
-                result.fee_amount,
-                Amount::from_sat(204),
-                "wrong fee amount for {}",
-                tc.name
-            );
-            let vouts = result
+                    &mut StdRng::seed_from_u64(seed),
+                )
+                .unwrap()
                 .selected
                 .iter()
-                .map(|utxo| utxo.outpoint().vout)
-                .collect::<Vec<u32>>();
-            assert_eq!(vouts, tc.exp_vouts, "wrong selected vouts for {}", tc.name);
+                .map(|utxo| utxo.outpoint())
+                .collect::<Vec<_>>()
+        };
+        for seed in 0..10 {
+            let selected = select(seed);
+            assert_eq!(selected.len(), 3);
+            assert_eq!(selected, select(seed));
         }
     }
+
+    #[test]
+    fn test_lowest_waste_coin_selection_insufficient_funds() {
+        let coin_selection = LowestWasteCoinSelection::<(
+            LargestFirstCoinSelection,
+            BranchAndBoundCoinSelection,
+        )>::default();
+        let result = coin_selection.coin_select(
+            vec![],
+            get_test_utxos(),
+            FeeRate::from_sat_per_vb_u32(1),
+            Amount::from_sat(500_000),
+            &ScriptBuf::default(),
+            &mut thread_rng(),
+        );
+        assert_matches!(
+            result,
+            Err(InsufficientFunds { available, .. }) if available == Amount::from_sat(300_010)
+        );
+    }
 }
@@ -14,12 +14,18 @@
 use crate::descriptor::policy::PolicyError;
 use crate::descriptor::{DescriptorError, ExtendedDescriptor};
 use crate::wallet::coin_selection;
-use crate::{descriptor, KeychainKind, LoadWithPersistError};
+use crate::wallet::signer::SignerError;
+use crate::wallet::tx_builder::AddForeignUtxoError;
+use crate::{descriptor, IndexOutOfBoundsError, KeychainKind, LoadWithPersistError};
 use alloc::{
     boxed::Box,
     string::{String, ToString},
 };
-use bitcoin::{absolute, psbt, Amount, BlockHash, Network, OutPoint, Sequence, Txid};
+use bdk_chain::local_chain::CannotConnectError;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{
+    absolute, psbt, Amount, BlockHash, FeeRate, Network, OutPoint, ScriptBuf, Sequence, Txid,
+};
 use core::fmt;
 
 /// The error type when loading a [`Wallet`] from a [`ChangeSet`].
@@ -84,6 +90,13 @@ pub enum LoadMismatch {
         /// The expected descriptor.
         expected: Option<Box<ExtendedDescriptor>>,
     },
+    /// Silent payment scan and spend keys do not match.
+    SilentPaymentKeys {
+        /// The loaded scan and spend public keys.
+        loaded: Box<(PublicKey, PublicKey)>,
+        /// The expected scan and spend public keys.
+        expected: Box<(PublicKey, PublicKey)>,
+    },
 }
 
 impl fmt::Display for LoadMismatch {
@@ -115,6 +128,13 @@ impl fmt::Display for LoadMismatch {
                         .map_or("None".to_string(), |d| d.to_string())
                 )
             }
+            LoadMismatch::SilentPaymentKeys { loaded, expected } => {
+                write!(
+                    f,
+                    "Silent payment keys mismatch: loaded scan key {} and spend key {}, expected scan key {} and spend key {}",
+                    loaded.0, loaded.1, expected.0, expected.1
+                )
+            }
         }
     }
 }
@@ -215,6 +235,41 @@ pub enum CreateTxError {
     MissingNonWitnessUtxo(OutPoint),
     /// Miniscript PSBT error
     MiniscriptPsbt(MiniscriptPsbtError),
+    /// An index passed to [`TxBuilder::subtract_fee_from`] doesn't refer to a recipient
+    ///
+    /// [`TxBuilder::subtract_fee_from`]: crate::wallet::tx_builder::TxBuilder::subtract_fee_from
+    SubtractFeeIndexOutOfBounds(IndexOutOfBoundsError),
+    /// Subtracting its share of the fee would leave the recipient below the dust limit
+    SubtractFeeBelowDust {
+        /// Index of the recipient
+        index: usize,
+        /// Share of the fee the recipient has to pay
+        fee: Amount,
+    },
+    /// A TRUC transaction spends an output of an unconfirmed non-TRUC transaction
+    TrucNonTrucUnconfirmedInput(OutPoint),
+    /// A TRUC transaction has more than one unconfirmed ancestor
+    TrucTooManyUnconfirmedAncestors,
+    /// A TRUC transaction is larger than allowed
+    TrucTooLarge {
+        /// Estimated virtual size of the transaction
+        vsize: u64,
+        /// Maximum virtual size of the transaction
+        max: u64,
+    },
+    /// A transaction paying a silent payment address spends a foreign input that may contribute
+    /// to the output keys
+    SilentPaymentForeignInput(OutPoint),
+    /// The private key of an input contributing to the silent payment output keys is not known
+    SilentPaymentMissingKey(OutPoint),
+    /// A transaction paying a silent payment address doesn't spend any input whose key can be
+    /// used to derive the output keys
+    SilentPaymentNoEligibleInput,
+    /// A [`ChangeStrategy::Custom`] split the change into no outputs, into dust outputs, or into
+    /// outputs worth more than the change
+    ///
+    /// [`ChangeStrategy::Custom`]: crate::wallet::tx_builder::ChangeStrategy::Custom
+    InvalidChangeSplit,
 }
 
 impl fmt::Display for CreateTxError {
@@ -281,6 +336,43 @@ impl fmt::Display for CreateTxError {
             CreateTxError::MiniscriptPsbt(err) => {
                 write!(f, "Miniscript PSBT error: {err}")
             }
+            CreateTxError::SubtractFeeIndexOutOfBounds(err) => {
+                write!(f, "Invalid output to subtract the fee from: {err}")
+            }
+            CreateTxError::TrucNonTrucUnconfirmedInput(outpoint) => {
+                write!(
+                    f,
+                    "TRUC transaction spends an unconfirmed non-TRUC output: {outpoint}"
+                )
+            }
+            CreateTxError::TrucTooManyUnconfirmedAncestors => {
+                write!(f, "TRUC transaction has more than one unconfirmed ancestor")
+            }
+            CreateTxError::TrucTooLarge { vsize, max } => {
+                write!(f, "TRUC transaction too large: {vsize} vB, max {max} vB")
+            }
+            CreateTxError::SilentPaymentForeignInput(outpoint) => {
+                write!(
+                    f,
+                    "Cannot pay a silent payment address with the foreign input {outpoint}"
+                )
+            }
+            CreateTxError::SilentPaymentMissingKey(outpoint) => {
+                write!(f, "Missing private key of silent payment input {outpoint}")
+            }
+            CreateTxError::SilentPaymentNoEligibleInput => {
+                write!(f, "No input can be used to pay a silent payment address")
+            }
+            CreateTxError::SubtractFeeBelowDust { index, fee } => {
+                write!(
+                    f,
+                    "Output {index} below the dust limit after subtracting a fee of {}",
+                    fee.display_dynamic()
+                )
+            }
+            CreateTxError::InvalidChangeSplit => {
+                write!(f, "Invalid split of the change into outputs")
+            }
         }
     }
 }
@@ -334,6 +426,10 @@ pub enum BuildFeeBumpError {
     FeeRateUnavailable,
     /// Input references an invalid output index in the previous transaction
     InvalidOutputIndex(OutPoint),
+    /// No transaction to replace was given
+    NoTransactions,
+    /// The transactions to replace have different versions
+    MixedVersions,
 }
 
 impl fmt::Display for BuildFeeBumpError {
@@ -360,8 +456,320 @@ impl fmt::Display for BuildFeeBumpError {
             Self::InvalidOutputIndex(op) => {
                 write!(f, "A txin referenced an invalid output: {op}")
             }
+            Self::NoTransactions => write!(f, "No transaction to replace"),
+            Self::MixedVersions => {
+                write!(f, "Transactions to replace have different versions")
+            }
         }
     }
 }
 
 impl core::error::Error for BuildFeeBumpError {}
+
+#[derive(Debug)]
+/// Error returned from [`Wallet::build_cpfp`]
+///
+/// [`Wallet::build_cpfp`]: super::Wallet::build_cpfp
+pub enum BuildCpfpError {
+    /// Thrown when a tx is not found in the internal database
+    TransactionNotFound(Txid),
+    /// Happens when trying to bump a transaction that is already confirmed
+    TransactionConfirmed(Txid),
+    /// The parent transaction has no unspent, unlocked output belonging to the wallet, nor an
+    /// unspent pay-to-anchor output
+    NoSpendableOutput(Txid),
+    /// The fee of the parent or of one of its unconfirmed ancestors can't be calculated
+    FeeRateUnavailable,
+}
+
+impl fmt::Display for BuildCpfpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TransactionNotFound(txid) => {
+                write!(
+                    f,
+                    "Transaction not found in the internal database with txid: {txid}"
+                )
+            }
+            Self::TransactionConfirmed(txid) => {
+                write!(f, "Transaction already confirmed with txid: {txid}")
+            }
+            Self::NoSpendableOutput(txid) => {
+                write!(
+                    f,
+                    "No spendable wallet output in transaction with txid: {txid}"
+                )
+            }
+            Self::FeeRateUnavailable => write!(f, "Fee rate unavailable"),
+        }
+    }
+}
+
+impl core::error::Error for BuildCpfpError {}
+
+#[derive(Debug)]
+/// Error returned from [`Wallet::check_replacement`]
+///
+/// [`Wallet::check_replacement`]: super::Wallet::check_replacement
+pub enum CheckReplacementError {
+    /// The replacement doesn't conflict with any unconfirmed transaction of the wallet
+    NoConflicts,
+    /// The fee of the replacement can't be calculated from the PSBT
+    Psbt(psbt::Error),
+    /// The fee of an evicted transaction can't be calculated
+    FeeUnavailable(Txid),
+}
+
+impl fmt::Display for CheckReplacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoConflicts => write!(f, "Transaction doesn't replace any transaction"),
+            Self::Psbt(err) => write!(f, "Failed to calculate the replacement fee: {err}"),
+            Self::FeeUnavailable(txid) => {
+                write!(f, "Fee unavailable for transaction with txid: {txid}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for CheckReplacementError {}
+
+#[derive(Debug)]
+/// Error returned from [`Wallet::process_payjoin_proposal`]
+///
+/// [`Wallet::process_payjoin_proposal`]: super::Wallet::process_payjoin_proposal
+pub enum ProcessPayjoinProposalError {
+    /// The proposal changed the version of the transaction
+    VersionChanged,
+    /// The proposal changed the lock time of the transaction
+    LockTimeChanged,
+    /// The proposal doesn't spend an input of the original transaction
+    MissingSenderInput(OutPoint),
+    /// The proposal changed the sequence of an input of the original transaction
+    SequenceChanged(OutPoint),
+    /// The proposal spends an output of the wallet that wasn't spent by the original transaction
+    NewSenderInput(OutPoint),
+    /// An input added by the receiver is not finalized
+    ReceiverInputNotFinalized(OutPoint),
+    /// An input added by the receiver doesn't have the output it spends
+    MissingUtxo(OutPoint),
+    /// An input added by the receiver spends a different script type than the sender inputs
+    ScriptTypeMismatch(OutPoint),
+    /// The proposal changed or removed an output to the receiver while output substitution is
+    /// disabled
+    PayeeOutputChanged(ScriptBuf),
+    /// The proposal lowered or removed an output to a third party
+    OutputChanged(ScriptBuf),
+    /// The sender pays more than allowed for the proposal
+    FeeContributionTooHigh {
+        /// Additional amount paid by the sender
+        contribution: Amount,
+        /// Maximum additional amount allowed
+        max: Amount,
+    },
+    /// The sender pays more than the fee of the inputs added by the receiver, at the fee rate of
+    /// the original transaction
+    FeeContributionAboveInputFee {
+        /// Additional amount paid by the sender
+        contribution: Amount,
+        /// Fee of the inputs added by the receiver
+        input_fee: Amount,
+    },
+    /// The fee rate of the proposal is too low
+    FeeRateTooLow {
+        /// Estimated fee rate of the proposal
+        fee_rate: FeeRate,
+        /// Minimum fee rate
+        min: FeeRate,
+    },
+    /// The fee of the original transaction or of the proposal can't be calculated
+    Psbt(psbt::Error),
+    /// Signing the proposal failed
+    Signer(SignerError),
+}
+
+impl fmt::Display for ProcessPayjoinProposalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VersionChanged => write!(f, "Payjoin proposal changed the transaction version"),
+            Self::LockTimeChanged => write!(f, "Payjoin proposal changed the lock time"),
+            Self::MissingSenderInput(outpoint) => {
+                write!(f, "Payjoin proposal is missing sender input: {outpoint}")
+            }
+            Self::SequenceChanged(outpoint) => {
+                write!(
+                    f,
+                    "Payjoin proposal changed the sequence of input: {outpoint}"
+                )
+            }
+            Self::NewSenderInput(outpoint) => {
+                write!(f, "Payjoin proposal added a sender input: {outpoint}")
+            }
+            Self::ReceiverInputNotFinalized(outpoint) => {
+                write!(f, "Payjoin receiver input not finalized: {outpoint}")
+            }
+            Self::MissingUtxo(outpoint) => {
+                write!(f, "Payjoin receiver input missing its UTXO: {outpoint}")
+            }
+            Self::ScriptTypeMismatch(outpoint) => {
+                write!(
+                    f,
+                    "Payjoin receiver input has a different script type: {outpoint}"
+                )
+            }
+            Self::PayeeOutputChanged(script) => {
+                write!(f, "Payjoin proposal changed the payee output: {script}")
+            }
+            Self::OutputChanged(script) => {
+                write!(f, "Payjoin proposal changed an output: {script}")
+            }
+            Self::FeeContributionTooHigh { contribution, max } => write!(
+                f,
+                "Payjoin fee contribution too high: {} sat, max {} sat",
+                contribution.to_sat(),
+                max.to_sat()
+            ),
+            Self::FeeContributionAboveInputFee {
+                contribution,
+                input_fee,
+            } => write!(
+                f,
+                "Payjoin fee contribution higher than the fee of the receiver inputs: {} sat, input fee {} sat",
+                contribution.to_sat(),
+                input_fee.to_sat()
+            ),
+            Self::FeeRateTooLow { fee_rate, min } => write!(
+                f,
+                "Payjoin proposal fee rate too low: {} sat/kwu, min {} sat/kwu",
+                fee_rate.to_sat_per_kwu(),
+                min.to_sat_per_kwu()
+            ),
+            Self::Psbt(err) => write!(f, "Failed to calculate the payjoin fee: {err}"),
+            Self::Signer(err) => write!(f, "Failed to sign the payjoin proposal: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for ProcessPayjoinProposalError {}
+
+#[derive(Debug)]
+/// Error returned from [`Wallet::contribute_payjoin`]
+///
+/// [`Wallet::contribute_payjoin`]: super::Wallet::contribute_payjoin
+pub enum ContributePayjoinError {
+    /// The original PSBT has no inputs
+    NoSenderInput,
+    /// The original PSBT is not finalized, so it can't be broadcast as a fallback
+    OriginalNotFinalized,
+    /// The original PSBT spends an output of the wallet
+    OwnedInput(OutPoint),
+    /// The original PSBT doesn't pay the wallet
+    NoReceiverOutput,
+    /// The wallet has no UTXO to contribute, or the UTXO can't pay for its own weight
+    NoUtxo,
+    /// The UTXO to contribute is not in the wallet
+    UnknownUtxo(OutPoint),
+    /// The fee of the original transaction can't be calculated
+    Psbt(psbt::Error),
+    /// Signing the proposal failed
+    Signer(SignerError),
+}
+
+impl fmt::Display for ContributePayjoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSenderInput => write!(f, "Original payjoin PSBT has no inputs"),
+            Self::OriginalNotFinalized => write!(f, "Original payjoin PSBT is not finalized"),
+            Self::OwnedInput(outpoint) => {
+                write!(
+                    f,
+                    "Original payjoin PSBT spends a wallet output: {outpoint}"
+                )
+            }
+            Self::NoReceiverOutput => write!(f, "Original payjoin PSBT doesn't pay the wallet"),
+            Self::NoUtxo => write!(f, "No UTXO to contribute to the payjoin"),
+            Self::UnknownUtxo(outpoint) => {
+                write!(f, "UTXO not found in the wallet: {outpoint}")
+            }
+            Self::Psbt(err) => write!(f, "Failed to calculate the original fee: {err}"),
+            Self::Signer(err) => write!(f, "Failed to sign the payjoin proposal: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for ContributePayjoinError {}
+
+#[derive(Debug)]
+/// Error returned from [`Wallet::build_sweep`]
+///
+/// [`Wallet::build_sweep`]: super::Wallet::build_sweep
+pub enum SweepError {
+    /// The descriptor or WIF key to sweep is invalid
+    Descriptor(DescriptorError),
+    /// No UTXO to sweep was given
+    NoUtxos,
+    /// The script of the UTXO is not derived from the descriptor or key to sweep, at one of the
+    /// first [`SWEEP_DERIVATION_RANGE`] derivation indexes of a ranged descriptor
+    ///
+    /// [`SWEEP_DERIVATION_RANGE`]: super::SWEEP_DERIVATION_RANGE
+    UnknownScript(OutPoint),
+    /// The UTXO can't be added to the transaction
+    ForeignUtxo(AddForeignUtxoError),
+    /// Creating the transaction failed
+    CreateTx(CreateTxError),
+    /// Signing the foreign inputs failed
+    Signer(SignerError),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Descriptor(err) => write!(f, "Invalid descriptor or key to sweep: {err}"),
+            Self::NoUtxos => write!(f, "No UTXO to sweep"),
+            Self::UnknownScript(outpoint) => {
+                write!(
+                    f,
+                    "UTXO not derived from the descriptor or key to sweep, within the first {} \
+                     derivation indexes: {outpoint}",
+                    super::SWEEP_DERIVATION_RANGE
+                )
+            }
+            Self::ForeignUtxo(err) => write!(f, "Invalid UTXO to sweep: {err}"),
+            Self::CreateTx(err) => write!(f, "Failed to create the sweep transaction: {err}"),
+            Self::Signer(err) => write!(f, "Failed to sign the sweep transaction: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for SweepError {}
+
+#[derive(Debug)]
+/// Error returned from [`Wallet::apply_block_with_prevouts`]
+///
+/// [`Wallet::apply_block_with_prevouts`]: super::Wallet::apply_block_with_prevouts
+pub enum ApplyBlockError {
+    /// The block can't be connected to the wallet's chain
+    CannotConnect(CannotConnectError),
+    /// An output spent by a transaction of the block is unknown, so the transaction can't be
+    /// scanned for silent payments
+    MissingPrevout(OutPoint),
+}
+
+impl fmt::Display for ApplyBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CannotConnect(err) => write!(f, "{err}"),
+            Self::MissingPrevout(outpoint) => {
+                write!(f, "Output spent in the block not found: {outpoint}")
+            }
+        }
+    }
+}
+
+impl From<CannotConnectError> for ApplyBlockError {
+    fn from(err: CannotConnectError) -> Self {
+        Self::CannotConnect(err)
+    }
+}
+
+impl core::error::Error for ApplyBlockError {}
@@ -38,11 +38,11 @@ use bitcoin::{
     absolute,
     consensus::encode::serialize,
     constants::genesis_block,
-    psbt,
-    secp256k1::Secp256k1,
+    psbt, relative,
+    secp256k1::{Secp256k1, SecretKey},
     sighash::{EcdsaSighashType, TapSighashType},
-    transaction, Address, Amount, Block, FeeRate, Network, NetworkKind, OutPoint, Psbt, ScriptBuf,
-    Sequence, SignedAmount, Transaction, TxOut, Txid, Weight, Witness,
+    transaction, Address, Amount, Block, FeeRate, Network, NetworkKind, OutPoint, Psbt, Script,
+    ScriptBuf, Sequence, SignedAmount, Transaction, TxIn, TxOut, Txid, Weight, Witness,
 };
 use miniscript::{
     descriptor::KeyMap,
@@ -59,25 +59,45 @@ pub mod locked_outpoints;
 #[cfg(feature = "rusqlite")]
 pub mod migration;
 mod params;
+pub mod payjoin;
+pub mod payout;
 mod persisted;
+pub mod replacement;
 pub mod signer;
+pub mod silent_payments;
+mod sweep;
 pub mod tx_builder;
 pub(crate) mod utils;
 
 use crate::collections::{BTreeMap, HashMap, HashSet};
+use crate::descriptor::policy::Condition;
 use crate::descriptor::{
     check_wallet_descriptor, error::Error as DescriptorError, policy::BuildSatisfaction,
     DerivedDescriptor, DescriptorMeta, ExtendedDescriptor, ExtractPolicy, IntoWalletDescriptor,
     Policy, XKeyUtils,
 };
-use crate::psbt::PsbtUtils;
+use crate::psbt::{v2::required_lock_time_fields, PsbtUtils};
 use crate::types::*;
 use crate::wallet::{
-    coin_selection::{DefaultCoinSelectionAlgorithm, Excess, InsufficientFunds},
-    error::{BuildFeeBumpError, CreateTxError, MiniscriptPsbtError},
+    coin_selection::{
+        CoinSelectionExplanation, DefaultCoinSelectionAlgorithm, Excess, ExclusionReason,
+        ExplainedUtxo, InsufficientFunds, UtxoStatus,
+    },
+    error::{
+        ApplyBlockError, BuildCpfpError, BuildFeeBumpError, CheckReplacementError, CreateTxError,
+        MiniscriptPsbtError,
+    },
+    replacement::{ReplacementReport, ReplacementViolation},
     signer::{SignOptions, SignerError, SignerOrdering, SignersContainer, TransactionSigner},
-    tx_builder::{FeePolicy, TxBuilder, TxParams},
-    utils::{check_nsequence_rbf, After, Older, SecpCtx},
+    silent_payments::{
+        label_tweak, psbt_output_field, scan_transaction, SilentPaymentAddress, SilentPaymentOutput,
+    },
+    tx_builder::{
+        AddUtxoError, AncestorPackage, ChangeContext, ChangeStrategy, FeePolicy, RequiredUtxo,
+        TxBuilder, TxParams, TxPlan, UtxoFilters, TRUC_CHILD_MAX_VSIZE, TRUC_MAX_VSIZE,
+        TRUC_VERSION,
+    },
+    utils::{check_nsequence_rbf, is_p2a, After, Older, SecpCtx},
 };
 
 // re-exports
@@ -87,6 +107,7 @@ pub use error::{LoadError, LoadMismatch};
 pub use event::*;
 pub use params::*;
 pub use persisted::*;
+pub use sweep::SWEEP_DERIVATION_RANGE;
 pub use utils::IsDust;
 pub use utils::TxDetails;
 
@@ -117,6 +138,8 @@ pub struct Wallet {
     network: Network,
     secp: SecpCtx,
     locked_outpoints: HashSet<OutPoint>,
+    silent_payments: silent_payments::ChangeSet,
+    silent_payment_keys: Option<(SecretKey, SecretKey)>,
 }
 
 /// An update to [`Wallet`].
@@ -347,11 +370,20 @@ impl Wallet {
 
         let locked_outpoints = HashSet::new();
 
+        let silent_payment_keys = params.silent_payment_keys;
+        let silent_payments = silent_payments::ChangeSet {
+            keys: silent_payment_keys.map(|(scan_key, spend_key)| {
+                (scan_key.public_key(&secp), spend_key.public_key(&secp))
+            }),
+            ..Default::default()
+        };
+
         let mut stage = ChangeSet {
             descriptor: Some(descriptor.clone()),
             change_descriptor: change_descriptor.clone(),
             local_chain: chain_changeset,
             network: Some(network),
+            silent_payments: silent_payments.clone(),
             ..Default::default()
         };
 
@@ -374,6 +406,8 @@ impl Wallet {
             stage,
             secp,
             locked_outpoints,
+            silent_payments,
+            silent_payment_keys,
         })
     }
 
@@ -561,6 +595,26 @@ impl Wallet {
 
         let mut stage = ChangeSet::default();
 
+        // Check the silent payment keys, or start receiving silent payments if there are none.
+        let mut silent_payments = changeset.silent_payments;
+        let silent_payment_keys = params.silent_payment_keys;
+        if let Some((scan_key, spend_key)) = silent_payment_keys {
+            let expected = (scan_key.public_key(&secp), spend_key.public_key(&secp));
+            match silent_payments.keys {
+                Some(loaded) if loaded != expected => {
+                    return Err(LoadError::Mismatch(LoadMismatch::SilentPaymentKeys {
+                        loaded: Box::new(loaded),
+                        expected: Box::new(expected),
+                    }));
+                }
+                Some(_) => {}
+                None => {
+                    silent_payments.keys = Some(expected);
+                    stage.silent_payments.keys = Some(expected);
+                }
+            }
+        }
+
         let tx_graph = make_indexed_graph(
             &mut stage,
             changeset.tx_graph,
@@ -581,6 +635,8 @@ impl Wallet {
             network,
             secp,
             locked_outpoints,
+            silent_payments,
+            silent_payment_keys,
         }))
     }
 
@@ -776,6 +832,97 @@ impl Wallet {
         self.tx_graph.index.index_of_spk(spk).cloned()
     }
 
+    /// Get the [BIP352] silent payment address of the wallet, with the given `label` if any.
+    ///
+    /// Returns `None` if the wallet doesn't receive silent payments, see
+    /// [`CreateParams::silent_payments`]. Labeled addresses also require the private scan key, and
+    /// their label is added to the ones the wallet scans for.
+    ///
+    /// **WARNING**: You must persist the changes resulting from one or more calls to this method
+    /// if you need the labels to be reloaded after closing the wallet.
+    /// See [`Wallet::reveal_next_address`].
+    ///
+    /// [BIP352]: https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki
+    pub fn silent_payment_address(&mut self, label: Option<u32>) -> Option<SilentPaymentAddress> {
+        let (scan, mut spend) = self.silent_payments.keys?;
+        if let Some(m) = label {
+            let (scan_key, _) = self.silent_payment_keys?;
+            let tweak = label_tweak(&scan_key, m)?;
+            spend = spend.add_exp_tweak(&self.secp, &tweak.into()).ok()?;
+            if self.silent_payments.labels.insert(m) {
+                let changeset = silent_payments::ChangeSet {
+                    labels: [m].into(),
+                    ..Default::default()
+                };
+                self.stage.merge(changeset.into());
+            }
+        }
+        Some(SilentPaymentAddress::new(
+            scan,
+            spend,
+            NetworkKind::from(self.network),
+        ))
+    }
+
+    /// Get the silent payment output received by the wallet at `outpoint`.
+    ///
+    /// Silent payment outputs don't belong to a keychain, so they are not returned by
+    /// [`Wallet::list_unspent`] and similar methods. See [`Wallet::list_silent_payment_unspent`].
+    pub fn silent_payment_output(&self, outpoint: OutPoint) -> Option<SilentPaymentOutput> {
+        self.silent_payments.outputs.get(&outpoint).copied()
+    }
+
+    /// Return the list of unspent silent payment outputs of this wallet
+    pub fn list_silent_payment_unspent(&self) -> impl Iterator<Item = SilentPaymentUtxo> + '_ {
+        self.tx_graph
+            .graph()
+            .filter_chain_unspents(
+                &self.chain,
+                self.chain.tip().block_id(),
+                CanonicalizationParams::default(),
+                self.silent_payment_outpoints(),
+            )
+            .map(|(output, full_txo)| new_silent_payment_utxo(output, full_txo))
+    }
+
+    /// List all silent payment outputs of this wallet (includes both spent and unspent,
+    /// confirmed and unconfirmed).
+    pub fn list_silent_payment_output(&self) -> impl Iterator<Item = SilentPaymentUtxo> + '_ {
+        self.tx_graph
+            .graph()
+            .filter_chain_txouts(
+                &self.chain,
+                self.chain.tip().block_id(),
+                CanonicalizationParams::default(),
+                self.silent_payment_outpoints(),
+            )
+            .map(|(output, full_txo)| new_silent_payment_utxo(output, full_txo))
+    }
+
+    /// The outpoints of the silent payment outputs of the wallet, indexed by their tweak.
+    fn silent_payment_outpoints(
+        &self,
+    ) -> impl Iterator<Item = (SilentPaymentOutput, OutPoint)> + '_ {
+        self.silent_payments
+            .outputs
+            .iter()
+            .map(|(&outpoint, &output)| (output, outpoint))
+    }
+
+    /// The unspent outputs of the descriptors, followed by the unspent silent payment outputs.
+    pub(crate) fn unspent_utxos(&self) -> impl Iterator<Item = Utxo> + '_ {
+        self.list_unspent()
+            .map(Utxo::Local)
+            .chain(self.list_silent_payment_unspent().map(Utxo::SilentPayment))
+    }
+
+    /// The maximum weight of the satisfaction of an output of the wallet's descriptors.
+    fn satisfaction_weight(&self, utxo: &LocalOutput) -> Weight {
+        self.public_descriptor(utxo.keychain)
+            .max_weight_to_satisfy()
+            .expect("descriptor should be satisfiable")
+    }
+
     /// Return the list of unspent outputs of this wallet
     pub fn list_unspent(&self) -> impl Iterator<Item = LocalOutput> + '_ {
         self.tx_graph
@@ -873,7 +1020,7 @@ impl Wallet {
     /// Returns the utxo owned by this wallet corresponding to `outpoint` if it exists in the
     /// wallet's database.
     pub fn get_utxo(&self, op: OutPoint) -> Option<LocalOutput> {
-        let ((keychain, index), _) = self.tx_graph.index.txout(op)?;
+        let (keychain, index) = self.tx_graph.index.txout(op)?.0;
         self.tx_graph
             .graph()
             .filter_chain_unspents(
@@ -1119,8 +1266,18 @@ impl Wallet {
             &self.chain,
             self.chain.tip().block_id(),
             CanonicalizationParams::default(),
-            self.tx_graph.index.outpoints().iter().cloned(),
-            |&(k, _), _| k == KeychainKind::Internal,
+            self.tx_graph
+                .index
+                .outpoints()
+                .iter()
+                .map(|&((keychain, _), outpoint)| (Some(keychain), outpoint))
+                .chain(
+                    self.silent_payments
+                        .outputs
+                        .keys()
+                        .map(|&outpoint| (None, outpoint)),
+                ),
+            |&keychain, _| keychain == Some(KeychainKind::Internal),
         )
     }
 
@@ -1225,12 +1382,210 @@ impl Wallet {
         }
     }
 
+    /// Start building a transaction from previously saved parameters.
+    ///
+    /// This returns a [`TxBuilder`] like [`build_tx`], with its parameters set to `params`. The
+    /// parameters are usually obtained from another builder with [`TxBuilder::params`], and can
+    /// be serialized to create the transaction at a later time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AddUtxoError::UnknownUtxo`] if one of the wallet UTXOs that must be spent (see
+    /// [`TxParams::utxos`]) isn't an unspent output of the wallet anymore.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt").unwrap().assume_checked();
+    /// let mut builder = wallet.build_tx();
+    /// builder.add_recipient(to_address.script_pubkey(), Amount::from_sat(50_000));
+    /// let saved = serde_json::to_string(builder.params())?;
+    ///
+    /// // later...
+    /// let params: TxParams = serde_json::from_str(&saved)?;
+    /// let psbt = wallet.build_tx_with_params(params)?.finish()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`build_tx`]: Self::build_tx
+    pub fn build_tx_with_params(
+        &mut self,
+        params: TxParams,
+    ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, AddUtxoError> {
+        let outpoints: Vec<OutPoint> = params
+            .utxos
+            .iter()
+            .filter_map(|utxo| match utxo {
+                RequiredUtxo::Local(outpoint) => Some(*outpoint),
+                RequiredUtxo::Foreign { .. } => None,
+            })
+            .collect();
+        if !outpoints.is_empty() {
+            let unspent: HashSet<OutPoint> =
+                self.unspent_utxos().map(|utxo| utxo.outpoint()).collect();
+            if let Some(&outpoint) = outpoints.iter().find(|op| !unspent.contains(op)) {
+                return Err(AddUtxoError::UnknownUtxo(outpoint));
+            }
+        }
+        Ok(TxBuilder {
+            wallet: self,
+            params,
+            coin_selection: DefaultCoinSelectionAlgorithm::default(),
+        })
+    }
+
+    /// Compute the largest amount that can be sent to `script_pubkey` in a single output, paying
+    /// `fee_rate`.
+    ///
+    /// This spends every UTXO accepted by `filters` whose value is higher than the fee needed to
+    /// spend it, without building a transaction. Only the type of `script_pubkey` matters, so the
+    /// script of any address of the right type can be used before the actual destination is
+    /// known. Locked outpoints and immature coinbase outputs are never spent.
+    ///
+    /// The UTXOs must also be spendable now under the spending policy of their descriptor, with
+    /// the policy paths of `filters`: a descriptor whose policy requires a path which isn't given
+    /// can't be spent, and the relative timelock (`older`) of the UTXOs and the absolute timelock
+    /// (`after`) of the chosen path must be reached. Time-based relative timelocks only require the
+    /// UTXO to be confirmed and time-based absolute timelocks aren't checked, as the wallet doesn't
+    /// know the time of the blocks.
+    ///
+    /// If the amount left after paying the fee would be below the dust limit of `script_pubkey`
+    /// the returned amount is zero and no UTXO is spent.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt").unwrap().assume_checked();
+    /// let filters = UtxoFilters {
+    ///     min_confirmations: 1,
+    ///     ..Default::default()
+    /// };
+    /// let max = wallet.max_spendable(
+    ///     &to_address.script_pubkey(),
+    ///     FeeRate::from_sat_per_vb(5).expect("valid feerate"),
+    ///     &filters,
+    /// );
+    /// println!("can send up to {}", max.amount);
+    /// ```
+    pub fn max_spendable(
+        &self,
+        script_pubkey: &Script,
+        fee_rate: FeeRate,
+        filters: &UtxoFilters,
+    ) -> MaxSpendable {
+        let tip_height = self.chain.tip().height();
+        let params = filters.tx_params();
+        let conditions = self.spending_conditions(&params);
+
+        let input_weight = |wutxo: &WeightedUtxo| {
+            bitcoin::TxIn::default().segwit_weight() + wutxo.satisfaction_weight
+        };
+        let utxos: Vec<WeightedUtxo> = self
+            .filter_utxos(&params, tip_height)
+            .into_iter()
+            .filter(|wutxo| match &wutxo.utxo {
+                Utxo::Local(utxo) => conditions
+                    .get(&utxo.keychain)
+                    .and_then(Option::as_ref)
+                    .is_some_and(|condition| is_timelock_reached(utxo, condition, tip_height)),
+                Utxo::SilentPayment(_) => true,
+                Utxo::Foreign { .. } => false,
+            })
+            .filter(|wutxo| wutxo.utxo.txout().value > fee_rate * input_weight(wutxo))
+            .collect();
+
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: script_pubkey.into(),
+            }],
+        };
+        let fee = fee_rate * (tx.weight() + utxos.iter().map(input_weight).sum::<Weight>());
+        let total: Amount = utxos.iter().map(|wutxo| wutxo.utxo.txout().value).sum();
+
+        match total.checked_sub(fee) {
+            Some(amount) if !amount.is_dust(script_pubkey) => MaxSpendable {
+                amount,
+                fee,
+                utxos: utxos.into_iter().map(|wutxo| wutxo.utxo).collect(),
+            },
+            _ => MaxSpendable {
+                amount: Amount::ZERO,
+                fee: Amount::ZERO,
+                utxos: vec![],
+            },
+        }
+    }
+
     pub(crate) fn create_tx<Cs: coin_selection::CoinSelectionAlgorithm>(
         &mut self,
         coin_selection: Cs,
         params: TxParams,
         rng: &mut impl RngCore,
+        explanation: Option<&mut CoinSelectionExplanation>,
     ) -> Result<Psbt, CreateTxError> {
+        let (plan, change_indexes) = self.plan_tx(&coin_selection, &params, rng, explanation)?;
+
+        // Record the silent payment outputs, a fee bump has to derive them again.
+        if !plan.silent_payments.is_empty() {
+            let changeset = silent_payments::ChangeSet {
+                sent: plan
+                    .silent_payments
+                    .iter()
+                    .map(|(vout, address)| {
+                        (
+                            plan.unsigned_tx.output[*vout].script_pubkey.clone(),
+                            *address,
+                        )
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            self.silent_payments.merge(changeset.clone());
+            self.stage.merge(changeset.into());
+        }
+
+        let mut psbt = self.complete_transaction(plan.unsigned_tx, plan.utxos, params)?;
+        for (vout, address) in &plan.silent_payments {
+            let (key, value) = psbt_output_field(address);
+            psbt.outputs[*vout].unknown.insert(key, value);
+        }
+
+        // Recording changes to the change keychain.
+        for (keychain, index) in change_indexes {
+            if let Some((_, index_changeset)) =
+                self.tx_graph.index.reveal_to_target(keychain, index)
+            {
+                self.stage.merge(index_changeset.into());
+                self.mark_used(keychain, index);
+            }
+        }
+
+        Ok(psbt)
+    }
+
+    /// Select the coins and build the unsigned transaction, without changing the wallet state.
+    ///
+    /// Also returns the keychains and indexes of the change addresses, which may not be revealed
+    /// yet. The coin selection is recorded in `explanation`, if any, even when it fails.
+    pub(crate) fn plan_tx<Cs: coin_selection::CoinSelectionAlgorithm>(
+        &self,
+        coin_selection: &Cs,
+        params: &TxParams,
+        rng: &mut impl RngCore,
+        mut explanation: Option<&mut CoinSelectionExplanation>,
+    ) -> Result<(TxPlan, Vec<(KeychainKind, u32)>), CreateTxError> {
         let keychains: BTreeMap<_, _> = self.tx_graph.index.keychains().collect();
         let external_descriptor = keychains.get(&KeychainKind::External).expect("must exist");
         let internal_descriptor = keychains.get(&KeychainKind::Internal);
@@ -1292,6 +1647,7 @@ impl Wallet {
             external_requirements.merge(&internal_requirements.unwrap_or_default())?;
 
         let version = match params.version {
+            _ if params.truc => TRUC_VERSION,
             Some(transaction::Version(0)) => return Err(CreateTxError::Version0),
             Some(transaction::Version::ONE) if requirements.csv.is_some() => {
                 return Err(CreateTxError::Version1Csv)
@@ -1367,7 +1723,8 @@ impl Wallet {
             (Some(sequence), _) => sequence,
         };
 
-        let (fee_rate, mut fee_amount) = match params.fee_policy.unwrap_or_default() {
+        let fee_policy = params.fee_policy.unwrap_or_default();
+        let (fee_rate, fee_amount) = match fee_policy {
             //FIXME: see https://github.com/bitcoindevkit/bdk/issues/256
             FeePolicy::FeeAmount(fee) => {
                 if let Some(previous_fee) = params.bumping_fee {
@@ -1406,11 +1763,28 @@ impl Wallet {
             return Err(CreateTxError::NoUtxosSelected);
         }
 
+        if let Some(&index) = params
+            .subtract_fee_from
+            .iter()
+            .find(|&&index| index >= params.recipients.len())
+        {
+            return Err(CreateTxError::SubtractFeeIndexOutOfBounds(
+                IndexOutOfBoundsError::new(index, params.recipients.len()),
+            ));
+        }
+
         let mut outgoing = Amount::ZERO;
         let recipients = params.recipients.iter().map(|(r, v)| (r, *v));
+        // Index of the output of each recipient, to find them after the change is added.
+        let mut recipient_vouts = Vec::with_capacity(params.recipients.len());
 
         for (index, (script_pubkey, value)) in recipients.enumerate() {
-            if !params.allow_dust && value.is_dust(script_pubkey) && !script_pubkey.is_op_return() {
+            // The dust limit of the outputs paying the fee is checked after subtracting it.
+            if !params.allow_dust
+                && value.is_dust(script_pubkey)
+                && !script_pubkey.is_op_return()
+                && !params.subtract_fee_from.contains(&index)
+            {
                 return Err(CreateTxError::OutputBelowDustLimit(index));
             }
 
@@ -1419,17 +1793,27 @@ impl Wallet {
                 value,
             };
 
+            recipient_vouts.push(tx.output.len());
             tx.output.push(new_out);
 
             outgoing += value;
         }
 
-        fee_amount += fee_rate * tx.weight();
+        // When the recipients pay the fee coins are selected for the amounts they receive, and
+        // coin selection doesn't pay any fee. The fee is calculated at `fee_rate` once the
+        // transaction is complete, including the bump fee of the unconfirmed ancestors.
+        let subtract_fee = !params.subtract_fee_from.is_empty();
+        let (selection_fee_rate, mut selection_fee_amount) = match subtract_fee {
+            true => (FeeRate::ZERO, Amount::ZERO),
+            false => (fee_rate, fee_amount + fee_rate * tx.weight()),
+        };
 
-        let (required_utxos, optional_utxos) = {
+        let (mut required_utxos, mut optional_utxos) = {
             // NOTE: manual selection overrides unspendable
-            let mut required: Vec<WeightedUtxo> = params.utxos.clone();
-            let optional = self.filter_utxos(&params, current_height.to_consensus_u32());
+            let mut required = self
+                .required_utxos(&params.utxos)
+                .map_err(|_| CreateTxError::UnknownUtxo)?;
+            let optional = self.filter_utxos(params, current_height.to_consensus_u32());
 
             // If `drain_wallet` is true, all UTxOs are required.
             if params.drain_wallet {
@@ -1440,44 +1824,154 @@ impl Wallet {
             }
         };
 
+        // BIP125 rules 3 and 4: a replacement must pay at least the fees of the transactions it
+        // replaces, plus the incremental relay fee for its own size. The fee rate is higher than
+        // the incremental relay fee, so the weight added by coin selection pays for itself and
+        // it's enough to reach the required fee for the weight known before selecting coins.
+        let replacement_fee = |weight: Weight| {
+            params
+                .bumping_fee
+                .map(|previous_fee| previous_fee.absolute + FeeRate::BROADCAST_MIN * weight)
+        };
+        if let (FeePolicy::FeeRate(_), false) = (fee_policy, subtract_fee) {
+            let known_weight = tx.weight()
+                + required_utxos
+                    .iter()
+                    .map(|wutxo| {
+                        bitcoin::TxIn::default().segwit_weight() + wutxo.satisfaction_weight
+                    })
+                    .sum::<Weight>();
+            if let Some(required) = replacement_fee(known_weight) {
+                selection_fee_amount += required
+                    .checked_sub(fee_rate * known_weight)
+                    .unwrap_or_default();
+            }
+        }
+
+        // Spending the output of an unconfirmed transaction means paying for its unconfirmed
+        // ancestors too, if they don't reach the requested fee rate by themselves. When the fee
+        // is subtracted from the recipients coin selection doesn't pay any fee, the recipients
+        // pay for the ancestors along with the rest of the fee.
+        let unconfirmed_txids = self.unconfirmed_txids();
+        if !subtract_fee {
+            for weighted_utxo in required_utxos.iter_mut().chain(&mut optional_utxos) {
+                let txid = weighted_utxo.utxo.outpoint().txid;
+                if unconfirmed_txids.contains(&txid) {
+                    weighted_utxo.bump_fee = self
+                        .bump_fee([txid], fee_rate, &unconfirmed_txids)
+                        .unwrap_or_default();
+                }
+            }
+        }
+
+        if let Some(explanation) = explanation.as_deref_mut() {
+            let candidates = required_utxos
+                .iter()
+                .chain(&optional_utxos)
+                .map(|wutxo| wutxo.utxo.outpoint())
+                .collect::<HashSet<_>>();
+            let excluded = self
+                .unspent_with_exclusion(params, current_height.to_consensus_u32())
+                .into_iter()
+                .filter(|(wutxo, _)| !candidates.contains(&wutxo.utxo.outpoint()))
+                .filter_map(|(wutxo, exclusion)| Some((wutxo, UtxoStatus::Excluded(exclusion?))));
+            let utxos = required_utxos
+                .iter()
+                .chain(&optional_utxos)
+                .map(|wutxo| (wutxo.clone(), UtxoStatus::NotSelected))
+                .chain(excluded);
+            *explanation = CoinSelectionExplanation {
+                utxos: utxos
+                    .map(|(wutxo, status)| ExplainedUtxo::new(wutxo, selection_fee_rate, status))
+                    .collect(),
+                fee_rate: selection_fee_rate,
+                target_amount: outgoing + selection_fee_amount,
+                algorithms: Vec::new(),
+            };
+            // Optional UTXOs costing at least their value aren't worth selecting.
+            let optional = required_utxos.len()..required_utxos.len() + optional_utxos.len();
+            for utxo in &mut explanation.utxos[optional] {
+                if !utxo.effective_value.is_positive() {
+                    utxo.status = UtxoStatus::Excluded(ExclusionReason::NegativeEffectiveValue);
+                }
+            }
+        }
+
         // Get drain script.
-        let mut drain_index = Option::<(KeychainKind, u32)>::None;
+        let change_keychain = self.map_keychain(KeychainKind::Internal);
         let drain_script = match params.drain_to {
             Some(ref drain_recipient) => drain_recipient.clone(),
             None => {
-                let change_keychain = self.map_keychain(KeychainKind::Internal);
-                let (index, spk) = self
-                    .tx_graph
-                    .index
-                    .unused_keychain_spks(change_keychain)
-                    .next()
-                    .unwrap_or_else(|| {
-                        let (next_index, _) = self
-                            .tx_graph
-                            .index
-                            .next_index(change_keychain)
-                            .expect("keychain must exist");
-                        let spk = self
-                            .peek_address(change_keychain, next_index)
-                            .script_pubkey();
-                        (next_index, spk)
-                    });
-                drain_index = Some((change_keychain, index));
+                let (_, spk) = self.change_spks(change_keychain).next().expect("infinite");
                 spk
             }
         };
 
-        let coin_selection = coin_selection
+        // Used to estimate the final size of the transaction.
+        let satisfaction_weights: HashMap<OutPoint, Weight> = required_utxos
+            .iter()
+            .chain(&optional_utxos)
+            .map(|wutxo| (wutxo.utxo.outpoint(), wutxo.satisfaction_weight))
+            .collect();
+        let bump_fees: HashMap<OutPoint, Amount> = required_utxos
+            .iter()
+            .chain(&optional_utxos)
+            .map(|wutxo| (wutxo.utxo.outpoint(), wutxo.bump_fee))
+            .collect();
+
+        let mut coin_selection = coin_selection
             .coin_select(
                 required_utxos,
                 optional_utxos,
-                fee_rate,
-                outgoing + fee_amount,
+                selection_fee_rate,
+                outgoing + selection_fee_amount,
                 &drain_script,
                 rng,
             )
             .map_err(CreateTxError::CoinSelection)?;
+        coin_selection.fill_input_weight(|outpoint| {
+            satisfaction_weights
+                .get(&outpoint)
+                .copied()
+                .unwrap_or(Weight::ZERO)
+        });
+
+        if let Some(explanation) = explanation {
+            let selected = coin_selection
+                .selected
+                .iter()
+                .map(|utxo| utxo.outpoint())
+                .collect::<HashSet<_>>();
+            for utxo in &mut explanation.utxos {
+                if selected.contains(&utxo.outpoint) {
+                    utxo.status = UtxoStatus::Selected;
+                }
+            }
+            explanation.algorithms = coin_selection.algorithms.clone();
+        }
 
+        // Selected UTXOs sharing unconfirmed ancestors each paid for them, the ancestors only need
+        // to be paid for once.
+        let selected_bump_fee = coin_selection
+            .selected
+            .iter()
+            .filter_map(|utxo| bump_fees.get(&utxo.outpoint()))
+            .copied()
+            .sum::<Amount>();
+        let shared_bump_fee = {
+            let txids = coin_selection
+                .selected
+                .iter()
+                .map(|utxo| utxo.outpoint().txid)
+                .filter(|txid| unconfirmed_txids.contains(txid));
+            self.bump_fee(txids, fee_rate, &unconfirmed_txids)
+                .unwrap_or(selected_bump_fee)
+        };
+        if let Excess::Change { amount, .. } = &mut coin_selection.excess {
+            *amount += selected_bump_fee
+                .checked_sub(shared_bump_fee)
+                .unwrap_or_default();
+        }
         let excess = &coin_selection.excess;
         tx.input = coin_selection
             .selected
@@ -1492,61 +1986,195 @@ impl Wallet {
 
         if tx.output.is_empty() {
             // Uh oh, our transaction has no outputs.
-            // We allow this when we have a `drain_to` address and either:
+            // We allow this when either:
             // - `drain_wallet` is enabled
             // - there are UTXOs we must spend (this happens, for example, when
             // sweeping specific UTXOs to a given address)
-            // Otherwise, we don't know who we should send the funds to, and how much
-            // we should send!
-            if params.drain_to.is_some() && (params.drain_wallet || !params.utxos.is_empty()) {
-                if let Excess::NoChange {
-                    dust_threshold,
-                    remaining_amount,
-                    change_fee,
-                } = excess
-                {
-                    return Err(CreateTxError::CoinSelection(InsufficientFunds {
-                        needed: *dust_threshold,
-                        available: remaining_amount
-                            .checked_sub(*change_fee)
-                            .unwrap_or_default(),
-                    }));
-                }
-            } else {
+            // The funds then go to the `drain_to` address, or to a change address if there is
+            // none. Otherwise, we don't know how much we should send!
+            if !params.drain_wallet && params.utxos.is_empty() {
                 return Err(CreateTxError::NoRecipients);
             }
         }
 
-        // If there's change, create and add a change output.
-        if let Excess::Change { amount, .. } = excess {
-            // Create drain output.
-            let drain_output = TxOut {
-                value: *amount,
-                script_pubkey: drain_script,
-            };
+        // Make sure the drain output is created when it's the only output, or when it's the max
+        // recipient.
+        if tx.output.is_empty() || params.max_recipient {
+            if let Excess::NoChange {
+                dust_threshold,
+                remaining_amount,
+                change_fee,
+            } = excess
+            {
+                return Err(CreateTxError::CoinSelection(InsufficientFunds {
+                    needed: *dust_threshold,
+                    available: remaining_amount
+                        .checked_sub(*change_fee)
+                        .unwrap_or_default(),
+                }));
+            }
+        }
+
+        // If there's change, create and add the change outputs.
+        let mut change_outputs = vec![];
+        let mut change_indexes = vec![];
+        if let Excess::Change { amount, fee } = excess {
+            match params.drain_to {
+                // The output of the max recipient is a payment, it isn't tracked as change.
+                Some(_) if params.max_recipient => tx.output.push(TxOut {
+                    value: *amount,
+                    script_pubkey: drain_script.clone(),
+                }),
+                Some(_) => change_outputs.push(TxOut {
+                    value: *amount,
+                    script_pubkey: drain_script.clone(),
+                }),
+                None => {
+                    let values = self.split_change(
+                        params,
+                        &tx,
+                        &coin_selection.selected,
+                        *amount,
+                        *fee,
+                        &drain_script,
+                    )?;
+                    for ((index, script_pubkey), value) in
+                        self.change_spks(change_keychain).zip(values)
+                    {
+                        change_indexes.push((change_keychain, index));
+                        change_outputs.push(TxOut {
+                            value,
+                            script_pubkey,
+                        });
+                    }
+                }
+            }
 
             // TODO: We should pay attention when adding a new output: this might increase
             // the length of the "number of vouts" parameter by 2 bytes, potentially making
             // our feerate too low.
-            tx.output.push(drain_output);
+            tx.output.extend(change_outputs.iter().cloned());
+        }
+
+        // Estimate the weight of the inputs like coin selection does.
+        let estimate_weight = |tx: &Transaction| {
+            Transaction {
+                input: vec![],
+                ..tx.clone()
+            }
+            .weight()
+                + coin_selection.input_weight
+        };
+
+        if subtract_fee {
+            let weight = estimate_weight(&tx);
+            let fee = fee_amount + fee_rate * weight + shared_bump_fee;
+            let fee = match (fee_policy, replacement_fee(weight)) {
+                (FeePolicy::FeeRate(_), Some(required)) => fee.max(required),
+                _ => fee,
+            };
+            let payers = params.subtract_fee_from.len() as u64;
+            let share = fee / payers;
+            let remainder = fee - share * payers;
+            for (i, &index) in params.subtract_fee_from.iter().enumerate() {
+                let output = &mut tx.output[recipient_vouts[index]];
+                let fee = if i == 0 { share + remainder } else { share };
+                match output.value.checked_sub(fee) {
+                    Some(value)
+                        if params.allow_dust
+                            || !value.is_dust(&output.script_pubkey)
+                            || output.script_pubkey.is_op_return() =>
+                    {
+                        output.value = value
+                    }
+                    _ => return Err(CreateTxError::SubtractFeeBelowDust { index, fee }),
+                }
+            }
+        }
+
+        // Coin selection aimed for the BIP125 required fee, make sure it was reached.
+        if let (Some(required), FeePolicy::FeeRate(_)) =
+            (replacement_fee(estimate_weight(&tx)), fee_policy)
+        {
+            let outgoing: Amount = tx.output.iter().map(|txout| txout.value).sum();
+            let fee = coin_selection.selected_amount() - outgoing;
+            if fee < required {
+                return Err(CreateTxError::FeeTooLow { required });
+            }
+        }
+
+        if params.truc {
+            let outgoing: Amount = tx.output.iter().map(|txout| txout.value).sum();
+            let fee = coin_selection.selected_amount() - outgoing;
+            self.check_truc(&tx, fee, estimate_weight(&tx))?;
         }
 
+        // The keys of the silent payment outputs depend on the inputs, so they can only be derived
+        // once the inputs are selected.
+        let silent_payment_outputs = if params.silent_payments.is_empty() {
+            vec![]
+        } else {
+            self.derive_silent_payment_outputs(
+                &mut tx,
+                &coin_selection.selected,
+                &params.silent_payments,
+            )?
+        };
+
         // Sort inputs/outputs according to the chosen algorithm.
         params.ordering.sort_tx_with_aux_rand(&mut tx, rng);
 
-        let psbt = self.complete_transaction(tx, coin_selection.selected, params)?;
-
-        // Recording changes to the change keychain.
-        if let (Excess::Change { .. }, Some((keychain, index))) = (excess, drain_index) {
-            if let Some((_, index_changeset)) =
-                self.tx_graph.index.reveal_to_target(keychain, index)
+        // Find the change outputs after sorting, the last one holding what's left of the change.
+        let mut change_vouts = Vec::<usize>::with_capacity(change_outputs.len());
+        for change_output in change_outputs.iter().rev() {
+            if let Some(vout) = (0..tx.output.len())
+                .rev()
+                .find(|vout| tx.output[*vout] == *change_output && !change_vouts.contains(vout))
             {
-                self.stage.merge(index_changeset.into());
-                self.mark_used(keychain, index);
+                change_vouts.push(vout);
             }
         }
+        let change_vout = change_vouts.first().copied();
+        change_vouts.sort_unstable();
+        let outgoing: Amount = tx.output.iter().map(|txout| txout.value).sum();
+        let fee = coin_selection.selected_amount() - outgoing;
+        let weight = estimate_weight(&tx);
+
+        // List the UTXOs in the order of the inputs.
+        let mut selected: HashMap<OutPoint, Utxo> = coin_selection
+            .selected
+            .into_iter()
+            .map(|utxo| (utxo.outpoint(), utxo))
+            .collect();
+        let utxos = tx
+            .input
+            .iter()
+            .filter_map(|txin| selected.remove(&txin.previous_output))
+            .collect();
 
-        Ok(psbt)
+        // Each derived script is unique, which gives the position of the outputs after sorting.
+        let silent_payments = silent_payment_outputs
+            .into_iter()
+            .filter_map(|(script_pubkey, address)| {
+                let vout = tx
+                    .output
+                    .iter()
+                    .position(|txout| txout.script_pubkey == script_pubkey)?;
+                Some((vout, address))
+            })
+            .collect();
+
+        let plan = TxPlan {
+            unsigned_tx: tx,
+            utxos,
+            change_vout,
+            change_vouts,
+            weight,
+            fee,
+            silent_payments,
+        };
+
+        Ok((plan, change_indexes))
     }
 
     /// Bump the fee of a transaction previously created with this wallet.
@@ -1555,6 +2183,10 @@ impl Wallet {
     /// *replace by fee* (RBF). If the transaction can be fee bumped then it returns a [`TxBuilder`]
     /// pre-populated with the inputs and outputs of the original transaction.
     ///
+    /// The outputs paying silent payment addresses (see [`TxBuilder::add_silent_payment_recipient`])
+    /// are derived again from the inputs of the replacement, since they change if new inputs are
+    /// added. The wallet only knows the silent payment outputs of the transactions it created.
+    ///
     /// ## Example
     ///
     /// ```no_run
@@ -1589,132 +2221,625 @@ impl Wallet {
     /// // broadcast fee_bumped_tx to replace original
     /// # Ok::<(), anyhow::Error>(())
     /// ```
-    // TODO: support for merging multiple transactions while bumping the fees
     pub fn build_fee_bump(
         &mut self,
         txid: Txid,
     ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, BuildFeeBumpError> {
-        let tx_graph = self.tx_graph.graph();
-        let txout_index = &self.tx_graph.index;
-        let chain_tip = self.chain.tip().block_id();
-        let chain_positions: HashMap<Txid, ChainPosition<_>> = tx_graph
-            .list_canonical_txs(&self.chain, chain_tip, CanonicalizationParams::default())
-            .map(|canon_tx| (canon_tx.tx_node.txid, canon_tx.chain_position))
-            .collect();
+        let params = self.fee_bump_params(&[txid])?;
 
-        let mut tx = tx_graph
-            .get_tx(txid)
-            .ok_or(BuildFeeBumpError::TransactionNotFound(txid))?
-            .as_ref()
-            .clone();
+        Ok(TxBuilder {
+            wallet: self,
+            params,
+            coin_selection: DefaultCoinSelectionAlgorithm::default(),
+        })
+    }
 
-        if chain_positions
-            .get(&txid)
-            .ok_or(BuildFeeBumpError::TransactionNotFound(txid))?
-            .is_confirmed()
-        {
-            return Err(BuildFeeBumpError::TransactionConfirmed(txid));
+    /// Replace several unconfirmed transactions previously created with this wallet with a
+    /// single transaction paying a higher fee.
+    ///
+    /// This works like [`build_fee_bump`], but the returned [`TxBuilder`] is pre-populated with
+    /// the inputs and the recipients of *all* the transactions in `txids`. The change outputs of
+    /// the original transactions are dropped, as well as any input or output that only links two
+    /// of the replaced transactions together (e.g. a transaction spending the change of another
+    /// one in the batch).
+    ///
+    /// Following [BIP125], the replacement must pay an absolute fee of at least the sum of the
+    /// fees of the original transactions and of their unconfirmed descendants, plus the
+    /// incremental relay fee for its own size. Coin selection adds that fee on top of the fee
+    /// rate when needed. The fee rate must be higher than the fee rate of each of the original
+    /// transactions, [`TxBuilder::finish`] returns [`CreateTxError::FeeRateTooLow`] otherwise.
+    ///
+    /// Returns an error if `txids` is empty, if the transactions have different versions or if any
+    /// of them can't be fee bumped.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let txids: Vec<Txid> = todo!();
+    /// let mut psbt = {
+    ///     let mut builder = wallet.build_batch_fee_bump(&txids)?;
+    ///     builder.fee_rate(FeeRate::from_sat_per_vb(20).expect("valid feerate"));
+    ///     builder.finish()?
+    /// };
+    /// let _ = wallet.sign(&mut psbt, SignOptions::default())?;
+    /// // broadcast the replacement of all the transactions in `txids`
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`build_fee_bump`]: Self::build_fee_bump
+    /// [BIP125]: https://github.com/bitcoin/bips/blob/master/bip-0125.mediawiki
+    pub fn build_batch_fee_bump(
+        &mut self,
+        txids: &[Txid],
+    ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, BuildFeeBumpError> {
+        if txids.is_empty() {
+            return Err(BuildFeeBumpError::NoTransactions);
+        }
+        let params = self.fee_bump_params(txids)?;
+
+        Ok(TxBuilder {
+            wallet: self,
+            params,
+            coin_selection: DefaultCoinSelectionAlgorithm::default(),
+        })
+    }
+
+    /// Cancel a transaction previously created with this wallet by replacing it with one that
+    /// sends all of its inputs back to the wallet.
+    ///
+    /// The returned [`TxBuilder`] spends exactly the inputs of the original transaction, drops all
+    /// of its recipients and sends everything, minus the fee, to a new change address. The fee
+    /// rate defaults to the lowest one satisfying the [BIP125] replacement rules, it can be
+    /// raised with [`TxBuilder::fee_rate`]. The change address is only revealed when the
+    /// transaction is built with [`TxBuilder::finish`].
+    ///
+    /// Returns the same errors as [`build_fee_bump`] if the transaction can't be replaced.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let txid: Txid = todo!();
+    /// let mut psbt = wallet.build_cancel_tx(txid)?.finish()?;
+    /// let _ = wallet.sign(&mut psbt, SignOptions::default())?;
+    /// // broadcast the replacement to cancel the original payment
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`build_fee_bump`]: Self::build_fee_bump
+    /// [BIP125]: https://github.com/bitcoin/bips/blob/master/bip-0125.mediawiki
+    pub fn build_cancel_tx(
+        &mut self,
+        txid: Txid,
+    ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, BuildFeeBumpError> {
+        let mut params = self.fee_bump_params(&[txid])?;
+        let previous_fee = params
+            .bumping_fee
+            .expect("fee bump params must have a previous fee");
+        let utxos = self
+            .required_utxos(&params.utxos)
+            .map_err(BuildFeeBumpError::UnknownUtxo)?;
+
+        // The change address is only revealed by `finish`.
+        let change_keychain = self.map_keychain(KeychainKind::Internal);
+        let (_, drain_script) = self.change_spks(change_keychain).next().expect("infinite");
+
+        // Estimate the size of the replacement to find the lowest fee rate which pays for the
+        // original fee plus the incremental relay fee.
+        let estimated_weight = Transaction {
+            version: params.version.unwrap_or(transaction::Version::TWO),
+            lock_time: absolute::LockTime::ZERO,
+            input: utxos
+                .iter()
+                .map(|wutxo| bitcoin::TxIn {
+                    previous_output: wutxo.utxo.outpoint(),
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: drain_script,
+            }],
+        }
+        .weight()
+            + utxos
+                .iter()
+                .map(|wutxo| wutxo.satisfaction_weight)
+                .sum::<Weight>();
+        let required_fee = previous_fee.absolute + FeeRate::BROADCAST_MIN * estimated_weight;
+        let absolute_rate = FeeRate::from_sat_per_kwu(
+            (required_fee.to_sat() * 1000).div_ceil(estimated_weight.to_wu().max(1)),
+        );
+        let replacement_rate = FeeRate::from_sat_per_kwu(
+            previous_fee.rate.to_sat_per_kwu() + FeeRate::BROADCAST_MIN.to_sat_per_kwu(),
+        );
+
+        params.recipients.clear();
+        params.silent_payments.clear();
+        params.manually_selected_only = true;
+        params.fee_policy = Some(FeePolicy::FeeRate(absolute_rate.max(replacement_rate)));
+
+        Ok(TxBuilder {
+            wallet: self,
+            params,
+            coin_selection: DefaultCoinSelectionAlgorithm::default(),
+        })
+    }
+
+    /// Check a candidate replacement transaction against the [BIP125] replacement rules.
+    ///
+    /// The transactions replaced by `psbt` are the unconfirmed transactions of the wallet
+    /// spending any of its inputs. Together with their unconfirmed descendants they would be
+    /// evicted from the mempool if the replacement was accepted. The returned
+    /// [`ReplacementReport`] lists every rule broken by the replacement:
+    ///
+    /// 1. every directly conflicting transaction signals replaceability, explicitly or through
+    ///    one of its unconfirmed ancestors;
+    /// 2. the replacement only spends unconfirmed outputs of transactions the conflicting
+    ///    transactions already spend from;
+    /// 3. the replacement pays at least the sum of the fees of the evicted transactions;
+    /// 4. the replacement pays for its own size at the [`INCREMENTAL_RELAY_FEE`] on top of that;
+    /// 5. no more than [`MAX_REPLACEMENT_EVICTIONS`] transactions are evicted.
+    ///
+    /// The PSBT doesn't need to be signed: the weight of the inputs that aren't finalized yet is
+    /// estimated from the wallet descriptors. Inputs not belonging to the wallet should be
+    /// finalized to get an accurate weight.
+    ///
+    /// Returns an error if `psbt` doesn't conflict with any unconfirmed transaction of the wallet
+    /// or if any of the fees can't be calculated.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let txid: Txid = todo!();
+    /// let psbt = {
+    ///     let mut builder = wallet.build_fee_bump(txid)?;
+    ///     builder.fee_rate(FeeRate::from_sat_per_vb(5).expect("valid feerate"));
+    ///     builder.finish()?
+    /// };
+    /// let report = wallet.check_replacement(&psbt)?;
+    /// for violation in &report.violations {
+    ///     println!("replacement would be rejected: {violation}");
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [BIP125]: https://github.com/bitcoin/bips/blob/master/bip-0125.mediawiki
+    /// [`INCREMENTAL_RELAY_FEE`]: replacement::INCREMENTAL_RELAY_FEE
+    /// [`MAX_REPLACEMENT_EVICTIONS`]: replacement::MAX_REPLACEMENT_EVICTIONS
+    pub fn check_replacement(
+        &self,
+        psbt: &Psbt,
+    ) -> Result<ReplacementReport, CheckReplacementError> {
+        let graph = self.tx_graph.graph();
+        let tx = &psbt.unsigned_tx;
+        let unconfirmed_txids = self.unconfirmed_txids();
+
+        let mut conflicts = Vec::<Txid>::new();
+        for (_, txid) in graph.direct_conflicts(tx) {
+            if unconfirmed_txids.contains(&txid) && !conflicts.contains(&txid) {
+                conflicts.push(txid);
+            }
+        }
+        if conflicts.is_empty() {
+            return Err(CheckReplacementError::NoConflicts);
+        }
+        let evicted: Vec<Txid> = graph
+            .walk_conflicts(tx, |_, txid| {
+                unconfirmed_txids.contains(&txid).then_some(txid)
+            })
+            .collect();
+
+        let mut violations = Vec::new();
+
+        // Rule 1
+        let signals_rbf = |tx: &Transaction| tx.input.iter().any(|txin| txin.sequence.is_rbf());
+        let mut original_parents = HashSet::<Txid>::new();
+        for &txid in &conflicts {
+            let original = graph
+                .get_tx(txid)
+                .expect("conflicting transaction must be in the graph");
+            original_parents.extend(original.input.iter().map(|txin| txin.previous_output.txid));
+            let inherits_signal = graph
+                .walk_ancestors(original.clone(), |_, ancestor| {
+                    unconfirmed_txids
+                        .contains(&ancestor.compute_txid())
+                        .then_some(ancestor)
+                })
+                .any(|ancestor| signals_rbf(&ancestor));
+            if !signals_rbf(&original) && !inherits_signal {
+                violations.push(ReplacementViolation::NotSignaling(txid));
+            }
+        }
+
+        // Rule 2, outputs of the transactions the originals already spend from can be spent
+        for txin in &tx.input {
+            let outpoint = txin.previous_output;
+            if unconfirmed_txids.contains(&outpoint.txid)
+                && !original_parents.contains(&outpoint.txid)
+            {
+                violations.push(ReplacementViolation::NewUnconfirmedInput(outpoint));
+            }
         }
 
-        if !tx
+        // Rule 3
+        let fee = psbt.fee().map_err(CheckReplacementError::Psbt)?;
+        let evicted_fee = evicted
+            .iter()
+            .map(|&txid| {
+                graph
+                    .get_tx(txid)
+                    .and_then(|tx| self.calculate_fee(&tx).ok())
+                    .ok_or(CheckReplacementError::FeeUnavailable(txid))
+            })
+            .sum::<Result<Amount, _>>()?;
+        if fee < evicted_fee {
+            violations.push(ReplacementViolation::AbsoluteFeeTooLow {
+                fee,
+                required: evicted_fee,
+            });
+        }
+
+        // Rule 4
+        let weight = self.estimate_psbt_weight(psbt);
+        let additional = fee.checked_sub(evicted_fee).unwrap_or_default();
+        let required = replacement::INCREMENTAL_RELAY_FEE * weight;
+        if additional < required {
+            violations.push(ReplacementViolation::IncrementalFeeTooLow {
+                additional,
+                required,
+            });
+        }
+
+        // Rule 5
+        if evicted.len() > replacement::MAX_REPLACEMENT_EVICTIONS {
+            violations.push(ReplacementViolation::TooManyEvictions {
+                count: evicted.len(),
+                max: replacement::MAX_REPLACEMENT_EVICTIONS,
+            });
+        }
+
+        Ok(ReplacementReport {
+            conflicts,
+            evicted,
+            fee,
+            weight,
+            evicted_fee,
+            violations,
+        })
+    }
+
+    /// Estimate the weight of the transaction of `psbt` once signed.
+    ///
+    /// The weight of the inputs that aren't finalized is estimated from the wallet descriptors,
+    /// inputs not belonging to the wallet are counted without their satisfaction.
+    fn estimate_psbt_weight(&self, psbt: &Psbt) -> Weight {
+        let graph = self.tx_graph.graph();
+        let missing_satisfaction_weight = psbt
+            .unsigned_tx
             .input
             .iter()
-            .any(|txin| txin.sequence.to_consensus_u32() <= 0xFFFFFFFD)
+            .zip(&psbt.inputs)
+            .filter(|(_, psbt_input)| {
+                psbt_input.final_script_sig.is_none() && psbt_input.final_script_witness.is_none()
+            })
+            .filter_map(|(txin, psbt_input)| {
+                let spk = match &psbt_input.witness_utxo {
+                    Some(txout) => txout.script_pubkey.clone(),
+                    None => graph.get_txout(txin.previous_output)?.script_pubkey.clone(),
+                };
+                let (keychain, _) = self.derivation_of_spk(spk)?;
+                self.public_descriptor(keychain)
+                    .max_weight_to_satisfy()
+                    .ok()
+            })
+            .sum::<Weight>();
+        let extracted = psbt.clone().extract_tx_unchecked_fee_rate();
+        let mut weight = extracted.weight() + missing_satisfaction_weight;
+        if missing_satisfaction_weight > Weight::ZERO
+            && extracted.input.iter().all(|txin| txin.witness.is_empty())
         {
-            return Err(BuildFeeBumpError::IrreplaceableTransaction(
-                tx.compute_txid(),
-            ));
+            // The segwit marker and flag, assuming some of the missing satisfactions are witnesses.
+            weight += Weight::from_wu(2);
         }
+        weight
+    }
 
-        let fee = self
-            .calculate_fee(&tx)
-            .map_err(|_| BuildFeeBumpError::FeeRateUnavailable)?;
-        let fee_rate = fee / tx.weight();
+    /// Recover the inputs and the recipients of the transactions to replace, together with the
+    /// fee they paid.
+    fn fee_bump_params(&self, txids: &[Txid]) -> Result<TxParams, BuildFeeBumpError> {
+        let tx_graph = self.tx_graph.graph();
+        let txout_index = &self.tx_graph.index;
+        let chain_tip = self.chain.tip().block_id();
+        let chain_positions: HashMap<Txid, ChainPosition<_>> = tx_graph
+            .list_canonical_txs(&self.chain, chain_tip, CanonicalizationParams::default())
+            .map(|canon_tx| (canon_tx.tx_node.txid, canon_tx.chain_position))
+            .collect();
 
-        // Remove the inputs from the tx and process them.
-        let utxos: Vec<WeightedUtxo> = tx
-            .input
-            .drain(..)
-            .map(|txin| -> Result<_, BuildFeeBumpError> {
+        let mut replaced_txids = HashSet::<Txid>::new();
+        let mut txs = Vec::<Transaction>::new();
+        for &txid in txids {
+            if !replaced_txids.insert(txid) {
+                continue;
+            }
+
+            let tx = tx_graph
+                .get_tx(txid)
+                .ok_or(BuildFeeBumpError::TransactionNotFound(txid))?
+                .as_ref()
+                .clone();
+
+            if chain_positions
+                .get(&txid)
+                .ok_or(BuildFeeBumpError::TransactionNotFound(txid))?
+                .is_confirmed()
+            {
+                return Err(BuildFeeBumpError::TransactionConfirmed(txid));
+            }
+
+            if !tx
+                .input
+                .iter()
+                .any(|txin| txin.sequence.to_consensus_u32() <= 0xFFFFFFFD)
+            {
+                return Err(BuildFeeBumpError::IrreplaceableTransaction(
+                    tx.compute_txid(),
+                ));
+            }
+
+            // Merging transactions of different versions would apply the rules of one version
+            // to the others, e.g. the TRUC limits to non-TRUC transactions.
+            if txs.first().is_some_and(|first| first.version != tx.version) {
+                return Err(BuildFeeBumpError::MixedVersions);
+            }
+
+            txs.push(tx);
+        }
+
+        // The unconfirmed descendants of the replaced txs are evicted along with them, so the
+        // replacement has to pay for their fees too.
+        let mut evicted_txids = HashSet::<Txid>::new();
+        for &txid in &replaced_txids {
+            evicted_txids.extend(tx_graph.walk_descendants(txid, |_, txid| {
+                (!replaced_txids.contains(&txid)
+                    && chain_positions
+                        .get(&txid)
+                        .is_some_and(|position| !position.is_confirmed()))
+                .then_some(txid)
+            }));
+        }
+
+        // Outputs of a replaced tx that are spent by another replaced tx won't exist anymore.
+        let spent_in_batch: HashSet<OutPoint> = txs
+            .iter()
+            .flat_map(|tx| tx.input.iter().map(|txin| txin.previous_output))
+            .filter(|outpoint| replaced_txids.contains(&outpoint.txid))
+            .collect();
+
+        let mut previous_fee = tx_builder::PreviousFee {
+            absolute: Amount::ZERO,
+            rate: FeeRate::ZERO,
+        };
+        for txid in evicted_txids {
+            previous_fee.absolute += tx_graph
+                .get_tx(txid)
+                .and_then(|tx| self.calculate_fee(&tx).ok())
+                .ok_or(BuildFeeBumpError::FeeRateUnavailable)?;
+        }
+        let version = txs.first().map(|tx| tx.version);
+        let mut utxos = Vec::<RequiredUtxo>::new();
+        let mut silent_payments = Vec::<(usize, SilentPaymentAddress)>::new();
+        let mut recipients = Vec::<(ScriptBuf, Amount)>::new();
+
+        for mut tx in txs {
+            let txid = tx.compute_txid();
+            let fee = self
+                .calculate_fee(&tx)
+                .map_err(|_| BuildFeeBumpError::FeeRateUnavailable)?;
+            previous_fee.absolute += fee;
+            previous_fee.rate = previous_fee.rate.max(fee / tx.weight());
+
+            // Remove the inputs from the tx and process them.
+            for txin in tx.input.drain(..) {
                 let outpoint = txin.previous_output;
+                if replaced_txids.contains(&outpoint.txid) {
+                    continue;
+                }
                 let prev_txout = tx_graph
                     .get_txout(outpoint)
                     .cloned()
                     .ok_or(BuildFeeBumpError::UnknownUtxo(outpoint))?;
-                match txout_index.index_of_spk(prev_txout.script_pubkey.clone()) {
-                    Some(&(keychain, derivation_index)) => {
-                        let txout = prev_txout;
-                        let chain_position = chain_positions
-                            .get(&outpoint.txid)
-                            .cloned()
-                            .ok_or(BuildFeeBumpError::TransactionNotFound(outpoint.txid))?;
-                        Ok(WeightedUtxo {
-                            satisfaction_weight: self
-                                .public_descriptor(keychain)
-                                .max_weight_to_satisfy()
-                                .expect("descriptor should be satisfiable"),
-                            utxo: Utxo::Local(LocalOutput {
-                                outpoint,
-                                txout,
-                                keychain,
-                                is_spent: true,
-                                derivation_index,
-                                chain_position,
-                            }),
-                        })
+                let is_mine = txout_index
+                    .index_of_spk(prev_txout.script_pubkey.clone())
+                    .is_some()
+                    || self.silent_payments.outputs.contains_key(&outpoint);
+                let utxo = match is_mine {
+                    true => {
+                        if !chain_positions.contains_key(&outpoint.txid) {
+                            return Err(BuildFeeBumpError::TransactionNotFound(outpoint.txid));
+                        }
+                        RequiredUtxo::Local(outpoint)
                     }
-                    None => Ok(WeightedUtxo {
+                    false => RequiredUtxo::Foreign {
+                        outpoint,
+                        psbt_input: Box::new(psbt::Input {
+                            witness_utxo: prev_txout
+                                .script_pubkey
+                                .witness_version()
+                                .map(|_| prev_txout),
+                            non_witness_utxo: tx_graph
+                                .get_tx(outpoint.txid)
+                                .map(|tx| tx.as_ref().clone()),
+                            ..Default::default()
+                        }),
                         satisfaction_weight: Weight::from_wu_usize(
                             serialize(&txin.script_sig).len() * 4 + serialize(&txin.witness).len(),
                         ),
-                        utxo: Utxo::Foreign {
-                            outpoint,
-                            sequence: txin.sequence,
-                            psbt_input: Box::new(psbt::Input {
-                                witness_utxo: prev_txout
-                                    .script_pubkey
-                                    .witness_version()
-                                    .map(|_| prev_txout),
-                                non_witness_utxo: tx_graph
-                                    .get_tx(outpoint.txid)
-                                    .map(|tx| tx.as_ref().clone()),
-                                ..Default::default()
-                            }),
-                        },
-                    }),
-                }
-            })
-            .collect::<Result<_, _>>()?;
-
-        if tx.output.len() > 1 {
-            let mut change_index = None;
-            for (index, txout) in tx.output.iter().enumerate() {
-                let change_keychain = self.map_keychain(KeychainKind::Internal);
-                match txout_index.index_of_spk(txout.script_pubkey.clone()) {
-                    Some((keychain, _)) if *keychain == change_keychain => {
-                        change_index = Some(index)
+                        sequence: txin.sequence,
+                    },
+                };
+                utxos.push(utxo);
+            }
+
+            let mut outputs: Vec<TxOut> = tx
+                .output
+                .into_iter()
+                .enumerate()
+                .filter(|(vout, _)| !spent_in_batch.contains(&OutPoint::new(txid, *vout as u32)))
+                .map(|(_, txout)| txout)
+                .collect();
+
+            if outputs.len() > 1 {
+                let mut change_index = None;
+                for (index, txout) in outputs.iter().enumerate() {
+                    let change_keychain = self.map_keychain(KeychainKind::Internal);
+                    match txout_index.index_of_spk(txout.script_pubkey.clone()) {
+                        Some((keychain, _)) if *keychain == change_keychain => {
+                            change_index = Some(index)
+                        }
+                        _ => {}
                     }
-                    _ => {}
+                }
+
+                if let Some(change_index) = change_index {
+                    outputs.remove(change_index);
                 }
             }
 
-            if let Some(change_index) = change_index {
-                tx.output.remove(change_index);
+            for txout in outputs {
+                // The silent payment outputs are derived again from the inputs of the replacement.
+                if let Some(address) = self.silent_payments.sent.get(&txout.script_pubkey) {
+                    silent_payments.push((recipients.len(), *address));
+                }
+                recipients.push((txout.script_pubkey, txout.value));
             }
         }
 
-        let params = TxParams {
-            version: Some(tx.version),
-            recipients: tx
-                .output
-                .into_iter()
-                .map(|txout| (txout.script_pubkey, txout.value))
-                .collect(),
+        Ok(TxParams {
+            version,
+            recipients,
             utxos,
-            bumping_fee: Some(tx_builder::PreviousFee {
-                absolute: fee,
-                rate: fee_rate,
-            }),
+            silent_payments,
+            bumping_fee: Some(previous_fee),
+            truc: version == Some(TRUC_VERSION),
+            ..Default::default()
+        })
+    }
+
+    /// Spend an output of an unconfirmed transaction to bump its fee (*child pays for parent*).
+    ///
+    /// Unlike [`build_fee_bump`], this works for transactions we did not create, or that do not
+    /// signal RBF, as long as one of their outputs belongs to the wallet or is a keyless
+    /// pay-to-anchor (P2A) output. The returned [`TxBuilder`] spends the wallet's largest unlocked
+    /// output of `parent_txid`, or its unspent anchor if there is none, back to the wallet's change
+    /// keychain. The fee of the child is sized so that the whole package, made of
+    /// the child, the parent and all of the parent's unconfirmed ancestors, pays at least
+    /// `target_package_feerate`.
+    ///
+    /// Additional wallet UTXOs are selected by the coin selection algorithm if the parent output
+    /// is not enough to pay for the package. Setting a different fee rate on the returned builder
+    /// sets the target fee rate of the package, while [`TxBuilder::fee_absolute`] sets the fee of
+    /// the child alone.
+    ///
+    /// To calculate the package fee the wallet must know every previous output spent by the
+    /// unconfirmed ancestors, see [`insert_txout`].
+    ///
+    /// The change address is only revealed when the transaction is built with
+    /// [`TxBuilder::finish`].
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let parent_txid: Txid = todo!();
+    /// let mut psbt = {
+    ///     let builder = wallet.build_cpfp(
+    ///         parent_txid,
+    ///         FeeRate::from_sat_per_vb(10).expect("valid feerate"),
+    ///     )?;
+    ///     builder.finish()?
+    /// };
+    /// let _ = wallet.sign(&mut psbt, SignOptions::default())?;
+    /// // broadcast the child transaction
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`build_fee_bump`]: Self::build_fee_bump
+    /// [`insert_txout`]: Self::insert_txout
+    pub fn build_cpfp(
+        &mut self,
+        parent_txid: Txid,
+        target_package_feerate: FeeRate,
+    ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, BuildCpfpError> {
+        let unconfirmed_txids = self.unconfirmed_txids();
+
+        let parent = self
+            .tx_graph
+            .graph()
+            .get_tx(parent_txid)
+            .ok_or(BuildCpfpError::TransactionNotFound(parent_txid))?;
+        if !unconfirmed_txids.contains(&parent_txid) {
+            if self.get_tx(parent_txid).is_some() {
+                return Err(BuildCpfpError::TransactionConfirmed(parent_txid));
+            }
+            return Err(BuildCpfpError::TransactionNotFound(parent_txid));
+        }
+
+        let output = self
+            .unspent_utxos()
+            .filter(|utxo| utxo.outpoint().txid == parent_txid)
+            .filter(|utxo| !self.is_outpoint_locked(utxo.outpoint()))
+            .max_by_key(|utxo| utxo.txout().value);
+        let utxo = match output {
+            Some(output) => RequiredUtxo::Local(output.outpoint()),
+            // Fall back to an unspent keyless anchor of the parent.
+            None => {
+                let (outpoint, txout) = parent
+                    .output
+                    .iter()
+                    .enumerate()
+                    .map(|(vout, txout)| (OutPoint::new(parent_txid, vout as u32), txout))
+                    .filter(|(_, txout)| is_p2a(&txout.script_pubkey))
+                    .find(|(outpoint, _)| {
+                        !self
+                            .tx_graph
+                            .graph()
+                            .outspends(*outpoint)
+                            .iter()
+                            .any(|txid| unconfirmed_txids.contains(txid))
+                    })
+                    .ok_or(BuildCpfpError::NoSpendableOutput(parent_txid))?;
+                RequiredUtxo::Foreign {
+                    outpoint,
+                    psbt_input: Box::new(psbt::Input {
+                        witness_utxo: Some(txout.clone()),
+                        ..Default::default()
+                    }),
+                    satisfaction_weight: Weight::ZERO,
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                }
+            }
+        };
+
+        // The fees of the package must be known for the child to pay for it, see `bump_fee`.
+        self.unconfirmed_ancestor_package([parent_txid], &unconfirmed_txids)
+            .map_err(|_| BuildCpfpError::FeeRateUnavailable)?;
+
+        let params = TxParams {
+            utxos: vec![utxo],
+            fee_policy: Some(FeePolicy::FeeRate(target_package_feerate)),
+            // The child of a TRUC transaction must be TRUC too.
+            truc: parent.version == TRUC_VERSION,
             ..Default::default()
         };
 
@@ -1725,6 +2850,118 @@ impl Wallet {
         })
     }
 
+    /// Plan the consolidation of the wallet's small UTXOs while fees are low.
+    ///
+    /// A UTXO is worth consolidating if spending it later at `long_term_feerate` would cost more
+    /// than spending it now at `current_feerate`, as long as it's worth more than the fee to spend
+    /// it now. Among the UTXOs accepted by `filters`, the smallest of these are picked first, up to
+    /// `max_inputs` of them, and the returned [`TxBuilder`] sends them all to a change address at
+    /// `current_feerate`. Locked outpoints and immature coinbase outputs are never picked.
+    ///
+    /// Returns `None` if the fees saved on the picked UTXOs don't cover the cost of the
+    /// consolidation transaction plus the cost of spending its output at `long_term_feerate`, which
+    /// is always the case if `current_feerate` isn't lower than `long_term_feerate`.
+    ///
+    /// The change address is only revealed when the transaction is built with
+    /// [`TxBuilder::finish`].
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// let filters = UtxoFilters {
+    ///     min_confirmations: 6,
+    ///     ..Default::default()
+    /// };
+    /// if let Some(mut builder) = wallet.plan_consolidation(
+    ///     FeeRate::from_sat_per_vb_u32(1),
+    ///     FeeRate::from_sat_per_vb_u32(20),
+    ///     100,
+    ///     &filters,
+    /// ) {
+    ///     let psbt = builder.finish()?;
+    ///     // sign and broadcast the consolidation
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn plan_consolidation(
+        &mut self,
+        current_feerate: FeeRate,
+        long_term_feerate: FeeRate,
+        max_inputs: usize,
+        filters: &UtxoFilters,
+    ) -> Option<TxBuilder<'_, DefaultCoinSelectionAlgorithm>> {
+        let input_weight =
+            |satisfaction_weight| TxIn::default().segwit_weight() + satisfaction_weight;
+
+        let params = filters.tx_params();
+        let mut candidates = self
+            .filter_utxos(&params, self.chain.tip().height())
+            .into_iter()
+            .map(|weighted_utxo| {
+                let weight = input_weight(weighted_utxo.satisfaction_weight);
+                (weighted_utxo.utxo, weight)
+            })
+            .filter(|(utxo, weight)| utxo.txout().value > current_feerate * *weight)
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(utxo, _)| utxo.txout().value);
+        candidates.truncate(max_inputs);
+
+        // The consolidation is worth it if the fees saved on its inputs pay for the rest of the
+        // transaction now, and for spending its output later.
+        let inputs_weight = candidates
+            .iter()
+            .fold(Weight::ZERO, |acc, (_, weight)| acc + *weight);
+        let change_keychain = self.map_keychain(KeychainKind::Internal);
+        let output = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: self.peek_address(change_keychain, 0).script_pubkey(),
+        };
+        let tx_weight = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![output],
+        }
+        .weight();
+        let output_spend_weight = input_weight(
+            self.public_descriptor(change_keychain)
+                .max_weight_to_satisfy()
+                .expect("descriptor should be satisfiable"),
+        );
+        let savings = (long_term_feerate * inputs_weight)
+            .checked_sub(current_feerate * inputs_weight)
+            .unwrap_or(Amount::ZERO);
+        if savings <= current_feerate * tx_weight + long_term_feerate * output_spend_weight {
+            return None;
+        }
+
+        let planned = candidates
+            .iter()
+            .map(|(utxo, _)| utxo.outpoint())
+            .collect::<HashSet<_>>();
+        let unspendable = self
+            .unspent_utxos()
+            .map(|utxo| utxo.outpoint())
+            .filter(|outpoint| !planned.contains(outpoint))
+            .collect();
+
+        let params = TxParams {
+            drain_wallet: true,
+            unspendable,
+            fee_policy: Some(FeePolicy::FeeRate(current_feerate)),
+            ..params
+        };
+
+        Some(TxBuilder {
+            wallet: self,
+            params,
+            coin_selection: DefaultCoinSelectionAlgorithm::default(),
+        })
+    }
+
     /// Sign a transaction with all the wallet's signers, in the order specified by every signer's
     /// [`SignerOrdering`]. This function returns the `Result` type with an encapsulated `bool` that
     /// has the value true if the PSBT was finalized, or false otherwise.
@@ -1759,14 +2996,27 @@ impl Wallet {
         self.update_psbt_with_descriptor(psbt)
             .map_err(SignerError::MiniscriptPsbt)?;
 
-        // If we aren't allowed to use `witness_utxo`, ensure that every input (except p2tr and
-        // finalized ones) has the `non_witness_utxo`.
+        // If we aren't allowed to use `witness_utxo`, ensure that every input (except p2tr, p2a,
+        // received silent payments and finalized ones) has the `non_witness_utxo`.
         if !sign_options.trust_witness_utxo
             && psbt
                 .inputs
                 .iter()
+                .zip(&psbt.unsigned_tx.input)
+                .filter(|(_, txin)| {
+                    !self
+                        .silent_payments
+                        .outputs
+                        .contains_key(&txin.previous_output)
+                })
+                .map(|(i, _)| i)
                 .filter(|i| i.final_script_witness.is_none() && i.final_script_sig.is_none())
                 .filter(|i| i.tap_internal_key.is_none() && i.tap_merkle_root.is_none())
+                .filter(|i| {
+                    !i.witness_utxo
+                        .as_ref()
+                        .is_some_and(|txout| is_p2a(&txout.script_pubkey))
+                })
                 .any(|i| i.non_witness_utxo.is_none())
         {
             return Err(SignerError::MissingNonWitnessUtxo);
@@ -1793,6 +3043,7 @@ impl Wallet {
         {
             signer.sign_transaction(psbt, &sign_options, &self.secp)?;
         }
+        self.sign_silent_payment_inputs(psbt)?;
 
         // Attempt to finalize.
         if sign_options.try_finalize {
@@ -1877,6 +3128,46 @@ impl Wallet {
             if psbt_input.final_script_sig.is_some() || psbt_input.final_script_witness.is_some() {
                 continue;
             }
+            // Keyless anchors are spent with an empty witness.
+            if psbt
+                .get_utxo_for(n)
+                .is_some_and(|txout| is_p2a(&txout.script_pubkey))
+            {
+                let length = psbt.inputs.len();
+                let psbt_input = psbt
+                    .inputs
+                    .get_mut(n)
+                    .ok_or(IndexOutOfBoundsError::new(n, length))?;
+                let original = mem::take(psbt_input);
+                psbt_input.unknown = required_lock_time_fields(&original);
+                psbt_input.non_witness_utxo = original.non_witness_utxo;
+                psbt_input.witness_utxo = original.witness_utxo;
+                psbt_input.final_script_witness = Some(Witness::new());
+                continue;
+            }
+            // Received silent payments are spent through the taproot key path.
+            if self
+                .silent_payments
+                .outputs
+                .contains_key(&input.previous_output)
+            {
+                match psbt_input.tap_key_sig {
+                    Some(signature) => {
+                        let length = psbt.inputs.len();
+                        let psbt_input = psbt
+                            .inputs
+                            .get_mut(n)
+                            .ok_or(IndexOutOfBoundsError::new(n, length))?;
+                        let original = mem::take(psbt_input);
+                        psbt_input.unknown = required_lock_time_fields(&original);
+                        psbt_input.non_witness_utxo = original.non_witness_utxo;
+                        psbt_input.witness_utxo = original.witness_utxo;
+                        psbt_input.final_script_witness = Some(Witness::p2tr_key_spend(&signature));
+                    }
+                    None => finished = false,
+                }
+                continue;
+            }
             let confirmation_height = confirmation_heights
                 .get(&input.previous_output.txid)
                 .copied();
@@ -1919,6 +3210,8 @@ impl Wallet {
                                 .get_mut(n)
                                 .ok_or(IndexOutOfBoundsError::new(n, length))?;
                             let original = mem::take(psbt_input);
+                            // Keep the lock time requirements of PSBTv2 inputs.
+                            psbt_input.unknown = required_lock_time_fields(&original);
                             psbt_input.non_witness_utxo = original.non_witness_utxo;
                             psbt_input.witness_utxo = original.witness_utxo;
                             if !tmp_input.script_sig.is_empty() {
@@ -1976,62 +3269,403 @@ impl Wallet {
         descriptor.at_derivation_index(child).ok()
     }
 
+    /// Check the [BIP431] topology and size rules of a TRUC transaction with the given fee and
+    /// estimated weight.
+    ///
+    /// If an unconfirmed parent already has another child, `tx` must pay enough to evict it
+    /// (sibling eviction).
+    ///
+    /// [BIP431]: https://github.com/bitcoin/bips/blob/master/bip-0431.mediawiki
+    fn check_truc(
+        &self,
+        tx: &Transaction,
+        fee: Amount,
+        weight: Weight,
+    ) -> Result<(), CreateTxError> {
+        let graph = self.tx_graph.graph();
+        let unconfirmed_txids = self.unconfirmed_txids();
+
+        let mut parent = None;
+        for txin in &tx.input {
+            let outpoint = txin.previous_output;
+            if !unconfirmed_txids.contains(&outpoint.txid) {
+                continue;
+            }
+            let parent_tx = graph
+                .get_tx(outpoint.txid)
+                .expect("unconfirmed transaction must be in the graph");
+            if parent_tx.version != TRUC_VERSION {
+                return Err(CreateTxError::TrucNonTrucUnconfirmedInput(outpoint));
+            }
+            // A TRUC transaction can have a single unconfirmed ancestor.
+            if parent.is_some_and(|txid| txid != outpoint.txid)
+                || parent_tx
+                    .input
+                    .iter()
+                    .any(|txin| unconfirmed_txids.contains(&txin.previous_output.txid))
+            {
+                return Err(CreateTxError::TrucTooManyUnconfirmedAncestors);
+            }
+            parent = Some(outpoint.txid);
+        }
+
+        let max_vsize = match parent {
+            Some(_) => TRUC_CHILD_MAX_VSIZE,
+            None => TRUC_MAX_VSIZE,
+        };
+        let vsize = weight.to_vbytes_ceil();
+        if vsize > max_vsize {
+            return Err(CreateTxError::TrucTooLarge {
+                vsize,
+                max: max_vsize,
+            });
+        }
+
+        // A TRUC transaction can have a single unconfirmed child. Any other child spending
+        // different outputs of the parent is evicted if `tx` pays enough to replace it.
+        if let Some(parent_txid) = parent {
+            let spent: HashSet<OutPoint> =
+                tx.input.iter().map(|txin| txin.previous_output).collect();
+            let parent_tx = graph
+                .get_tx(parent_txid)
+                .expect("unconfirmed transaction must be in the graph");
+            let siblings: HashSet<Txid> = (0..parent_tx.output.len() as u32)
+                .map(|vout| OutPoint::new(parent_txid, vout))
+                .filter(|outpoint| !spent.contains(outpoint))
+                .flat_map(|outpoint| graph.outspends(outpoint).iter().copied())
+                .filter(|txid| unconfirmed_txids.contains(txid))
+                .filter(|txid| {
+                    // Transactions conflicting with `tx` are replaced anyway.
+                    graph.get_tx(*txid).is_some_and(|sibling| {
+                        sibling
+                            .input
+                            .iter()
+                            .all(|txin| !spent.contains(&txin.previous_output))
+                    })
+                })
+                .collect();
+            let sibling_fee = siblings
+                .iter()
+                .filter_map(|txid| graph.get_tx(*txid))
+                .map(|sibling| self.calculate_fee(&sibling).unwrap_or_default())
+                .sum::<Amount>();
+            if !siblings.is_empty() {
+                let required = sibling_fee + FeeRate::BROADCAST_MIN * weight;
+                if fee < required {
+                    return Err(CreateTxError::FeeTooLow { required });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Txids of the canonical transactions that are not confirmed yet.
+    fn unconfirmed_txids(&self) -> HashSet<Txid> {
+        let chain_tip = self.chain.tip().block_id();
+        self.tx_graph
+            .graph()
+            .list_canonical_txs(&self.chain, chain_tip, CanonicalizationParams::default())
+            .filter(|canon_tx| !canon_tx.chain_position.is_confirmed())
+            .map(|canon_tx| canon_tx.tx_node.txid)
+            .collect()
+    }
+
+    /// Sum the fee and weight of `txids` and of all their unconfirmed ancestors.
+    ///
+    /// Transactions that are not part of `unconfirmed_txids` are considered confirmed and stop
+    /// the walk.
+    fn unconfirmed_ancestor_package(
+        &self,
+        txids: impl IntoIterator<Item = Txid>,
+        unconfirmed_txids: &HashSet<Txid>,
+    ) -> Result<AncestorPackage, CalculateFeeError> {
+        let graph = self.tx_graph.graph();
+        let mut package = AncestorPackage::default();
+        let mut visited = HashSet::<Txid>::new();
+        let mut to_visit = txids.into_iter().collect::<Vec<_>>();
+
+        while let Some(txid) = to_visit.pop() {
+            if !unconfirmed_txids.contains(&txid) || !visited.insert(txid) {
+                continue;
+            }
+            let tx = match graph.get_tx(txid) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            package.fee += graph.calculate_fee(&tx)?;
+            package.weight += tx.weight();
+            to_visit.extend(tx.input.iter().map(|txin| txin.previous_output.txid));
+        }
+
+        Ok(package)
+    }
+
+    /// The fee needed to bring `txids` and their unconfirmed ancestors up to `fee_rate`, like
+    /// Bitcoin Core's "bump fee", or `None` if the fee of one of them can't be calculated.
+    fn bump_fee(
+        &self,
+        txids: impl IntoIterator<Item = Txid>,
+        fee_rate: FeeRate,
+        unconfirmed_txids: &HashSet<Txid>,
+    ) -> Option<Amount> {
+        let package = self
+            .unconfirmed_ancestor_package(txids, unconfirmed_txids)
+            .ok()?;
+        Some(
+            (fee_rate * package.weight)
+                .checked_sub(package.fee)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// The scripts of `keychain` that can receive change, in order: the revealed unused ones, then
+    /// the ones that aren't revealed yet. The script of a non-ranged descriptor is repeated.
+    fn change_spks(&self, keychain: KeychainKind) -> impl Iterator<Item = (u32, ScriptBuf)> + '_ {
+        let has_wildcard = self.public_descriptor(keychain).has_wildcard();
+        let (next_index, _) = self
+            .tx_graph
+            .index
+            .next_index(keychain)
+            .expect("keychain must exist");
+        self.tx_graph
+            .index
+            .unused_keychain_spks(keychain)
+            .chain((next_index..).map(move |index| {
+                let index = if has_wildcard { index } else { next_index };
+                (index, self.peek_address(keychain, index).script_pubkey())
+            }))
+    }
+
+    /// Split the change of `tx` into the values of its change outputs, according to the change
+    /// strategy of `params`.
+    ///
+    /// `amount` is the change left for a single output once its `fee` is paid.
+    fn split_change(
+        &self,
+        params: &TxParams,
+        tx: &Transaction,
+        selected: &[Utxo],
+        amount: Amount,
+        fee: Amount,
+        drain_script: &Script,
+    ) -> Result<Vec<Amount>, CreateTxError> {
+        if let ChangeStrategy::Single = params.change_strategy {
+            return Ok(vec![amount]);
+        }
+        let spent = selected
+            .iter()
+            .map(|utxo| utxo.outpoint())
+            .collect::<HashSet<_>>();
+        let context = ChangeContext {
+            amount,
+            output_fee: fee,
+            dust_threshold: drain_script.minimal_non_dust(),
+            payments: tx.output.iter().map(|txout| txout.value).collect(),
+            unspent: self
+                .unspent_utxos()
+                .filter(|utxo| !spent.contains(&utxo.outpoint()))
+                .map(|utxo| utxo.txout().value)
+                .collect(),
+        };
+        params
+            .change_strategy
+            .split(&context)
+            .ok_or(CreateTxError::InvalidChangeSplit)
+    }
+
+    /// The conditions to spend the outputs of each keychain with the policy paths of `params`.
+    ///
+    /// A keychain maps to `None` if its spending policy requires a policy path which isn't given,
+    /// or can't be satisfied with the given one.
+    fn spending_conditions(&self, params: &TxParams) -> BTreeMap<KeychainKind, Option<Condition>> {
+        self.keychains()
+            .map(|(keychain, descriptor)| {
+                let (signers, policy_path) = match keychain {
+                    KeychainKind::External => (&self.signers, &params.external_policy_path),
+                    KeychainKind::Internal => (&self.change_signers, &params.internal_policy_path),
+                };
+                let condition =
+                    match descriptor.extract_policy(signers, BuildSatisfaction::None, &self.secp) {
+                        Ok(Some(policy)) if policy.requires_path() && policy_path.is_none() => None,
+                        Ok(Some(policy)) => policy
+                            .get_condition(policy_path.as_ref().unwrap_or(&BTreeMap::new()))
+                            .ok(),
+                        Ok(None) => Some(Condition::default()),
+                        Err(_) => None,
+                    };
+                (keychain, condition)
+            })
+            .collect()
+    }
+
+    /// Looks up the UTXOs that must be spent.
+    ///
+    /// The outputs of the wallet are found even if they are spent, so that a fee bump can spend
+    /// the inputs of the transactions it replaces. Returns the outpoint of the first output which
+    /// isn't in the wallet.
+    fn required_utxos(&self, utxos: &[RequiredUtxo]) -> Result<Vec<WeightedUtxo>, OutPoint> {
+        let outpoints: HashSet<OutPoint> = utxos
+            .iter()
+            .filter_map(|utxo| match utxo {
+                RequiredUtxo::Local(outpoint) => Some(*outpoint),
+                RequiredUtxo::Foreign { .. } => None,
+            })
+            .collect();
+        let outputs: HashMap<OutPoint, WeightedUtxo> = match outpoints.is_empty() {
+            true => HashMap::new(),
+            false => self
+                .list_output()
+                .filter(|output| outpoints.contains(&output.outpoint))
+                .map(|output| {
+                    let satisfaction_weight = self.satisfaction_weight(&output);
+                    (
+                        output.outpoint,
+                        WeightedUtxo::new(satisfaction_weight, Utxo::Local(output)),
+                    )
+                })
+                .chain(
+                    self.list_silent_payment_output()
+                        .filter(|output| outpoints.contains(&output.outpoint))
+                        .map(|output| {
+                            let satisfaction_weight = silent_payments::SATISFACTION_WEIGHT;
+                            let outpoint = output.outpoint;
+                            let utxo = Utxo::SilentPayment(output);
+                            (outpoint, WeightedUtxo::new(satisfaction_weight, utxo))
+                        }),
+                )
+                .collect(),
+        };
+
+        utxos
+            .iter()
+            .map(|utxo| match utxo {
+                RequiredUtxo::Local(outpoint) => outputs.get(outpoint).cloned().ok_or(*outpoint),
+                RequiredUtxo::Foreign {
+                    outpoint,
+                    psbt_input,
+                    satisfaction_weight,
+                    sequence,
+                } => Ok(WeightedUtxo::new(
+                    *satisfaction_weight,
+                    Utxo::Foreign {
+                        outpoint: *outpoint,
+                        sequence: *sequence,
+                        psbt_input: psbt_input.clone(),
+                    },
+                )),
+            })
+            .collect()
+    }
+
     /// Given the options returns the list of utxos that must be used to form the
     /// transaction and any further that may be used if needed.
     fn filter_utxos(&self, params: &TxParams, current_height: u32) -> Vec<WeightedUtxo> {
-        if params.manually_selected_only {
-            vec![]
-        // Only process optional UTxOs if manually_selected_only is false.
-        } else {
-            let manually_selected_outpoints = params
-                .utxos
-                .iter()
-                .map(|wutxo| wutxo.utxo.outpoint())
-                .collect::<HashSet<OutPoint>>();
-            self.tx_graph
-                .graph()
-                // Get all unspent UTxOs from wallet.
-                // NOTE: the UTxOs returned by the following method already belong to wallet as the
-                // call chain uses get_tx_node infallibly.
-                .filter_chain_unspents(
-                    &self.chain,
-                    self.chain.tip().block_id(),
-                    CanonicalizationParams::default(),
-                    self.tx_graph.index.outpoints().iter().cloned(),
-                )
-                // Filter out locked outpoints.
-                .filter(|(_, txo)| !self.is_outpoint_locked(txo.outpoint))
-                // Only create LocalOutput if UTxO is mature.
-                .filter_map(move |((k, i), full_txo)| {
-                    full_txo
-                        .is_mature(current_height)
-                        .then(|| new_local_utxo(k, i, full_txo))
-                })
-                // only process UTXOs not selected manually, they will be considered later in the
-                // chain
-                // NOTE: this avoid UTXOs in both required and optional list
-                .filter(|may_spend| !manually_selected_outpoints.contains(&may_spend.outpoint))
-                // only add to optional UTxOs those which satisfy the change policy if we reuse
-                // change
-                .filter(|local_output| {
-                    self.keychains().count() == 1
-                        || params.change_policy.is_satisfied_by(local_output)
-                })
-                // Only add to optional UTxOs those marked as spendable.
-                .filter(|local_output| !params.unspendable.contains(&local_output.outpoint))
-                // If bumping fees only add to optional UTxOs those confirmed.
-                .filter(|local_output| {
-                    params.bumping_fee.is_none() || local_output.chain_position.is_confirmed()
-                })
-                .map(|utxo| WeightedUtxo {
-                    satisfaction_weight: self
-                        .public_descriptor(utxo.keychain)
-                        .max_weight_to_satisfy()
-                        .unwrap(),
-                    utxo: Utxo::Local(utxo),
-                })
-                .collect()
-        }
+        let manually_selected_outpoints = params
+            .utxos
+            .iter()
+            .map(RequiredUtxo::outpoint)
+            .collect::<HashSet<OutPoint>>();
+        self.unspent_with_exclusion(params, current_height)
+            .into_iter()
+            // only process UTXOs not selected manually, they will be considered later in the
+            // chain
+            // NOTE: this avoid UTXOs in both required and optional list
+            .filter(|(may_spend, _)| {
+                !manually_selected_outpoints.contains(&may_spend.utxo.outpoint())
+            })
+            .filter_map(|(utxo, exclusion)| exclusion.is_none().then_some(utxo))
+            .collect()
+    }
+
+    /// Returns the unspent outputs of the wallet, with the reason they can't be spent by a
+    /// transaction built with `params`, if any.
+    fn unspent_with_exclusion(
+        &self,
+        params: &TxParams,
+        current_height: u32,
+    ) -> Vec<(WeightedUtxo, Option<ExclusionReason>)> {
+        let conditions = self.spending_conditions(params);
+        let graph = self.tx_graph.graph();
+        let tip = self.chain.tip().block_id();
+        // Get all unspent UTxOs from wallet.
+        // NOTE: the UTxOs returned by the following method already belong to wallet as the
+        // call chain uses get_tx_node infallibly.
+        let local = graph
+            .filter_chain_unspents(
+                &self.chain,
+                tip,
+                CanonicalizationParams::default(),
+                self.tx_graph.index.outpoints().iter().cloned(),
+            )
+            .map(|((k, i), full_txo)| {
+                let is_mature = full_txo.is_mature(current_height);
+                let utxo = new_local_utxo(k, i, full_txo);
+                let is_change_allowed = params.change_policy.is_satisfied_by(&utxo);
+                let is_policy_satisfied = conditions.get(&k).is_some_and(Option::is_some);
+                let chain_position = utxo.chain_position;
+                let weighted_utxo =
+                    WeightedUtxo::new(self.satisfaction_weight(&utxo), Utxo::Local(utxo));
+                let checks = (is_mature, is_change_allowed, is_policy_satisfied);
+                (weighted_utxo, chain_position, checks)
+            });
+        // Silent payment outputs aren't change, and are spent with a single key.
+        let silent_payments = graph
+            .filter_chain_unspents(
+                &self.chain,
+                tip,
+                CanonicalizationParams::default(),
+                self.silent_payment_outpoints(),
+            )
+            .map(|(output, full_txo)| {
+                let is_mature = full_txo.is_mature(current_height);
+                let utxo = new_silent_payment_utxo(output, full_txo);
+                let is_change_allowed =
+                    params.change_policy != tx_builder::ChangeSpendPolicy::OnlyChange;
+                let chain_position = utxo.chain_position;
+                let weighted_utxo = WeightedUtxo::new(
+                    silent_payments::SATISFACTION_WEIGHT,
+                    Utxo::SilentPayment(utxo),
+                );
+                let checks = (is_mature, is_change_allowed, true);
+                (weighted_utxo, chain_position, checks)
+            });
+
+        local
+            .chain(silent_payments)
+            .map(
+                |(
+                    weighted_utxo,
+                    chain_position,
+                    (is_mature, is_change_allowed, is_policy_satisfied),
+                )| {
+                    let outpoint = weighted_utxo.utxo.outpoint();
+                    let exclusion = if params.manually_selected_only {
+                        Some(ExclusionReason::ManuallySelectedOnly)
+                    } else if self.is_outpoint_locked(outpoint) {
+                        Some(ExclusionReason::Locked)
+                    } else if !is_mature {
+                        Some(ExclusionReason::Immature)
+                    // only spend those which satisfy the change policy if we reuse change
+                    } else if self.keychains().count() != 1 && !is_change_allowed {
+                        Some(ExclusionReason::ChangePolicy)
+                    } else if params.unspendable.contains(&outpoint) {
+                        Some(ExclusionReason::Unspendable)
+                    // If bumping fees or building a TRUC transaction only spend those confirmed.
+                    } else if (params.bumping_fee.is_some() || params.truc)
+                        && !chain_position.is_confirmed()
+                        || confirmations(&chain_position, current_height) < params.min_confirmations
+                    {
+                        Some(ExclusionReason::Unconfirmed)
+                    } else if !is_policy_satisfied {
+                        Some(ExclusionReason::Policy)
+                    } else {
+                        None
+                    };
+                    (weighted_utxo, exclusion)
+                },
+            )
+            .collect()
     }
 
     fn complete_transaction(
@@ -2087,17 +3721,27 @@ impl Wallet {
                             },
                         }
                 }
+                // Received silent payments are taproot outputs without a descriptor.
+                Utxo::SilentPayment(utxo) => {
+                    *psbt_input = psbt::Input {
+                        sighash_type: params.sighash,
+                        witness_utxo: Some(utxo.txout),
+                        ..psbt::Input::default()
+                    }
+                }
                 Utxo::Foreign {
                     outpoint,
                     psbt_input: foreign_psbt_input,
                     ..
                 } => {
-                    let is_taproot = foreign_psbt_input
+                    // Neither taproot nor keyless anchor inputs need the previous transaction:
+                    // the former commit to every spent amount, the latter aren't signed.
+                    let is_taproot_or_anchor = foreign_psbt_input
                         .witness_utxo
                         .as_ref()
-                        .map(|txout| txout.script_pubkey.is_p2tr())
+                        .map(|txout| txout.script_pubkey.is_p2tr() || is_p2a(&txout.script_pubkey))
                         .unwrap_or(false);
-                    if !is_taproot
+                    if !is_taproot_or_anchor
                         && !params.only_witness_utxo
                         && foreign_psbt_input.non_witness_utxo.is_none()
                     {
@@ -2362,9 +4006,9 @@ impl Wallet {
 
     /// List unspent outpoints that are currently locked.
     pub fn list_locked_unspent(&self) -> impl Iterator<Item = OutPoint> + '_ {
-        self.list_unspent()
-            .filter(|output| self.is_outpoint_locked(output.outpoint))
-            .map(|output| output.outpoint)
+        self.unspent_utxos()
+            .map(|utxo| utxo.outpoint())
+            .filter(|&outpoint| self.is_outpoint_locked(outpoint))
     }
 
     /// Whether the `outpoint` is locked. See [`Wallet::lock_outpoint`] for more.
@@ -2408,17 +4052,7 @@ impl Wallet {
     ///
     /// [`apply_block_connected_to`]: Self::apply_block_connected_to
     pub fn apply_block(&mut self, block: &Block, height: u32) -> Result<(), CannotConnectError> {
-        let connected_to = match height.checked_sub(1) {
-            Some(prev_height) => BlockId {
-                height: prev_height,
-                hash: block.header.prev_blockhash,
-            },
-            None => BlockId {
-                height,
-                hash: block.block_hash(),
-            },
-        };
-        self.apply_block_connected_to(block, height, connected_to)
+        self.apply_block_connected_to(block, height, prev_block_id(block, height))
             .map_err(|err| match err {
                 ApplyHeaderError::InconsistentBlocks => {
                     unreachable!("connected_to is derived from the block so must be consistent")
@@ -2427,6 +4061,47 @@ impl Wallet {
             })
     }
 
+    /// Introduces a `block` of `height` to the wallet like [`apply_block`], and finds the
+    /// transactions paying the wallet's [`silent_payment_address`].
+    ///
+    /// Finding silent payments requires the outputs spent by each transaction of the block. They
+    /// are looked up in `prevouts`, in the `block` itself and in the wallet's transaction graph.
+    /// If one of them can't be found, [`ApplyBlockError::MissingPrevout`] is returned and the
+    /// wallet is left unchanged.
+    ///
+    /// **WARNING**: You must persist the changes resulting from one or more calls to this method
+    /// if you need the inserted block data to be reloaded after closing the wallet.
+    /// See [`Wallet::reveal_next_address`].
+    ///
+    /// [`apply_block`]: Self::apply_block
+    /// [`silent_payment_address`]: Self::silent_payment_address
+    pub fn apply_block_with_prevouts(
+        &mut self,
+        block: &Block,
+        height: u32,
+        prevouts: impl IntoIterator<Item = (OutPoint, TxOut)>,
+    ) -> Result<(), ApplyBlockError> {
+        let prevouts = match self.silent_payment_keys {
+            Some(_) => Some(
+                self.block_prevouts(block, prevouts.into_iter().collect())
+                    .map_err(ApplyBlockError::MissingPrevout)?,
+            ),
+            None => None,
+        };
+        self.apply_block_with(
+            block,
+            height,
+            prev_block_id(block, height),
+            prevouts.as_ref(),
+        )
+        .map_err(|err| match err {
+            ApplyHeaderError::InconsistentBlocks => {
+                unreachable!("connected_to is derived from the block so must be consistent")
+            }
+            ApplyHeaderError::CannotConnect(err) => ApplyBlockError::CannotConnect(err),
+        })
+    }
+
     /// Introduces a `block` of `height` to the wallet, and tries to connect it to the
     /// `prev_blockhash` of the block's header and returns events.
     ///
@@ -2453,14 +4128,32 @@ impl Wallet {
     /// [`LocalChain`]. Relevant transactions are filtered from the `block` and inserted into the
     /// internal [`TxGraph`].
     ///
+    /// The transactions spending the silent payments received by the wallet are inserted too, but
+    /// the ones paying its [`silent_payment_address`] are not found, as that requires the outputs
+    /// they spend: use [`apply_block_with_prevouts`] instead.
+    ///
     /// **WARNING**: You must persist the changes resulting from one or more calls to this method
     /// if you need the inserted block data to be reloaded after closing the wallet.
     /// See [`Wallet::reveal_next_address`].
+    ///
+    /// [`silent_payment_address`]: Self::silent_payment_address
+    /// [`apply_block_with_prevouts`]: Self::apply_block_with_prevouts
     pub fn apply_block_connected_to(
         &mut self,
         block: &Block,
         height: u32,
         connected_to: BlockId,
+    ) -> Result<(), ApplyHeaderError> {
+        self.apply_block_with(block, height, connected_to, None)
+    }
+
+    /// Applies `block`, scanning it for silent payments if the outputs it spends are given.
+    fn apply_block_with(
+        &mut self,
+        block: &Block,
+        height: u32,
+        connected_to: BlockId,
+        prevouts: Option<&HashMap<OutPoint, TxOut>>,
     ) -> Result<(), ApplyHeaderError> {
         let mut changeset = ChangeSet::default();
         changeset.merge(
@@ -2469,10 +4162,118 @@ impl Wallet {
                 .into(),
         );
         changeset.merge(self.tx_graph.apply_block_relevant(block, height).into());
+        changeset.merge(self.scan_block_silent_payments(block, height, prevouts));
         self.stage.merge(changeset);
         Ok(())
     }
 
+    /// Looks up the outputs spent by the transactions of `block` in `prevouts`, in the block and
+    /// in the transaction graph. Returns the first outpoint which can't be found.
+    fn block_prevouts(
+        &self,
+        block: &Block,
+        mut prevouts: HashMap<OutPoint, TxOut>,
+    ) -> Result<HashMap<OutPoint, TxOut>, OutPoint> {
+        let block_txs: HashMap<Txid, &Transaction> = block
+            .txdata
+            .iter()
+            .map(|tx| (tx.compute_txid(), tx))
+            .collect();
+        for txin in block
+            .txdata
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .flat_map(|tx| &tx.input)
+        {
+            let outpoint = txin.previous_output;
+            if prevouts.contains_key(&outpoint) {
+                continue;
+            }
+            let txout = match block_txs.get(&outpoint.txid) {
+                Some(prev_tx) => prev_tx.output.get(outpoint.vout as usize).cloned(),
+                None => self.tx_graph.graph().get_txout(outpoint).cloned(),
+            };
+            prevouts.insert(outpoint, txout.ok_or(outpoint)?);
+        }
+        Ok(prevouts)
+    }
+
+    /// Insert the transactions of `block` spending the silent payments the wallet received and,
+    /// given the outputs spent in the block, the ones paying its silent payment addresses, and
+    /// record the outputs found.
+    fn scan_block_silent_payments(
+        &mut self,
+        block: &Block,
+        height: u32,
+        prevouts: Option<&HashMap<OutPoint, TxOut>>,
+    ) -> ChangeSet {
+        let mut changeset = ChangeSet::default();
+        let (Some((scan_key, _)), Some((_, spend_public_key))) =
+            (self.silent_payment_keys, self.silent_payments.keys)
+        else {
+            return changeset;
+        };
+        let labels: Vec<_> = self
+            .silent_payments
+            .labels
+            .iter()
+            .filter_map(|&m| {
+                let tweak = label_tweak(&scan_key, m)?;
+                Some((m, tweak, tweak.public_key(&self.secp)))
+            })
+            .collect();
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId {
+                height,
+                hash: block.block_hash(),
+            },
+            confirmation_time: block.header.time as u64,
+        };
+
+        for tx in block.txdata.iter().filter(|tx| !tx.is_coinbase()) {
+            let tx_prevouts: Option<Vec<TxOut>> = prevouts.and_then(|prevouts| {
+                tx.input
+                    .iter()
+                    .map(|txin| prevouts.get(&txin.previous_output).cloned())
+                    .collect()
+            });
+            let found = tx_prevouts
+                .and_then(|tx_prevouts| {
+                    scan_transaction(
+                        &self.secp,
+                        &scan_key,
+                        &spend_public_key,
+                        &labels,
+                        tx,
+                        &tx_prevouts,
+                    )
+                })
+                .unwrap_or_default();
+            let spends_silent_payment = tx.input.iter().any(|txin| {
+                self.silent_payments
+                    .outputs
+                    .contains_key(&txin.previous_output)
+            });
+            if found.is_empty() && !spends_silent_payment {
+                continue;
+            }
+
+            let txid = tx.compute_txid();
+            let silent_payments = silent_payments::ChangeSet {
+                outputs: found
+                    .into_iter()
+                    .map(|(vout, output)| (OutPoint::new(txid, vout), output))
+                    .collect(),
+                ..Default::default()
+            };
+            self.silent_payments.merge(silent_payments.clone());
+            changeset.merge(silent_payments.into());
+            changeset.merge(self.tx_graph.insert_tx(tx.clone()).into());
+            changeset.merge(self.tx_graph.insert_anchor(txid, anchor).into());
+        }
+        changeset
+    }
+
     /// Applies relevant transactions from `block` of `height` to the wallet, connects the
     /// block to the internal chain and returns events.
     ///
@@ -2813,6 +4614,35 @@ where
     Ok(wallet_name)
 }
 
+/// Number of confirmations of `utxo` once a transaction spending it is mined in the block after
+/// `current_height`.
+fn confirmations(
+    chain_position: &ChainPosition<ConfirmationBlockTime>,
+    current_height: u32,
+) -> u32 {
+    chain_position
+        .confirmation_height_upper_bound()
+        .map_or(0, |height| {
+            current_height.saturating_add(1).saturating_sub(height)
+        })
+}
+
+/// Whether the timelocks of `condition` allow spending `utxo` in the block after `current_height`.
+fn is_timelock_reached(utxo: &LocalOutput, condition: &Condition, current_height: u32) -> bool {
+    let csv_reached = match condition.csv.and_then(|csv| csv.to_relative_lock_time()) {
+        None => true,
+        Some(relative::LockTime::Blocks(blocks)) => {
+            confirmations(&utxo.chain_position, current_height) >= u32::from(blocks.value())
+        }
+        Some(relative::LockTime::Time(_)) => utxo.chain_position.is_confirmed(),
+    };
+    let timelock_reached = match condition.timelock {
+        Some(absolute::LockTime::Blocks(height)) => height.to_consensus_u32() <= current_height,
+        _ => true,
+    };
+    csv_reached && timelock_reached
+}
+
 fn new_local_utxo(
     keychain: KeychainKind,
     derivation_index: u32,
@@ -2828,6 +4658,33 @@ fn new_local_utxo(
     }
 }
 
+/// The block preceding `block`, which is at `height`.
+fn prev_block_id(block: &Block, height: u32) -> BlockId {
+    match height.checked_sub(1) {
+        Some(prev_height) => BlockId {
+            height: prev_height,
+            hash: block.header.prev_blockhash,
+        },
+        None => BlockId {
+            height,
+            hash: block.block_hash(),
+        },
+    }
+}
+
+fn new_silent_payment_utxo(
+    output: SilentPaymentOutput,
+    full_txo: FullTxOut<ConfirmationBlockTime>,
+) -> SilentPaymentUtxo {
+    SilentPaymentUtxo {
+        outpoint: full_txo.outpoint,
+        txout: full_txo.txout,
+        is_spent: full_txo.spent_by.is_some(),
+        output,
+        chain_position: full_txo.chain_position,
+    }
+}
+
 fn make_indexed_graph(
     stage: &mut ChangeSet,
     tx_graph_changeset: chain::tx_graph::ChangeSet<ConfirmationBlockTime>,
@@ -2992,6 +4849,7 @@ mod test {
                     .public_descriptor(utxo.keychain)
                     .max_weight_to_satisfy()
                     .unwrap(),
+                bump_fee: Amount::ZERO,
                 utxo: Utxo::Local(utxo),
             })
             .unwrap()];
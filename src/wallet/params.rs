@@ -1,6 +1,7 @@
 use alloc::boxed::Box;
 
 use bdk_chain::keychain_txout::DEFAULT_LOOKAHEAD;
+use bitcoin::secp256k1::SecretKey;
 use bitcoin::{BlockHash, Network, NetworkKind};
 use miniscript::descriptor::KeyMap;
 
@@ -67,6 +68,7 @@ pub struct CreateParams {
     pub(crate) genesis_hash: Option<BlockHash>,
     pub(crate) lookahead: u32,
     pub(crate) use_spk_cache: bool,
+    pub(crate) silent_payment_keys: Option<(SecretKey, SecretKey)>,
 }
 
 impl CreateParams {
@@ -90,6 +92,7 @@ impl CreateParams {
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
             use_spk_cache: false,
+            silent_payment_keys: None,
         }
     }
 
@@ -112,6 +115,7 @@ impl CreateParams {
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
             use_spk_cache: false,
+            silent_payment_keys: None,
         }
     }
 
@@ -137,6 +141,7 @@ impl CreateParams {
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
             use_spk_cache: false,
+            silent_payment_keys: None,
         }
     }
 
@@ -182,6 +187,18 @@ impl CreateParams {
         self
     }
 
+    /// Receive [BIP352] silent payments with the given `scan_key` and `spend_key`.
+    ///
+    /// The blocks given to [`Wallet::apply_block_with_prevouts`] are scanned for outputs paying the
+    /// wallet's [`Wallet::silent_payment_address`]. The private keys are not persisted, they must
+    /// be provided again with [`LoadParams::silent_payments`] when loading the wallet.
+    ///
+    /// [BIP352]: https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki
+    pub fn silent_payments(mut self, scan_key: SecretKey, spend_key: SecretKey) -> Self {
+        self.silent_payment_keys = Some((scan_key, spend_key));
+        self
+    }
+
     /// Create [`PersistedWallet`] with the given [`WalletPersister`].
     pub fn create_wallet<P>(
         self,
@@ -222,6 +239,7 @@ pub struct LoadParams {
     pub(crate) check_change_descriptor: Option<Option<DescriptorToExtract>>,
     pub(crate) extract_keys: bool,
     pub(crate) use_spk_cache: bool,
+    pub(crate) silent_payment_keys: Option<(SecretKey, SecretKey)>,
 }
 
 impl LoadParams {
@@ -239,6 +257,7 @@ impl LoadParams {
             check_change_descriptor: None,
             extract_keys: false,
             use_spk_cache: false,
+            silent_payment_keys: None,
         }
     }
 
@@ -331,6 +350,16 @@ impl LoadParams {
         self
     }
 
+    /// Use the given silent payment `scan_key` and `spend_key` to scan blocks and spend the
+    /// outputs received, see [`CreateParams::silent_payments`].
+    ///
+    /// The keys must match the ones of the loaded wallet, if any. Without them, the silent payment
+    /// outputs already found are listed but can't be spent.
+    pub fn silent_payments(mut self, scan_key: SecretKey, spend_key: SecretKey) -> Self {
+        self.silent_payment_keys = Some((scan_key, spend_key));
+        self
+    }
+
     /// Load [`PersistedWallet`] with the given [`WalletPersister`].
     pub fn load_wallet<P>(
         self,
@@ -0,0 +1,422 @@
+//! Payjoin
+//!
+//! This module implements both sides of a [BIP78] payjoin: the sender checks the proposal of the
+//! receiver with [`Wallet::process_payjoin_proposal`], according to the [`PayjoinParams`] it sent
+//! along with the original PSBT, and the receiver adds its input with
+//! [`Wallet::contribute_payjoin`].
+//!
+//! Both sides only deal with PSBTs: transporting them, for example over HTTP or through a BIP77
+//! directory, is left to the application.
+//!
+//! [`Wallet::process_payjoin_proposal`]: crate::Wallet::process_payjoin_proposal
+//! [`Wallet::contribute_payjoin`]: crate::Wallet::contribute_payjoin
+//! [BIP78]: https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki
+
+use alloc::vec::Vec;
+
+use bitcoin::{
+    psbt, Address, AddressType, Amount, FeeRate, OutPoint, Psbt, Script, TxIn, TxOut, Weight,
+};
+use rand_core::RngCore;
+
+use crate::error::{ContributePayjoinError, ProcessPayjoinProposalError};
+use crate::psbt::PsbtUtils;
+use crate::signer::{SignOptions, SignerError};
+use crate::Wallet;
+
+/// Parameters of the sender of a payjoin, sent to the receiver along with the original PSBT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayjoinParams {
+    /// Maximum amount the sender accepts to pay on top of the original transaction, the
+    /// `maxadditionalfeecontribution` parameter
+    pub max_additional_fee_contribution: Amount,
+    /// Minimum fee rate of the proposal, the `minfeerate` parameter
+    pub min_fee_rate: FeeRate,
+    /// Whether the receiver is forbidden to change its outputs, the
+    /// `disableoutputsubstitution` parameter
+    pub disable_output_substitution: bool,
+}
+
+impl Default for PayjoinParams {
+    fn default() -> Self {
+        Self {
+            max_additional_fee_contribution: Amount::ZERO,
+            min_fee_rate: FeeRate::ZERO,
+            disable_output_substitution: false,
+        }
+    }
+}
+
+impl Wallet {
+    /// Check a [BIP78] payjoin proposal from the receiver and sign the sender inputs.
+    ///
+    /// `original` is the PSBT sent to the receiver, `payee` the script of the address of the
+    /// receiver, and `params` the parameters sent along with the original PSBT. The proposal is
+    /// rejected if:
+    ///
+    /// - it changes the version or the lock time of the transaction, or the sequence of the sender
+    ///   inputs, or doesn't spend all of them;
+    /// - it spends other outputs of the wallet;
+    /// - an input added by the receiver is not finalized, lacks the output it spends, or spends a
+    ///   different script type than the sender inputs;
+    /// - it removes or lowers an output of the original transaction to a third party, or the
+    ///   output to `payee` while [`disable_output_substitution`] is set;
+    /// - the sender pays more than [`max_additional_fee_contribution`] on top of the original
+    ///   transaction, counting both its inputs and its outputs, or more than the fee of the inputs
+    ///   added by the receiver at the fee rate of the original transaction;
+    /// - its estimated fee rate is lower than [`min_fee_rate`].
+    ///
+    /// The sender inputs of the returned PSBT are restored from `original` and signed with the
+    /// wallet signers, so the PSBT is finalized if the wallet can sign all of them.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # use bdk_wallet::payjoin::PayjoinParams;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let original: Psbt = todo!();
+    /// # let proposal: Psbt = todo!();
+    /// # let payee: Address = todo!();
+    /// let params = PayjoinParams::default();
+    /// // send `original` and `params` to the receiver and get `proposal` back
+    /// let psbt = wallet.process_payjoin_proposal(
+    ///     &original,
+    ///     proposal,
+    ///     &payee.script_pubkey(),
+    ///     &params,
+    ///     SignOptions::default(),
+    /// )?;
+    /// // broadcast the payjoin transaction
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [BIP78]: https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki
+    /// [`disable_output_substitution`]: PayjoinParams::disable_output_substitution
+    /// [`max_additional_fee_contribution`]: PayjoinParams::max_additional_fee_contribution
+    /// [`min_fee_rate`]: PayjoinParams::min_fee_rate
+    pub fn process_payjoin_proposal(
+        &self,
+        original: &Psbt,
+        mut proposal: Psbt,
+        payee: &Script,
+        params: &PayjoinParams,
+        sign_options: SignOptions,
+    ) -> Result<Psbt, ProcessPayjoinProposalError> {
+        let original_tx = &original.unsigned_tx;
+        let tx = proposal.unsigned_tx.clone();
+        if tx.version != original_tx.version {
+            return Err(ProcessPayjoinProposalError::VersionChanged);
+        }
+        if tx.lock_time != original_tx.lock_time {
+            return Err(ProcessPayjoinProposalError::LockTimeChanged);
+        }
+        proposal
+            .inputs
+            .resize_with(tx.input.len(), Default::default);
+        proposal
+            .outputs
+            .resize_with(tx.output.len(), Default::default);
+
+        let original_utxos = (0..original_tx.input.len())
+            .map(|n| original.get_utxo_for(n))
+            .collect::<Option<Vec<TxOut>>>()
+            .ok_or(ProcessPayjoinProposalError::Psbt(psbt::Error::MissingUtxo))?;
+        let sender_script_type = {
+            let mut script_types = original_utxos
+                .iter()
+                .map(|txout| self.script_type(&txout.script_pubkey));
+            let first = script_types.next().flatten();
+            script_types
+                .all(|script_type| script_type == first)
+                .then_some(first)
+        };
+        for txin in &original_tx.input {
+            if !tx
+                .input
+                .iter()
+                .any(|proposal_txin| proposal_txin.previous_output == txin.previous_output)
+            {
+                return Err(ProcessPayjoinProposalError::MissingSenderInput(
+                    txin.previous_output,
+                ));
+            }
+        }
+
+        let mut receiver_input_weight = Weight::ZERO;
+        for (n, txin) in tx.input.iter().enumerate() {
+            let outpoint = txin.previous_output;
+            match original_tx
+                .input
+                .iter()
+                .position(|original_txin| original_txin.previous_output == outpoint)
+            {
+                Some(i) => {
+                    if txin.sequence != original_tx.input[i].sequence {
+                        return Err(ProcessPayjoinProposalError::SequenceChanged(outpoint));
+                    }
+                    // Restore the sender input, without the signatures of the original
+                    // transaction.
+                    let original_input = &original.inputs[i];
+                    proposal.inputs[n] = psbt::Input {
+                        non_witness_utxo: original_input.non_witness_utxo.clone(),
+                        witness_utxo: original_input.witness_utxo.clone(),
+                        sighash_type: original_input.sighash_type,
+                        ..Default::default()
+                    };
+                }
+                None => {
+                    let psbt_input = &proposal.inputs[n];
+                    if psbt_input.final_script_sig.is_none()
+                        && psbt_input.final_script_witness.is_none()
+                    {
+                        return Err(ProcessPayjoinProposalError::ReceiverInputNotFinalized(
+                            outpoint,
+                        ));
+                    }
+                    let txout = proposal
+                        .get_utxo_for(n)
+                        .ok_or(ProcessPayjoinProposalError::MissingUtxo(outpoint))?;
+                    if self.is_mine(txout.script_pubkey.clone()) {
+                        return Err(ProcessPayjoinProposalError::NewSenderInput(outpoint));
+                    }
+                    if let Some(script_type) = sender_script_type {
+                        if self.script_type(&txout.script_pubkey) != script_type {
+                            return Err(ProcessPayjoinProposalError::ScriptTypeMismatch(outpoint));
+                        }
+                    }
+                    receiver_input_weight += TxIn {
+                        script_sig: psbt_input.final_script_sig.clone().unwrap_or_default(),
+                        witness: psbt_input.final_script_witness.clone().unwrap_or_default(),
+                        ..txin.clone()
+                    }
+                    .segwit_weight();
+                }
+            }
+        }
+
+        // The outputs to third parties must be kept, the output to the receiver only if output
+        // substitution is disabled.
+        for txout in &original_tx.output {
+            if self.is_mine(txout.script_pubkey.clone()) {
+                continue;
+            }
+            let is_payee = txout.script_pubkey.as_script() == payee;
+            if is_payee && !params.disable_output_substitution {
+                continue;
+            }
+            if !tx.output.iter().any(|proposal_txout| {
+                proposal_txout.script_pubkey == txout.script_pubkey
+                    && proposal_txout.value >= txout.value
+            }) {
+                return Err(match is_payee {
+                    true => {
+                        ProcessPayjoinProposalError::PayeeOutputChanged(txout.script_pubkey.clone())
+                    }
+                    false => {
+                        ProcessPayjoinProposalError::OutputChanged(txout.script_pubkey.clone())
+                    }
+                });
+            }
+        }
+
+        // What the sender spends, net of the outputs it gets back.
+        let sender_received = |outputs: &[TxOut]| -> Amount {
+            outputs
+                .iter()
+                .filter(|txout| self.is_mine(txout.script_pubkey.clone()))
+                .map(|txout| txout.value)
+                .sum()
+        };
+        let sender_spent: Amount = original_utxos.iter().map(|txout| txout.value).sum();
+        let original_cost = sender_spent
+            .checked_sub(sender_received(&original_tx.output))
+            .unwrap_or_default();
+        let proposal_cost = sender_spent
+            .checked_sub(sender_received(&tx.output))
+            .unwrap_or_default();
+        let contribution = proposal_cost.checked_sub(original_cost).unwrap_or_default();
+        if contribution > params.max_additional_fee_contribution {
+            return Err(ProcessPayjoinProposalError::FeeContributionTooHigh {
+                contribution,
+                max: params.max_additional_fee_contribution,
+            });
+        }
+        // The sender only contributes to the fee of the inputs of the receiver.
+        let original_fee = original.fee().map_err(ProcessPayjoinProposalError::Psbt)?;
+        let original_fee_rate = original_fee / self.estimate_psbt_weight(original);
+        let input_fee = original_fee_rate * receiver_input_weight;
+        if contribution > input_fee {
+            return Err(ProcessPayjoinProposalError::FeeContributionAboveInputFee {
+                contribution,
+                input_fee,
+            });
+        }
+
+        let fee = proposal.fee().map_err(ProcessPayjoinProposalError::Psbt)?;
+        let fee_rate = fee / self.estimate_psbt_weight(&proposal);
+        if fee_rate < params.min_fee_rate {
+            return Err(ProcessPayjoinProposalError::FeeRateTooLow {
+                fee_rate,
+                min: params.min_fee_rate,
+            });
+        }
+
+        self.sign(&mut proposal, sign_options)
+            .map_err(ProcessPayjoinProposalError::Signer)?;
+        Ok(proposal)
+    }
+
+    /// Contribute a wallet UTXO to a [BIP78] payjoin, as the receiver.
+    ///
+    /// Uses the thread-local random number generator (rng).
+    ///
+    /// `original` must be the finalized PSBT of the sender, paying the wallet. The returned
+    /// proposal spends `utxo`, or if `None` a confirmed unlocked UTXO of the same script type as
+    /// the sender inputs (of any type if they don't all share one), and adds its value to the
+    /// first output paying the wallet, minus the fee for the new input at the fee rate of the
+    /// original transaction. The new input is signed and finalized, while the sender inputs are
+    /// left for the sender to sign again.
+    ///
+    /// The original PSBT should be kept, to broadcast it if the sender doesn't complete the
+    /// payjoin. The contributed UTXO should be locked with [`lock_outpoint`] until then.
+    ///
+    /// [BIP78]: https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki
+    /// [`lock_outpoint`]: Self::lock_outpoint
+    #[cfg(feature = "std")]
+    pub fn contribute_payjoin(
+        &self,
+        original: &Psbt,
+        utxo: Option<OutPoint>,
+        sign_options: SignOptions,
+    ) -> Result<Psbt, ContributePayjoinError> {
+        self.contribute_payjoin_with_aux_rand(
+            original,
+            utxo,
+            sign_options,
+            &mut bitcoin::key::rand::thread_rng(),
+        )
+    }
+
+    /// Contribute a wallet UTXO to a [BIP78] payjoin, as the receiver.
+    ///
+    /// Uses a provided random number generator (rng) to choose the position of the new input.
+    /// See [`contribute_payjoin`] for details.
+    ///
+    /// [BIP78]: https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki
+    /// [`contribute_payjoin`]: Self::contribute_payjoin
+    pub fn contribute_payjoin_with_aux_rand(
+        &self,
+        original: &Psbt,
+        utxo: Option<OutPoint>,
+        sign_options: SignOptions,
+        rng: &mut impl RngCore,
+    ) -> Result<Psbt, ContributePayjoinError> {
+        let original_tx = &original.unsigned_tx;
+        if original_tx.input.is_empty() {
+            return Err(ContributePayjoinError::NoSenderInput);
+        }
+        if original
+            .inputs
+            .iter()
+            .any(|input| input.final_script_sig.is_none() && input.final_script_witness.is_none())
+        {
+            return Err(ContributePayjoinError::OriginalNotFinalized);
+        }
+        let mut sender_script_types = Vec::with_capacity(original_tx.input.len());
+        for (n, txin) in original_tx.input.iter().enumerate() {
+            let txout = original
+                .get_utxo_for(n)
+                .ok_or(ContributePayjoinError::Psbt(psbt::Error::MissingUtxo))?;
+            if self.is_mine(txout.script_pubkey.clone()) {
+                return Err(ContributePayjoinError::OwnedInput(txin.previous_output));
+            }
+            sender_script_types.push(self.script_type(&txout.script_pubkey));
+        }
+        // Match the script type of the sender inputs only if they all share the same one.
+        let sender_script_type = sender_script_types
+            .first()
+            .copied()
+            .flatten()
+            .filter(|first| {
+                sender_script_types
+                    .iter()
+                    .all(|script_type| *script_type == Some(*first))
+            });
+        let receiver_vout = original_tx
+            .output
+            .iter()
+            .position(|txout| self.is_mine(txout.script_pubkey.clone()))
+            .ok_or(ContributePayjoinError::NoReceiverOutput)?;
+
+        let utxo = match utxo {
+            Some(outpoint) => self
+                .get_utxo(outpoint)
+                .ok_or(ContributePayjoinError::UnknownUtxo(outpoint))?,
+            None => self
+                .list_unspent()
+                .filter(|utxo| utxo.chain_position.is_confirmed())
+                .filter(|utxo| !self.is_outpoint_locked(utxo.outpoint))
+                .find(|utxo| {
+                    sender_script_type.is_none_or(|script_type| {
+                        self.script_type(&utxo.txout.script_pubkey) == Some(script_type)
+                    })
+                })
+                .ok_or(ContributePayjoinError::NoUtxo)?,
+        };
+
+        // The receiver pays for its input at the fee rate of the original transaction.
+        let fee = original.fee().map_err(ContributePayjoinError::Psbt)?;
+        let fee_rate = fee / original.clone().extract_tx_unchecked_fee_rate().weight();
+        let satisfaction_weight = self.satisfaction_weight(&utxo);
+        let input_fee = fee_rate * (TxIn::default().segwit_weight() + satisfaction_weight);
+        let contribution = utxo
+            .txout
+            .value
+            .checked_sub(input_fee)
+            .ok_or(ContributePayjoinError::NoUtxo)?;
+
+        let outpoint = utxo.outpoint;
+        let psbt_input = self
+            .get_psbt_input(utxo, None, false)
+            .map_err(|_| ContributePayjoinError::UnknownUtxo(outpoint))?;
+        let mut proposal = original.clone();
+        let index = rng.next_u32() as usize % (original_tx.input.len() + 1);
+        proposal.unsigned_tx.input.insert(
+            index,
+            TxIn {
+                previous_output: outpoint,
+                sequence: original_tx.input[0].sequence,
+                ..Default::default()
+            },
+        );
+        proposal.inputs.insert(index, psbt_input);
+        proposal.unsigned_tx.output[receiver_vout].value += contribution;
+        for output in &mut proposal.outputs {
+            output.bip32_derivation.clear();
+            output.tap_key_origins.clear();
+        }
+
+        let finalized = self
+            .sign(&mut proposal, sign_options)
+            .map_err(ContributePayjoinError::Signer)?;
+        if !finalized {
+            return Err(ContributePayjoinError::Signer(SignerError::MissingKey));
+        }
+        // The sender signs its inputs again.
+        for (n, input) in proposal.inputs.iter_mut().enumerate() {
+            if n != index {
+                *input = psbt::Input::default();
+            }
+        }
+        Ok(proposal)
+    }
+
+    /// The address type of `script`, used to compare the script types of payjoin inputs.
+    fn script_type(&self, script: &Script) -> Option<AddressType> {
+        Address::from_script(script, self.network)
+            .ok()?
+            .address_type()
+    }
+}
@@ -0,0 +1,300 @@
+//! Batched payouts
+//!
+//! This module contains [`PayoutBatcher`], a queue of payout requests that are paid together in
+//! as few transactions as possible.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::mem;
+
+use bitcoin::policy::MAX_STANDARD_TX_WEIGHT;
+use bitcoin::{Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Weight};
+use rand_core::RngCore;
+
+use crate::collections::HashSet;
+use crate::error::CreateTxError;
+use crate::{TxPlan, Wallet};
+
+/// Default maximum number of payouts paid by a single transaction.
+pub const DEFAULT_MAX_OUTPUTS: usize = 100;
+
+/// A payment waiting in a [`PayoutBatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutRequest<I> {
+    /// Identifier of the request, chosen by the caller
+    pub id: I,
+    /// Script to pay
+    pub script_pubkey: ScriptBuf,
+    /// Amount to pay
+    pub amount: Amount,
+}
+
+/// A transaction paying a batch of [`PayoutRequest`]s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayoutBatch<I> {
+    /// The unsigned transaction
+    pub psbt: Psbt,
+    /// Identifiers of the requests paid by the transaction, in the order they were queued
+    pub ids: Vec<I>,
+}
+
+/// Result of [`PayoutBatcher::build`].
+#[derive(Debug)]
+pub struct PayoutReport<I> {
+    /// Transactions built, each paying a different batch of requests
+    pub batches: Vec<PayoutBatch<I>>,
+    /// Identifiers of the requests that couldn't be paid, either because the wallet doesn't have
+    /// enough funds or because their transaction alone would be heavier than the maximum weight.
+    /// These requests are left in the queue.
+    pub deferred: Vec<I>,
+    /// Error that stopped the batching, if any. The requests not paid yet are left in the queue.
+    pub error: Option<CreateTxError>,
+}
+
+/// A queue of payouts paid in batched transactions.
+///
+/// Requests are added with [`add`] and paid, in the order they were added, by [`build`]. Each
+/// transaction pays at most [`max_outputs`] requests and weighs at most [`max_weight`]: a batch
+/// exceeding the maximum weight is split in two. If the wallet can't fund a batch, the last
+/// requests of the batch are deferred until the rest can be paid.
+///
+/// The transactions are built with [`Wallet::build_tx`] and [`TxBuilder::set_recipients`] and
+/// never spend the same UTXO twice. As with [`TxBuilder::finish`], the changes staged in the
+/// wallet must be persisted to avoid change address reuse.
+///
+/// ## Example
+///
+/// ```no_run
+/// # use bitcoin::*;
+/// # use bdk_wallet::*;
+/// # use bdk_wallet::payout::PayoutBatcher;
+/// # let mut wallet = doctest_wallet!();
+/// # let alice = ScriptBuf::new();
+/// # let bob = ScriptBuf::new();
+/// let mut batcher = PayoutBatcher::new();
+/// batcher
+///     .fee_rate(FeeRate::from_sat_per_vb_u32(5))
+///     .add("withdrawal-1", alice, Amount::from_sat(50_000))
+///     .add("withdrawal-2", bob, Amount::from_sat(20_000));
+/// let report = batcher.build(&mut wallet);
+/// for batch in report.batches {
+///     println!("{:?} paid by {}", batch.ids, batch.psbt.unsigned_tx.compute_txid());
+/// }
+/// ```
+///
+/// [`add`]: Self::add
+/// [`build`]: Self::build
+/// [`max_outputs`]: Self::max_outputs
+/// [`max_weight`]: Self::max_weight
+/// [`TxBuilder::set_recipients`]: crate::TxBuilder::set_recipients
+/// [`TxBuilder::finish`]: crate::TxBuilder::finish
+#[derive(Debug, Clone)]
+pub struct PayoutBatcher<I> {
+    pending: Vec<PayoutRequest<I>>,
+    max_outputs: usize,
+    max_weight: Weight,
+    fee_rate: Option<FeeRate>,
+}
+
+impl<I> Default for PayoutBatcher<I> {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            max_outputs: DEFAULT_MAX_OUTPUTS,
+            max_weight: Weight::from_wu(MAX_STANDARD_TX_WEIGHT as u64),
+            fee_rate: None,
+        }
+    }
+}
+
+impl<I: Clone> PayoutBatcher<I> {
+    /// Create an empty batcher, with [`DEFAULT_MAX_OUTPUTS`] and the maximum standard
+    /// transaction weight as limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of requests paid by a single transaction.
+    ///
+    /// The change output, if any, is not counted. A value of `0` is treated as `1`.
+    pub fn max_outputs(&mut self, max_outputs: usize) -> &mut Self {
+        self.max_outputs = max_outputs.max(1);
+        self
+    }
+
+    /// Set the maximum estimated weight of a transaction once signed.
+    pub fn max_weight(&mut self, max_weight: Weight) -> &mut Self {
+        self.max_weight = max_weight;
+        self
+    }
+
+    /// Set the fee rate of the transactions, see [`TxBuilder::fee_rate`].
+    ///
+    /// [`TxBuilder::fee_rate`]: crate::TxBuilder::fee_rate
+    pub fn fee_rate(&mut self, fee_rate: FeeRate) -> &mut Self {
+        self.fee_rate = Some(fee_rate);
+        self
+    }
+
+    /// Queue a payment of `amount` to `script_pubkey`, identified by `id`.
+    pub fn add(&mut self, id: I, script_pubkey: ScriptBuf, amount: Amount) -> &mut Self {
+        self.pending.push(PayoutRequest {
+            id,
+            script_pubkey,
+            amount,
+        });
+        self
+    }
+
+    /// The requests waiting to be paid, in the order they were added.
+    pub fn pending(&self) -> &[PayoutRequest<I>] {
+        &self.pending
+    }
+
+    /// Build the transactions paying the pending requests.
+    ///
+    /// Uses the thread-local random number generator (rng).
+    ///
+    /// The requests paid are removed from the queue. See [`PayoutReport`] for the requests that
+    /// are left.
+    #[cfg(feature = "std")]
+    pub fn build(&mut self, wallet: &mut Wallet) -> PayoutReport<I> {
+        self.build_with_aux_rand(wallet, &mut bitcoin::key::rand::thread_rng())
+    }
+
+    /// Build the transactions paying the pending requests.
+    ///
+    /// Uses a provided random number generator (rng). See [`build`] for details.
+    ///
+    /// [`build`]: Self::build
+    pub fn build_with_aux_rand(
+        &mut self,
+        wallet: &mut Wallet,
+        rng: &mut impl RngCore,
+    ) -> PayoutReport<I> {
+        // Requests are tagged with their position in the queue to keep the order of the ones left.
+        let mut queue: VecDeque<(usize, PayoutRequest<I>)> = mem::take(&mut self.pending)
+            .into_iter()
+            .enumerate()
+            .collect();
+        let mut deferred = Vec::new();
+        let mut report = PayoutReport {
+            batches: Vec::new(),
+            deferred: Vec::new(),
+            error: None,
+        };
+        // UTXOs spent by the transactions already built.
+        let mut spent = HashSet::<OutPoint>::new();
+
+        while !queue.is_empty() {
+            let len = self.max_outputs.min(queue.len());
+            let mut batch: Vec<(usize, PayoutRequest<I>)> = queue.drain(..len).collect();
+
+            let result = loop {
+                match self.plan(wallet, &batch, &spent, rng) {
+                    Ok(plan) if plan.weight > self.max_weight => {
+                        if batch.len() == 1 {
+                            deferred.append(&mut batch);
+                            break Ok(None);
+                        }
+                        // Pay the second half in another transaction.
+                        let second_half = batch.split_off(batch.len() / 2);
+                        for request in second_half.into_iter().rev() {
+                            queue.push_front(request);
+                        }
+                    }
+                    Ok(plan) => break Ok(Some(plan)),
+                    Err(CreateTxError::CoinSelection(_)) => {
+                        deferred.extend(batch.pop());
+                        if batch.is_empty() {
+                            break Ok(None);
+                        }
+                    }
+                    Err(e) => break Err(e),
+                }
+            };
+
+            let result = result.and_then(|plan| match plan {
+                Some(plan) => self.finish(wallet, &batch, &plan, rng).map(Some),
+                None => Ok(None),
+            });
+            match result {
+                Ok(Some(psbt)) => {
+                    spent.extend(
+                        psbt.unsigned_tx
+                            .input
+                            .iter()
+                            .map(|txin| txin.previous_output),
+                    );
+                    report.batches.push(PayoutBatch {
+                        psbt,
+                        ids: batch.into_iter().map(|(_, request)| request.id).collect(),
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    report.error = Some(e);
+                    deferred.extend(batch);
+                    deferred.extend(queue);
+                    break;
+                }
+            }
+        }
+
+        deferred.sort_by_key(|(position, _)| *position);
+        self.pending = deferred.into_iter().map(|(_, request)| request).collect();
+        report.deferred = self
+            .pending
+            .iter()
+            .map(|request| request.id.clone())
+            .collect();
+        report
+    }
+
+    /// Plan a transaction paying `batch` without spending any of the `spent` UTXOs.
+    fn plan(
+        &self,
+        wallet: &mut Wallet,
+        batch: &[(usize, PayoutRequest<I>)],
+        spent: &HashSet<OutPoint>,
+        rng: &mut impl RngCore,
+    ) -> Result<TxPlan, CreateTxError> {
+        let mut builder = wallet.build_tx();
+        builder.set_recipients(recipients(batch));
+        if let Some(fee_rate) = self.fee_rate {
+            builder.fee_rate(fee_rate);
+        }
+        for outpoint in spent {
+            builder.add_unspendable(*outpoint);
+        }
+        builder.plan_with_aux_rand(rng)
+    }
+
+    /// Create the transaction paying `batch` with the UTXOs selected by `plan`.
+    fn finish(
+        &self,
+        wallet: &mut Wallet,
+        batch: &[(usize, PayoutRequest<I>)],
+        plan: &TxPlan,
+        rng: &mut impl RngCore,
+    ) -> Result<Psbt, CreateTxError> {
+        let outpoints: Vec<OutPoint> = plan.utxos.iter().map(|utxo| utxo.outpoint()).collect();
+        let mut builder = wallet.build_tx();
+        builder
+            .set_recipients(recipients(batch))
+            .add_utxos(&outpoints)
+            .expect("planned UTXOs must be in the wallet")
+            .manually_selected_only();
+        if let Some(fee_rate) = self.fee_rate {
+            builder.fee_rate(fee_rate);
+        }
+        builder.finish_with_aux_rand(rng)
+    }
+}
+
+fn recipients<I>(batch: &[(usize, PayoutRequest<I>)]) -> Vec<(ScriptBuf, Amount)> {
+    batch
+        .iter()
+        .map(|(_, request)| (request.script_pubkey.clone(), request.amount))
+        .collect()
+}
@@ -0,0 +1,111 @@
+//! Replacement checks
+//!
+//! This module contains the report returned by [`Wallet::check_replacement`], which verifies a
+//! candidate replacement transaction against the [BIP125] rules before it is broadcast.
+//!
+//! [`Wallet::check_replacement`]: crate::Wallet::check_replacement
+//! [BIP125]: https://github.com/bitcoin/bips/blob/master/bip-0125.mediawiki
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use bitcoin::{Amount, FeeRate, OutPoint, Txid, Weight};
+
+/// Maximum number of transactions a replacement is allowed to evict from the mempool (BIP125
+/// rule 5).
+pub const MAX_REPLACEMENT_EVICTIONS: usize = 100;
+
+/// Fee rate used as the incremental relay fee when checking BIP125 rule 4.
+pub const INCREMENTAL_RELAY_FEE: FeeRate = FeeRate::BROADCAST_MIN;
+
+/// A BIP125 rule broken by a replacement transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplacementViolation {
+    /// Rule 1: the original transaction doesn't signal replaceability, neither explicitly nor
+    /// through one of its unconfirmed ancestors.
+    NotSignaling(Txid),
+    /// Rule 2: the replacement spends an unconfirmed output of a transaction none of the original
+    /// transactions spends from.
+    NewUnconfirmedInput(OutPoint),
+    /// Rule 3: the replacement pays a lower absolute fee than the transactions it evicts.
+    AbsoluteFeeTooLow {
+        /// Fee paid by the replacement
+        fee: Amount,
+        /// Sum of the fees paid by the evicted transactions
+        required: Amount,
+    },
+    /// Rule 4: the replacement doesn't pay for its own bandwidth at the incremental relay fee.
+    IncrementalFeeTooLow {
+        /// Fee paid by the replacement on top of the fees of the evicted transactions
+        additional: Amount,
+        /// Additional fee required by the incremental relay fee
+        required: Amount,
+    },
+    /// Rule 5: the replacement would evict too many transactions from the mempool.
+    TooManyEvictions {
+        /// Number of transactions evicted by the replacement
+        count: usize,
+        /// Maximum number of transactions that can be evicted
+        max: usize,
+    },
+}
+
+impl fmt::Display for ReplacementViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSignaling(txid) => {
+                write!(f, "Transaction doesn't signal replaceability: {txid}")
+            }
+            Self::NewUnconfirmedInput(outpoint) => {
+                write!(f, "Replacement spends a new unconfirmed output: {outpoint}")
+            }
+            Self::AbsoluteFeeTooLow { fee, required } => write!(
+                f,
+                "Replacement fee too low: {} sat, required {} sat",
+                fee.to_sat(),
+                required.to_sat()
+            ),
+            Self::IncrementalFeeTooLow {
+                additional,
+                required,
+            } => write!(
+                f,
+                "Replacement doesn't pay the incremental relay fee: {} sat, required {} sat",
+                additional.to_sat(),
+                required.to_sat()
+            ),
+            Self::TooManyEvictions { count, max } => write!(
+                f,
+                "Replacement evicts too many transactions: {count}, max {max}"
+            ),
+        }
+    }
+}
+
+/// Report of a BIP125 check of a replacement transaction, returned by
+/// [`Wallet::check_replacement`].
+///
+/// [`Wallet::check_replacement`]: crate::Wallet::check_replacement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplacementReport {
+    /// Unconfirmed transactions directly conflicting with the replacement
+    pub conflicts: Vec<Txid>,
+    /// All the transactions evicted by the replacement: the direct conflicts and their
+    /// unconfirmed descendants
+    pub evicted: Vec<Txid>,
+    /// Fee paid by the replacement
+    pub fee: Amount,
+    /// Weight of the replacement, including the estimated weight of the missing signatures
+    pub weight: Weight,
+    /// Sum of the fees paid by the evicted transactions
+    pub evicted_fee: Amount,
+    /// Rules broken by the replacement, empty if it would be accepted
+    pub violations: Vec<ReplacementViolation>,
+}
+
+impl ReplacementReport {
+    /// Whether the replacement satisfies all the BIP125 rules.
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
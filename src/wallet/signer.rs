@@ -841,7 +841,7 @@ impl Default for SignOptions {
 }
 
 /// Computes the taproot sighash.
-fn compute_tap_sighash(
+pub(crate) fn compute_tap_sighash(
     psbt: &Psbt,
     input_index: usize,
     extra: Option<taproot::TapLeafHash>,
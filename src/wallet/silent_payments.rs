@@ -0,0 +1,744 @@
+//! Silent payments
+//!
+//! This module contains [`SilentPaymentAddress`], the static address of a [BIP352] silent payment
+//! recipient. Payments to it are added to a transaction with
+//! [`TxBuilder::add_silent_payment_recipient`]: the output key is derived from the private keys
+//! of the inputs, once they are selected, so that only the recipient can find the output.
+//!
+//! The silent payment outputs of a PSBT created by the wallet are marked with the
+//! `PSBT_OUT_SP_V0_INFO` field of [BIP375], so that external signers can check them. The wallet
+//! also records the address paid by each of them, so that a fee bump derives the output again
+//! from its new inputs.
+//!
+//! A wallet created with [`CreateParams::silent_payments`] also receives silent payments: the
+//! blocks given to [`Wallet::apply_block_with_prevouts`], along with the outputs they spend, are
+//! scanned for outputs paying its
+//! [silent payment addresses](crate::Wallet::silent_payment_address). The outputs found are
+//! recorded in the [`ChangeSet`] with the tweak of their key, so that the wallet can list and spend
+//! them.
+//!
+//! [BIP352]: https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki
+//! [BIP375]: https://github.com/bitcoin/bips/blob/master/bip-0375.mediawiki
+//! [`TxBuilder::add_silent_payment_recipient`]: crate::TxBuilder::add_silent_payment_recipient
+//! [`CreateParams::silent_payments`]: crate::CreateParams::silent_payments
+//! [`Wallet::apply_block_with_prevouts`]: crate::Wallet::apply_block_with_prevouts
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use bdk_chain::Merge;
+use bitcoin::bech32::primitives::decode::{CheckedHrpstring, CheckedHrpstringError};
+use bitcoin::bech32::primitives::iter::{ByteIterExt, Fe32IterExt};
+use bitcoin::bech32::{Bech32m, Fe32, Hrp};
+use bitcoin::bip32::ChildNumber;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::{hash160, sha256, Hash, HashEngine};
+use bitcoin::key::{Secp256k1, TapTweak, TweakedPublicKey, XOnlyPublicKey};
+use bitcoin::psbt::raw;
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::Message;
+use bitcoin::secp256k1::{self, Keypair, PublicKey, Scalar, SecretKey, Signing, Verification};
+use bitcoin::{
+    taproot, NetworkKind, OutPoint, PrivateKey, Psbt, ScriptBuf, TapNodeHash, Transaction, TxIn,
+    TxOut, Weight,
+};
+use miniscript::descriptor::{Descriptor, DescriptorSecretKey, ShInner, Wildcard};
+use miniscript::ToPublicKey;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::collections::{BTreeMap, BTreeSet};
+use crate::error::CreateTxError;
+use crate::signer::{self, SignerError};
+use crate::types::{IndexOutOfBoundsError, Utxo};
+use crate::{KeychainKind, Wallet};
+
+/// Type of the `PSBT_OUT_SP_V0_INFO` output field of [BIP375], the scan and spend keys of the
+/// silent payment address paid by the output.
+///
+/// [BIP375]: https://github.com/bitcoin/bips/blob/master/bip-0375.mediawiki
+pub const PSBT_OUT_SP_V0_INFO: u8 = 0x09;
+
+/// The x-only key `H` of [BIP341], with no known discrete logarithm. Taproot inputs using it as
+/// internal key can't be spent through the key path and are not used by silent payments.
+///
+/// [BIP341]: https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Maximum weight of the satisfaction of a received silent payment output, spent through the
+/// taproot key path: the length of the signature, the signature and its sighash type.
+pub(crate) const SATISFACTION_WEIGHT: Weight = Weight::from_wu(1 + 65);
+
+const MAINNET_HRP: &str = "sp";
+const TESTNET_HRP: &str = "tsp";
+
+/// A [BIP352] silent payment address.
+///
+/// [BIP352]: https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SilentPaymentAddress {
+    /// Key used by the recipient to scan transactions
+    pub scan: PublicKey,
+    /// Key used by the recipient to spend the outputs it receives, already tweaked if the
+    /// address is labeled
+    pub spend: PublicKey,
+    /// Whether the address is used on mainnet or on a test network
+    pub network: NetworkKind,
+}
+
+impl SilentPaymentAddress {
+    /// Create an address from its scan and spend keys.
+    pub fn new(scan: PublicKey, spend: PublicKey, network: NetworkKind) -> Self {
+        Self {
+            scan,
+            spend,
+            network,
+        }
+    }
+
+    /// The value of the `PSBT_OUT_SP_V0_INFO` field of the outputs paying this address.
+    pub fn psbt_output_info(&self) -> Vec<u8> {
+        let mut info = self.scan.serialize().to_vec();
+        info.extend(self.spend.serialize());
+        info
+    }
+
+    /// Parse the value of a `PSBT_OUT_SP_V0_INFO` field.
+    pub fn from_psbt_output_info(
+        info: &[u8],
+        network: NetworkKind,
+    ) -> Result<Self, SilentPaymentAddressError> {
+        if info.len() != 66 {
+            return Err(SilentPaymentAddressError::InvalidLength(info.len()));
+        }
+        Ok(Self {
+            scan: PublicKey::from_slice(&info[..33])?,
+            spend: PublicKey::from_slice(&info[33..])?,
+            network,
+        })
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = match self.network {
+            NetworkKind::Main => MAINNET_HRP,
+            NetworkKind::Test => TESTNET_HRP,
+        };
+        let hrp = Hrp::parse_unchecked(hrp);
+        for c in self
+            .psbt_output_info()
+            .into_iter()
+            .bytes_to_fes()
+            .with_checksum::<Bech32m>(&hrp)
+            .with_witness_version(Fe32::Q)
+            .chars()
+        {
+            fmt::Write::write_char(f, c)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = SilentPaymentAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut checked = CheckedHrpstring::new::<Bech32m>(s)?;
+        let network = match checked.hrp().to_lowercase().as_str() {
+            MAINNET_HRP => NetworkKind::Main,
+            TESTNET_HRP => NetworkKind::Test,
+            _ => {
+                return Err(SilentPaymentAddressError::InvalidHrp(
+                    checked.hrp().to_string(),
+                ))
+            }
+        };
+        let version = checked
+            .remove_witness_version()
+            .ok_or(SilentPaymentAddressError::InvalidLength(0))?
+            .to_u8();
+        let data: Vec<u8> = checked.byte_iter().collect();
+        match version {
+            // Later versions are backward compatible: they can only append data.
+            0 if data.len() == 66 => Self::from_psbt_output_info(&data, network),
+            1..=30 if data.len() >= 66 => Self::from_psbt_output_info(&data[..66], network),
+            0..=30 => Err(SilentPaymentAddressError::InvalidLength(data.len())),
+            _ => Err(SilentPaymentAddressError::UnsupportedVersion(version)),
+        }
+    }
+}
+
+impl Serialize for SilentPaymentAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SilentPaymentAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Error returned when parsing a [`SilentPaymentAddress`].
+#[derive(Debug)]
+pub enum SilentPaymentAddressError {
+    /// The address is not valid bech32m
+    Bech32(CheckedHrpstringError),
+    /// The human readable part is neither `sp` nor `tsp`
+    InvalidHrp(String),
+    /// The address version is not supported
+    UnsupportedVersion(u8),
+    /// The address doesn't contain two public keys
+    InvalidLength(usize),
+    /// One of the public keys is invalid
+    InvalidKey(secp256k1::Error),
+}
+
+impl fmt::Display for SilentPaymentAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bech32(err) => write!(f, "Invalid silent payment address encoding: {err}"),
+            Self::InvalidHrp(hrp) => {
+                write!(
+                    f,
+                    "Invalid silent payment address human readable part: {hrp}"
+                )
+            }
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported silent payment address version: {version}")
+            }
+            Self::InvalidLength(len) => {
+                write!(f, "Invalid silent payment address data length: {len}")
+            }
+            Self::InvalidKey(err) => write!(f, "Invalid silent payment address key: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for SilentPaymentAddressError {}
+
+impl From<CheckedHrpstringError> for SilentPaymentAddressError {
+    fn from(err: CheckedHrpstringError) -> Self {
+        Self::Bech32(err)
+    }
+}
+
+impl From<secp256k1::Error> for SilentPaymentAddressError {
+    fn from(err: secp256k1::Error) -> Self {
+        Self::InvalidKey(err)
+    }
+}
+
+/// A silent payment output received by the wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SilentPaymentOutput {
+    /// Tweak added to the spend key of the wallet to get the private key of the output
+    pub tweak: SecretKey,
+    /// Label of the address paid by the output, if any
+    pub label: Option<u32>,
+}
+
+/// Represents changes to the silent payments received by a wallet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    /// Scan and spend public keys of the wallet.
+    pub keys: Option<(PublicKey, PublicKey)>,
+    /// Labels of the addresses given out by the wallet.
+    pub labels: BTreeSet<u32>,
+    /// Outputs received by the wallet.
+    pub outputs: BTreeMap<OutPoint, SilentPaymentOutput>,
+    /// Addresses paid by the transactions created by the wallet, by the script of their output.
+    #[serde(default)]
+    pub sent: BTreeMap<ScriptBuf, SilentPaymentAddress>,
+}
+
+impl Merge for ChangeSet {
+    fn merge(&mut self, other: Self) {
+        if other.keys.is_some() {
+            debug_assert!(
+                self.keys.is_none() || self.keys == other.keys,
+                "silent payment keys must never change"
+            );
+            self.keys = other.keys;
+        }
+        self.labels.extend(other.labels);
+        self.outputs.extend(other.outputs);
+        self.sent.extend(other.sent);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.keys.is_none()
+            && self.labels.is_empty()
+            && self.outputs.is_empty()
+            && self.sent.is_empty()
+    }
+}
+
+/// The `PSBT_OUT_SP_V0_INFO` field of an output paying `address`.
+pub(crate) fn psbt_output_field(address: &SilentPaymentAddress) -> (raw::Key, Vec<u8>) {
+    (
+        raw::Key {
+            type_value: PSBT_OUT_SP_V0_INFO,
+            key: Vec::new(),
+        },
+        address.psbt_output_info(),
+    )
+}
+
+/// Whether `internal_key` is the unspendable key `H` of BIP341.
+pub(crate) fn is_nums(internal_key: &XOnlyPublicKey) -> bool {
+    internal_key.serialize() == NUMS_H
+}
+
+/// The private key a taproot input contributes to silent payments: the tweaked output key,
+/// negated if its y-coordinate is odd.
+pub(crate) fn taproot_input_key<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    internal_key: &SecretKey,
+    merkle_root: Option<TapNodeHash>,
+) -> SecretKey {
+    let keypair = Keypair::from_secret_key(secp, internal_key)
+        .tap_tweak(secp, merkle_root)
+        .to_keypair();
+    even_y_key(secp, &keypair.secret_key())
+}
+
+/// `key`, negated if the y-coordinate of its public key is odd.
+pub(crate) fn even_y_key<C: Signing>(secp: &Secp256k1<C>, key: &SecretKey) -> SecretKey {
+    match key.x_only_public_key(secp).1 {
+        secp256k1::Parity::Even => *key,
+        secp256k1::Parity::Odd => key.negate(),
+    }
+}
+
+/// The private key of a received `output`, the tweaked `spend_key`.
+pub(crate) fn output_key(spend_key: &SecretKey, output: &SilentPaymentOutput) -> Option<SecretKey> {
+    spend_key.add_tweak(&Scalar::from(output.tweak)).ok()
+}
+
+/// `hash_BIP0352/Label(ser256(b_scan) || ser32(m))`, the tweak of the spend key for the addresses
+/// with label `m`.
+pub(crate) fn label_tweak(scan_key: &SecretKey, m: u32) -> Option<SecretKey> {
+    let hash = tagged_hash(
+        "BIP0352/Label",
+        &[&scan_key.secret_bytes(), &m.to_be_bytes()],
+    );
+    SecretKey::from_slice(&hash).ok()
+}
+
+/// The public key `txin` contributes to the silent payment outputs of its transaction, or `None`
+/// if the input is not eligible. `prevout` is the output spent by `txin`.
+pub(crate) fn input_public_key(txin: &TxIn, prevout: &TxOut) -> Option<PublicKey> {
+    let script_pubkey = &prevout.script_pubkey;
+    // Only compressed keys are eligible.
+    let witness_key = || {
+        let key = txin.witness.last()?;
+        (key.len() == 33).then(|| PublicKey::from_slice(key).ok())?
+    };
+    if script_pubkey.is_p2pkh() {
+        let pubkey_hash = &script_pubkey.as_bytes()[3..23];
+        txin.script_sig
+            .instructions()
+            .filter_map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(bytes)) if bytes.len() == 33 => Some(bytes.as_bytes()),
+                _ => None,
+            })
+            .find(|key| hash160::Hash::hash(key).as_byte_array() == pubkey_hash)
+            .and_then(|key| PublicKey::from_slice(key).ok())
+    } else if script_pubkey.is_p2sh() {
+        txin.script_sig
+            .redeem_script()
+            .is_some_and(|redeem_script| redeem_script.is_p2wpkh())
+            .then(witness_key)?
+    } else if script_pubkey.is_p2wpkh() {
+        witness_key()
+    } else if script_pubkey.is_p2tr() {
+        let mut witness: Vec<&[u8]> = txin.witness.iter().collect();
+        // Drop the annex.
+        if witness.len() > 1 && witness.last()?.first() == Some(&0x50) {
+            witness.pop();
+        }
+        // Script path spends with the unspendable internal key are not eligible.
+        if witness.len() > 1 {
+            let control_block = witness.last()?;
+            let internal_key = XOnlyPublicKey::from_slice(control_block.get(1..33)?).ok()?;
+            if is_nums(&internal_key) {
+                return None;
+            }
+        }
+        let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..]).ok()?;
+        Some(output_key.public_key(secp256k1::Parity::Even))
+    } else {
+        None
+    }
+}
+
+/// Find the outputs of `tx` paying the receiver with `scan_key` and `spend_key`, with or without
+/// one of `labels`, given as label, tweak and tweak times `G`. `prevouts` are the outputs spent by
+/// the inputs of `tx`, in the same order.
+///
+/// Returns the index of the outputs found, or `None` if the transaction can't contain silent
+/// payments.
+pub(crate) fn scan_transaction<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    scan_key: &SecretKey,
+    spend_key: &PublicKey,
+    labels: &[(u32, SecretKey, PublicKey)],
+    tx: &Transaction,
+    prevouts: &[TxOut],
+) -> Option<Vec<(u32, SilentPaymentOutput)>> {
+    // Transactions spending outputs of unknown segwit versions are skipped.
+    if prevouts.iter().any(|prevout| {
+        prevout
+            .script_pubkey
+            .witness_version()
+            .is_some_and(|version| version.to_num() > 1)
+    }) {
+        return None;
+    }
+    let mut outputs: Vec<(u32, XOnlyPublicKey)> = (0..)
+        .zip(&tx.output)
+        .filter(|(_, txout)| txout.script_pubkey.is_p2tr())
+        .filter_map(|(vout, txout)| {
+            let key = XOnlyPublicKey::from_slice(&txout.script_pubkey.as_bytes()[2..]).ok()?;
+            Some((vout, key))
+        })
+        .collect();
+    if outputs.is_empty() {
+        return None;
+    }
+
+    let input_keys: Vec<PublicKey> = tx
+        .input
+        .iter()
+        .zip(prevouts)
+        .filter_map(|(txin, prevout)| input_public_key(txin, prevout))
+        .collect();
+    let input_keys_sum = PublicKey::combine_keys(&input_keys.iter().collect::<Vec<_>>()).ok()?;
+    let input_hash = input_hash(
+        tx.input.iter().map(|txin| &txin.previous_output),
+        &input_keys_sum,
+    )?;
+    let ecdh_shared_secret = input_keys_sum
+        .mul_tweak(secp, &input_hash)
+        .ok()?
+        .mul_tweak(secp, &Scalar::from(*scan_key))
+        .ok()?;
+
+    // Look for the `k`-th output until one is missing.
+    let mut found = Vec::new();
+    for k in 0.. {
+        let tweak =
+            SecretKey::from_slice(&output_tweak(&ecdh_shared_secret, k)?.to_be_bytes()).ok()?;
+        let output_key = spend_key.add_exp_tweak(secp, &Scalar::from(tweak)).ok()?;
+        let matched = outputs
+            .iter()
+            .position(|(_, key)| output_key.x_only_public_key().0 == *key);
+        let (position, output) = match matched {
+            Some(position) => (position, SilentPaymentOutput { tweak, label: None }),
+            None => match labels.iter().find_map(|(m, label_tweak, label_key)| {
+                let labeled_key = output_key.combine(label_key).ok()?.x_only_public_key().0;
+                let position = outputs.iter().position(|(_, key)| labeled_key == *key)?;
+                Some((position, *m, label_tweak))
+            }) {
+                Some((position, m, label_tweak)) => {
+                    let output = SilentPaymentOutput {
+                        tweak: tweak.add_tweak(&Scalar::from(*label_tweak)).ok()?,
+                        label: Some(m),
+                    };
+                    (position, output)
+                }
+                None => break,
+            },
+        };
+        let (vout, _) = outputs.remove(position);
+        found.push((vout, output));
+    }
+    Some(found)
+}
+
+/// `hash_BIP0352/Inputs(outpoint_L || A)`, with `outpoint_L` the smallest outpoint spent by the
+/// transaction and `A` the sum of the public keys of its eligible inputs.
+pub(crate) fn input_hash<'a>(
+    outpoints: impl IntoIterator<Item = &'a OutPoint>,
+    input_keys_sum: &PublicKey,
+) -> Option<Scalar> {
+    let smallest_outpoint = outpoints.into_iter().map(serialize).min()?;
+    let hash = tagged_hash(
+        "BIP0352/Inputs",
+        &[&smallest_outpoint, &input_keys_sum.serialize()],
+    );
+    Scalar::from_be_bytes(hash).ok()
+}
+
+/// `hash_BIP0352/SharedSecret(ecdh_shared_secret || ser32(k))`, the tweak of the `k`-th output
+/// paying the same scan key.
+pub(crate) fn output_tweak(ecdh_shared_secret: &PublicKey, k: u32) -> Option<Scalar> {
+    let hash = tagged_hash(
+        "BIP0352/SharedSecret",
+        &[&ecdh_shared_secret.serialize(), &k.to_be_bytes()],
+    );
+    Scalar::from_be_bytes(hash).ok()
+}
+
+/// Derive the scripts of the outputs paying `recipients`, in the same order, from the private
+/// keys of the eligible inputs of a transaction spending `outpoints`.
+///
+/// Returns `None` if the keys cancel out or, with negligible probability, if a tweak is invalid.
+pub(crate) fn derive_output_scripts<'a, C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    input_keys: &[SecretKey],
+    outpoints: impl IntoIterator<Item = &'a OutPoint>,
+    recipients: &[SilentPaymentAddress],
+) -> Option<Vec<ScriptBuf>> {
+    let (first, rest) = input_keys.split_first()?;
+    let input_keys_sum = rest
+        .iter()
+        .try_fold(*first, |sum, key| sum.add_tweak(&Scalar::from(*key)).ok())?;
+    let input_hash = input_hash(
+        outpoints,
+        &PublicKey::from_secret_key(secp, &input_keys_sum),
+    )?;
+    let tweaked_input_keys = Scalar::from(input_keys_sum.mul_tweak(&input_hash).ok()?);
+
+    // Outputs paying the same scan key are numbered in the order of the recipients.
+    let mut shared_secrets: Vec<(PublicKey, PublicKey, u32)> = Vec::new();
+    recipients
+        .iter()
+        .map(|address| {
+            let position = match shared_secrets
+                .iter()
+                .position(|(scan, ..)| *scan == address.scan)
+            {
+                Some(position) => position,
+                None => {
+                    let ecdh_shared_secret =
+                        address.scan.mul_tweak(secp, &tweaked_input_keys).ok()?;
+                    shared_secrets.push((address.scan, ecdh_shared_secret, 0));
+                    shared_secrets.len() - 1
+                }
+            };
+            let (_, ecdh_shared_secret, k) = &mut shared_secrets[position];
+            let tweak = output_tweak(ecdh_shared_secret, *k)?;
+            *k += 1;
+            let output_key = address.spend.add_exp_tweak(secp, &tweak).ok()?;
+            Some(ScriptBuf::new_p2tr_tweaked(
+                TweakedPublicKey::dangerous_assume_tweaked(output_key.x_only_public_key().0),
+            ))
+        })
+        .collect()
+}
+
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+impl Wallet {
+    /// Replace the placeholder scripts of the silent payment recipients with the scripts derived
+    /// from the keys of the `selected` inputs.
+    ///
+    /// Returns the derived scripts with the address they pay.
+    pub(super) fn derive_silent_payment_outputs(
+        &self,
+        tx: &mut Transaction,
+        selected: &[Utxo],
+        silent_payments: &[(usize, SilentPaymentAddress)],
+    ) -> Result<Vec<(ScriptBuf, SilentPaymentAddress)>, CreateTxError> {
+        let mut input_keys = Vec::new();
+        for utxo in selected {
+            input_keys.extend(self.silent_payment_input_key(utxo)?);
+        }
+        let recipients: Vec<SilentPaymentAddress> = silent_payments
+            .iter()
+            .map(|(_, address)| *address)
+            .collect();
+        let scripts = derive_output_scripts(
+            &self.secp,
+            &input_keys,
+            tx.input.iter().map(|txin| &txin.previous_output),
+            &recipients,
+        )
+        .ok_or(CreateTxError::SilentPaymentNoEligibleInput)?;
+
+        Ok(silent_payments
+            .iter()
+            .zip(scripts)
+            .map(|(&(index, address), script_pubkey)| {
+                // Recipients come first in the outputs, in the order they were added.
+                tx.output[index].script_pubkey = script_pubkey.clone();
+                (script_pubkey, address)
+            })
+            .collect())
+    }
+
+    /// The private key `utxo` contributes to the silent payment outputs of a transaction, or
+    /// `None` if the input is not eligible.
+    fn silent_payment_input_key(&self, utxo: &Utxo) -> Result<Option<SecretKey>, CreateTxError> {
+        let utxo = match utxo {
+            Utxo::Local(utxo) => utxo,
+            // Received silent payments are spent through the taproot key path.
+            Utxo::SilentPayment(utxo) => {
+                let private_key = self
+                    .silent_payment_keys
+                    .and_then(|(_, spend_key)| output_key(&spend_key, &utxo.output))
+                    .ok_or(CreateTxError::SilentPaymentMissingKey(utxo.outpoint))?;
+                return Ok(Some(even_y_key(&self.secp, &private_key)));
+            }
+            Utxo::Foreign {
+                outpoint,
+                psbt_input,
+                ..
+            } => {
+                let script_pubkey = psbt_input
+                    .witness_utxo
+                    .as_ref()
+                    .map(|txout| &txout.script_pubkey)
+                    .or_else(|| {
+                        let tx = psbt_input.non_witness_utxo.as_ref()?;
+                        Some(&tx.output.get(outpoint.vout as usize)?.script_pubkey)
+                    });
+                // The keys of a foreign input are unknown, which is only fine if it can't be
+                // eligible.
+                return match script_pubkey {
+                    Some(script_pubkey)
+                        if !script_pubkey.is_p2wpkh()
+                            && !script_pubkey.is_p2tr()
+                            && !script_pubkey.is_p2pkh()
+                            && !script_pubkey.is_p2sh() =>
+                    {
+                        Ok(None)
+                    }
+                    _ => Err(CreateTxError::SilentPaymentForeignInput(*outpoint)),
+                };
+            }
+        };
+
+        let missing_key = CreateTxError::SilentPaymentMissingKey(utxo.outpoint);
+        let &(keychain, index) = self
+            .tx_graph
+            .index
+            .index_of_spk(utxo.txout.script_pubkey.clone())
+            .ok_or(CreateTxError::UnknownUtxo)?;
+        let descriptor = self
+            .public_descriptor(keychain)
+            .at_derivation_index(index)
+            .expect("child can't be hardened");
+
+        let key = match &descriptor {
+            Descriptor::Wpkh(wpkh) => wpkh.as_inner(),
+            Descriptor::Pkh(pkh) => pkh.as_inner(),
+            Descriptor::Sh(sh) => match sh.as_inner() {
+                ShInner::Wpkh(wpkh) => wpkh.as_inner(),
+                _ => return Ok(None),
+            },
+            Descriptor::Tr(tr) => {
+                let internal_key = tr.internal_key().to_x_only_pubkey();
+                if is_nums(&internal_key) {
+                    return Ok(None);
+                }
+                let private_key = self
+                    .find_private_key(keychain, index, |public_key| {
+                        public_key.inner.x_only_public_key().0 == internal_key
+                    })
+                    .ok_or(missing_key)?;
+                return Ok(Some(taproot_input_key(
+                    &self.secp,
+                    &private_key.inner,
+                    tr.spend_info().merkle_root(),
+                )));
+            }
+            _ => return Ok(None),
+        };
+        let public_key = key.to_public_key();
+        // Uncompressed keys are not eligible.
+        if !public_key.compressed {
+            return Ok(None);
+        }
+        let private_key = self
+            .find_private_key(keychain, index, |candidate| {
+                candidate.inner == public_key.inner
+            })
+            .ok_or(missing_key)?;
+        Ok(Some(private_key.inner))
+    }
+
+    /// Find among the signers of `keychain` the private key, derived at `index`, whose public key
+    /// `matches`.
+    fn find_private_key(
+        &self,
+        keychain: KeychainKind,
+        index: u32,
+        matches: impl Fn(&bitcoin::PublicKey) -> bool,
+    ) -> Option<PrivateKey> {
+        let signers = self.get_signers(keychain);
+        signers
+            .signers()
+            .into_iter()
+            .filter_map(|signer| signer.descriptor_secret_key())
+            .flat_map(|key| key.into_single_keys())
+            .find_map(|key| {
+                let private_key = match key {
+                    DescriptorSecretKey::Single(single) => single.key,
+                    DescriptorSecretKey::XPrv(xkey) => {
+                        let path = match xkey.wildcard {
+                            Wildcard::None => xkey.derivation_path,
+                            Wildcard::Unhardened => xkey
+                                .derivation_path
+                                .child(ChildNumber::from_normal_idx(index).ok()?),
+                            Wildcard::Hardened => xkey
+                                .derivation_path
+                                .child(ChildNumber::from_hardened_idx(index).ok()?),
+                        };
+                        xkey.xkey.derive_priv(&self.secp, &path).ok()?.to_priv()
+                    }
+                    DescriptorSecretKey::MultiXPrv(_) => return None,
+                };
+                matches(&private_key.public_key(&self.secp)).then_some(private_key)
+            })
+    }
+
+    /// Sign the inputs spending silent payments received by the wallet, through the taproot key
+    /// path with the tweaked spend key.
+    pub(super) fn sign_silent_payment_inputs(&self, psbt: &mut Psbt) -> Result<(), SignerError> {
+        let Some((_, spend_key)) = self.silent_payment_keys else {
+            return Ok(());
+        };
+        for (n, txin) in psbt.unsigned_tx.input.iter().enumerate() {
+            let Some(output) = self.silent_payments.outputs.get(&txin.previous_output) else {
+                continue;
+            };
+            let psbt_input = psbt
+                .inputs
+                .get(n)
+                .ok_or(IndexOutOfBoundsError::new(n, psbt.inputs.len()))?;
+            if psbt_input.final_script_witness.is_some() || psbt_input.tap_key_sig.is_some() {
+                continue;
+            }
+            let private_key = output_key(&spend_key, output).ok_or(SignerError::InvalidKey)?;
+            let keypair = Keypair::from_secret_key(&self.secp, &private_key);
+            let (sighash, sighash_type) = signer::compute_tap_sighash(psbt, n, None)?;
+            let signature = self
+                .secp
+                .sign_schnorr_no_aux_rand(&Message::from(sighash), &keypair);
+            psbt.inputs[n].tap_key_sig = Some(taproot::Signature {
+                signature,
+                sighash_type,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! Sweeping the funds of foreign keys and descriptors into the wallet.
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use bitcoin::{psbt, FeeRate, NetworkKind, OutPoint, PrivateKey, Psbt, TxIn, Witness};
+use miniscript::psbt::{PsbtExt, PsbtInputExt};
+use rand_core::RngCore;
+
+use crate::collections::BTreeMap;
+use crate::descriptor::{check_wallet_descriptor, DescriptorError, IntoWalletDescriptor};
+use crate::error::{MiniscriptPsbtError, SweepError};
+use crate::signer::{SignOptions, SignersContainer};
+use crate::wallet::tx_builder;
+use crate::Wallet;
+
+/// Number of derivation indexes of a ranged descriptor searched by [`Wallet::build_sweep`] for
+/// the scripts of the UTXOs to sweep.
+pub const SWEEP_DERIVATION_RANGE: u32 = 1000;
+
+impl Wallet {
+    /// Sweep the `utxos` of a foreign descriptor, or of a single private key in WIF, into the
+    /// wallet.
+    ///
+    /// Uses the thread-local random number generator (rng).
+    ///
+    /// The returned PSBT spends all the `utxos` to the next unused change address of the wallet,
+    /// at `fee_rate`. Its inputs are signed with a temporary signer holding the private keys of
+    /// `descriptor_or_wif`, which are not added to the wallet, and finalized when possible.
+    ///
+    /// A WIF key is swept from its P2PKH outputs and, if the key is compressed, from its P2WPKH,
+    /// P2SH-P2WPKH and P2TR outputs. The outputs of a ranged descriptor are looked for among its
+    /// first [`SWEEP_DERIVATION_RANGE`] derivation indexes.
+    ///
+    /// Each UTXO is given with a PSBT input containing its `witness_utxo` or, for non-segwit
+    /// outputs, its `non_witness_utxo`, as with [`TxBuilder::add_foreign_utxo`]. The
+    /// `witness_utxo` of segwit outputs is trusted, so make sure it comes from a source you trust.
+    ///
+    /// The change address is only revealed if the PSBT is built.
+    ///
+    /// **WARNING**: To avoid address reuse you must persist the changes resulting from this method
+    /// before closing the wallet. See [`Wallet::reveal_next_address`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// let wif = "cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW";
+    /// # let key = bitcoin::PrivateKey::from_wif(wif)?;
+    /// # let script_pubkey = ScriptBuf::new_p2wpkh(&key.public_key(&Default::default()).wpubkey_hash()?);
+    /// // The UTXOs of the key, as found with a blockchain client
+    /// let outpoint = OutPoint::new("ad1bb3c4fe4b1f1fcafa7dd8d4fbb2fe8e4b3fd0fa7fd32a5b06fa5ae2a5fdf2".parse()?, 0);
+    /// let psbt_input = psbt::Input {
+    ///     witness_utxo: Some(TxOut { value: Amount::from_sat(50_000), script_pubkey }),
+    ///     ..Default::default()
+    /// };
+    /// let psbt = wallet.build_sweep(wif, [(outpoint, psbt_input)], FeeRate::from_sat_per_vb_u32(2))?;
+    /// let tx = psbt.extract_tx()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`TxBuilder::add_foreign_utxo`]: crate::TxBuilder::add_foreign_utxo
+    #[cfg(feature = "std")]
+    pub fn build_sweep(
+        &mut self,
+        descriptor_or_wif: &str,
+        utxos: impl IntoIterator<Item = (OutPoint, psbt::Input)>,
+        fee_rate: FeeRate,
+    ) -> Result<Psbt, SweepError> {
+        self.build_sweep_with_aux_rand(
+            descriptor_or_wif,
+            utxos,
+            fee_rate,
+            &mut bitcoin::key::rand::thread_rng(),
+        )
+    }
+
+    /// Sweep the `utxos` of a foreign descriptor, or of a single private key in WIF, into the
+    /// wallet.
+    ///
+    /// Uses a provided random number generator (rng) to build the transaction. See
+    /// [`build_sweep`] for details.
+    ///
+    /// [`build_sweep`]: Self::build_sweep
+    pub fn build_sweep_with_aux_rand(
+        &mut self,
+        descriptor_or_wif: &str,
+        utxos: impl IntoIterator<Item = (OutPoint, psbt::Input)>,
+        fee_rate: FeeRate,
+        rng: &mut impl RngCore,
+    ) -> Result<Psbt, SweepError> {
+        let descriptors = match PrivateKey::from_wif(descriptor_or_wif) {
+            Ok(key) if key.compressed => vec![
+                format!("pkh({key})"),
+                format!("wpkh({key})"),
+                format!("sh(wpkh({key}))"),
+                format!("tr({key})"),
+            ],
+            Ok(key) => vec![format!("pkh({key})")],
+            Err(_) => vec![descriptor_or_wif.to_string()],
+        };
+        let network_kind = NetworkKind::from(self.network);
+        let descriptors = descriptors
+            .into_iter()
+            .map(|descriptor| {
+                let (descriptor, keymap) =
+                    descriptor.into_wallet_descriptor(&self.secp, network_kind)?;
+                check_wallet_descriptor(&descriptor)?;
+                let signers = SignersContainer::build(keymap, &descriptor, &self.secp);
+                Ok((descriptor, signers))
+            })
+            .collect::<Result<Vec<_>, DescriptorError>>()
+            .map_err(SweepError::Descriptor)?;
+
+        let mut foreign_utxos = Vec::new();
+        let mut spent_by = BTreeMap::new();
+        for (outpoint, mut psbt_input) in utxos {
+            let txout = psbt_input
+                .witness_utxo
+                .clone()
+                .or_else(|| {
+                    let tx = psbt_input.non_witness_utxo.as_ref()?;
+                    tx.output.get(outpoint.vout as usize).cloned()
+                })
+                .ok_or(SweepError::ForeignUtxo(
+                    tx_builder::AddForeignUtxoError::MissingUtxo,
+                ))?;
+            let (descriptor_index, derived) = descriptors
+                .iter()
+                .enumerate()
+                .find_map(|(descriptor_index, (descriptor, _))| {
+                    let range = if descriptor.has_wildcard() {
+                        0..SWEEP_DERIVATION_RANGE
+                    } else {
+                        0..1
+                    };
+                    let (index, _) = descriptor
+                        .find_derivation_index_for_spk(&self.secp, &txout.script_pubkey, range)
+                        .ok()??;
+                    let derived = descriptor
+                        .at_derivation_index(index)
+                        .expect("child can't be hardened");
+                    Some((descriptor_index, derived))
+                })
+                .ok_or(SweepError::UnknownScript(outpoint))?;
+            spent_by.insert(outpoint, descriptor_index);
+            psbt_input
+                .update_with_descriptor_unchecked(&derived)
+                .map_err(|err| SweepError::CreateTx(MiniscriptPsbtError::Conversion(err).into()))?;
+            let satisfaction_weight = derived
+                .max_weight_to_satisfy()
+                .expect("descriptor should be satisfiable");
+            foreign_utxos.push((outpoint, psbt_input, satisfaction_weight));
+        }
+        if foreign_utxos.is_empty() {
+            return Err(SweepError::NoUtxos);
+        }
+
+        // Without recipients, the inputs go to a change address, revealed once the PSBT is built.
+        let mut builder = self.build_tx();
+        builder
+            .manually_selected_only()
+            .only_witness_utxo()
+            .fee_rate(fee_rate);
+        for (outpoint, psbt_input, satisfaction_weight) in foreign_utxos {
+            builder
+                .add_foreign_utxo(outpoint, psbt_input, satisfaction_weight)
+                .map_err(SweepError::ForeignUtxo)?;
+        }
+        let mut psbt = builder
+            .finish_with_aux_rand(rng)
+            .map_err(SweepError::CreateTx)?;
+
+        let sign_options = SignOptions {
+            trust_witness_utxo: true,
+            ..Default::default()
+        };
+        for (descriptor_index, (_, signers)) in descriptors.iter().enumerate() {
+            // Signers try to sign every input, so the inputs of the other descriptors, which
+            // may require another signing algorithm, are hidden by marking them as finalized.
+            let is_spent =
+                |txin: &TxIn| spent_by.get(&txin.previous_output) == Some(&descriptor_index);
+            let mut descriptor_psbt = psbt.clone();
+            for (txin, psbt_input) in psbt
+                .unsigned_tx
+                .input
+                .iter()
+                .zip(&mut descriptor_psbt.inputs)
+            {
+                if !is_spent(txin) {
+                    psbt_input.final_script_witness = Some(Witness::new());
+                }
+            }
+            for signer in signers.signers() {
+                signer
+                    .sign_transaction(&mut descriptor_psbt, &sign_options, &self.secp)
+                    .map_err(SweepError::Signer)?;
+            }
+            for (n, txin) in psbt.unsigned_tx.input.iter().enumerate() {
+                if is_spent(txin) {
+                    psbt.inputs[n] = descriptor_psbt.inputs[n].clone();
+                }
+            }
+        }
+        for n in 0..psbt.inputs.len() {
+            // Inputs missing signatures are left for the caller to complete.
+            let _ = psbt.finalize_inp_mut(&self.secp, n);
+        }
+        Ok(psbt)
+    }
+}
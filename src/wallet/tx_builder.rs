@@ -41,6 +41,7 @@ use core::fmt;
 
 use alloc::sync::Arc;
 
+use bitcoin::key::TweakedPublicKey;
 use bitcoin::psbt::{self, Psbt};
 use bitcoin::script::PushBytes;
 use bitcoin::{
@@ -48,18 +49,32 @@ use bitcoin::{
     TxIn, TxOut, Txid, Weight,
 };
 use rand_core::RngCore;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use super::coin_selection::CoinSelectionAlgorithm;
-use super::utils::shuffle_slice;
+use super::coin_selection::{CoinSelectionAlgorithm, CoinSelectionExplanation};
+use super::silent_payments::SilentPaymentAddress;
+use super::utils::{is_p2a, shuffle_slice};
 use super::{CreateTxError, Wallet};
-use crate::collections::{BTreeMap, HashMap, HashSet};
-use crate::{KeychainKind, LocalOutput, Utxo, WeightedUtxo};
+use crate::collections::{BTreeMap, HashSet};
+use crate::psbt::v2::{PsbtV2, TxModifiable};
+use crate::{KeychainKind, LocalOutput, Utxo};
+
+/// Version of TRUC (*topologically restricted until confirmation*) transactions, see [BIP431].
+///
+/// [BIP431]: https://github.com/bitcoin/bips/blob/master/bip-0431.mediawiki
+pub const TRUC_VERSION: Version = Version(3);
+
+/// Maximum virtual size of a TRUC transaction.
+pub const TRUC_MAX_VSIZE: u64 = 10_000;
+
+/// Maximum virtual size of a TRUC transaction with an unconfirmed parent.
+pub const TRUC_CHILD_MAX_VSIZE: u64 = 1_000;
 
 /// A transaction builder
 ///
-/// A `TxBuilder` is created by calling [`build_tx`] or [`build_fee_bump`] on a wallet. After
-/// assigning it, you set options on it until finally calling [`finish`] to consume the builder and
-/// generate the transaction.
+/// A `TxBuilder` is created by calling [`build_tx`], [`build_fee_bump`] or [`build_cpfp`] on a
+/// wallet. After assigning it, you set options on it until finally calling [`finish`] to consume
+/// the builder and generate the transaction.
 ///
 /// Each option setting method on `TxBuilder` takes and returns `&mut self` so you can chain calls
 /// as in the following example:
@@ -107,6 +122,7 @@ use crate::{KeychainKind, LocalOutput, Utxo, WeightedUtxo};
 ///
 /// [`build_tx`]: Wallet::build_tx
 /// [`build_fee_bump`]: Wallet::build_fee_bump
+/// [`build_cpfp`]: Wallet::build_cpfp
 /// [`finish`]: Self::finish
 /// [`coin_selection`]: Self::coin_selection
 #[derive(Debug)]
@@ -117,29 +133,105 @@ pub struct TxBuilder<'a, Cs> {
 }
 
 /// The parameters for transaction creation sans coin selection algorithm.
-//TODO: TxParams should eventually be exposed publicly.
-#[derive(Default, Debug, Clone)]
-pub(crate) struct TxParams {
-    pub(crate) recipients: Vec<(ScriptBuf, Amount)>,
-    pub(crate) drain_wallet: bool,
-    pub(crate) drain_to: Option<ScriptBuf>,
-    pub(crate) fee_policy: Option<FeePolicy>,
-    pub(crate) internal_policy_path: Option<BTreeMap<String, Vec<usize>>>,
-    pub(crate) external_policy_path: Option<BTreeMap<String, Vec<usize>>>,
-    pub(crate) utxos: Vec<WeightedUtxo>,
-    pub(crate) unspendable: HashSet<OutPoint>,
-    pub(crate) manually_selected_only: bool,
-    pub(crate) sighash: Option<psbt::PsbtSighashType>,
-    pub(crate) ordering: TxOrdering,
-    pub(crate) locktime: Option<absolute::LockTime>,
-    pub(crate) sequence: Option<Sequence>,
-    pub(crate) version: Option<Version>,
-    pub(crate) change_policy: ChangeSpendPolicy,
-    pub(crate) only_witness_utxo: bool,
-    pub(crate) add_global_xpubs: bool,
+///
+/// The parameters are set with a [`TxBuilder`] and can be retrieved with [`TxBuilder::params`].
+/// They can be serialized to create the transaction later, by passing them to
+/// [`Wallet::build_tx_with_params`]. As long as the wallet state hasn't changed and the same
+/// coin selection algorithm and random number generator are used, the resulting PSBT is the same.
+///
+/// Only the intent of the transaction is stored: the UTXOs of the wallet are referred to by their
+/// outpoint and looked up again when the transaction is built. The state of a fee bump (see
+/// [`Wallet::build_fee_bump`]) isn't part of the parameters, so the parameters of a fee bump
+/// create a new transaction spending the same UTXOs.
+///
+/// A [`TxOrdering::Custom`] ordering and a [`ChangeStrategy::Custom`] strategy can't be
+/// serialized.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TxParams {
+    /// Recipients of the transaction, see [`TxBuilder::add_recipient`]
+    pub recipients: Vec<(ScriptBuf, Amount)>,
+    /// Whether to spend all the UTXOs of the wallet, see [`TxBuilder::drain_wallet`]
+    pub drain_wallet: bool,
+    /// Script receiving the change, see [`TxBuilder::drain_to`]
+    pub drain_to: Option<ScriptBuf>,
+    /// Fee to pay, see [`TxBuilder::fee_rate`] and [`TxBuilder::fee_absolute`]
+    pub fee_policy: Option<FeePolicy>,
+    /// Policy path of the internal descriptor, see [`TxBuilder::policy_path`]
+    pub internal_policy_path: Option<BTreeMap<String, Vec<usize>>>,
+    /// Policy path of the external descriptor, see [`TxBuilder::policy_path`]
+    pub external_policy_path: Option<BTreeMap<String, Vec<usize>>>,
+    /// UTXOs that must be spent, see [`TxBuilder::add_utxo`] and [`TxBuilder::add_foreign_utxo`]
+    pub utxos: Vec<RequiredUtxo>,
+    /// UTXOs that must not be spent, see [`TxBuilder::unspendable`]
+    pub unspendable: HashSet<OutPoint>,
+    /// Minimum number of confirmations of the spent UTXOs, see
+    /// [`TxBuilder::exclude_below_confirmations`]
+    pub min_confirmations: u32,
+    /// Whether to only spend the UTXOs in `utxos`, see [`TxBuilder::manually_selected_only`]
+    pub manually_selected_only: bool,
+    /// Sighash type to sign with, see [`TxBuilder::sighash`]
+    pub sighash: Option<psbt::PsbtSighashType>,
+    /// Ordering of the inputs and outputs, see [`TxBuilder::ordering`]
+    pub ordering: TxOrdering,
+    /// Locktime of the transaction, see [`TxBuilder::nlocktime`]
+    pub locktime: Option<absolute::LockTime>,
+    /// nSequence of the inputs, see [`TxBuilder::set_exact_sequence`]
+    pub sequence: Option<Sequence>,
+    /// Version of the transaction, see [`TxBuilder::version`]
+    pub version: Option<Version>,
+    /// Whether to spend change outputs, see [`TxBuilder::change_policy`]
+    pub change_policy: ChangeSpendPolicy,
+    /// How to handle the change, see [`TxBuilder::change_strategy`]
+    pub change_strategy: ChangeStrategy,
+    /// Whether to only fill the `witness_utxo` of the inputs, see
+    /// [`TxBuilder::only_witness_utxo`]
+    pub only_witness_utxo: bool,
+    /// Whether to add the extended keys to the PSBT, see [`TxBuilder::add_global_xpubs`]
+    pub add_global_xpubs: bool,
+    /// Height used for the anti fee sniping locktime, see [`TxBuilder::current_height`]
+    pub current_height: Option<absolute::LockTime>,
+    /// Whether to allow dust outputs, see [`TxBuilder::allow_dust`]
+    pub allow_dust: bool,
+    /// Indexes of the recipients paying the fee, see [`TxBuilder::subtract_fee_from`]
+    pub subtract_fee_from: Vec<usize>,
+    /// Whether the last recipient receives the maximum amount, see
+    /// [`TxBuilder::add_max_recipient`]
+    pub max_recipient: bool,
+    /// Whether to build a TRUC transaction, see [`TxBuilder::truc`]
+    pub truc: bool,
+    /// Silent payment recipients as the index of their output and their address, see
+    /// [`TxBuilder::add_silent_payment_recipient`]
+    pub silent_payments: Vec<(usize, SilentPaymentAddress)>,
+    #[serde(skip)]
     pub(crate) bumping_fee: Option<PreviousFee>,
-    pub(crate) current_height: Option<absolute::LockTime>,
-    pub(crate) allow_dust: bool,
+}
+
+/// A UTXO that must be spent by the transaction, see [`TxParams::utxos`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RequiredUtxo {
+    /// An output of the wallet, looked up when the transaction is built
+    Local(OutPoint),
+    /// An output not owned by the wallet, see [`TxBuilder::add_foreign_utxo`]
+    Foreign {
+        /// The location of the output
+        outpoint: OutPoint,
+        /// The information about the input
+        psbt_input: Box<psbt::Input>,
+        /// The weight of the witness data and `scriptSig`
+        satisfaction_weight: Weight,
+        /// The nSequence value to set for this input
+        sequence: Sequence,
+    },
+}
+
+impl RequiredUtxo {
+    /// Get the location of the UTXO
+    pub fn outpoint(&self) -> OutPoint {
+        match self {
+            RequiredUtxo::Local(outpoint) | RequiredUtxo::Foreign { outpoint, .. } => *outpoint,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -148,9 +240,29 @@ pub(crate) struct PreviousFee {
     pub rate: FeeRate,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) enum FeePolicy {
+/// Total fee and weight of a set of unconfirmed transactions, which a child spending their
+/// outputs has to pay for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct AncestorPackage {
+    pub fee: Amount,
+    pub weight: Weight,
+}
+
+impl Default for AncestorPackage {
+    fn default() -> Self {
+        Self {
+            fee: Amount::ZERO,
+            weight: Weight::ZERO,
+        }
+    }
+}
+
+/// Fee paid by a transaction, see [`TxParams::fee_policy`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum FeePolicy {
+    /// Pay a fee rate, see [`TxBuilder::fee_rate`]
     FeeRate(FeeRate),
+    /// Pay an absolute fee, see [`TxBuilder::fee_absolute`]
     FeeAmount(Amount),
 }
 
@@ -162,6 +274,11 @@ impl Default for FeePolicy {
 
 // Methods supported for any CoinSelectionAlgorithm.
 impl<'a, Cs> TxBuilder<'a, Cs> {
+    /// Get the parameters of the transaction, see [`TxParams`].
+    pub fn params(&self) -> &TxParams {
+        &self.params
+    }
+
     /// Set a custom fee rate.
     ///
     /// This method sets the mining fee paid by the transaction as a rate on its size.
@@ -172,6 +289,11 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// Note that this is really a minimum feerate -- it's possible to
     /// overshoot it slightly since adding a change output to drain the remaining
     /// excess might not be viable.
+    ///
+    /// When spending outputs of unconfirmed transactions the fee rate applies to the package made
+    /// of the transaction and its unconfirmed ancestors: the fee they are missing to reach
+    /// `fee_rate` is added to the fee of the transaction, so that miners get the requested fee
+    /// rate when mining them together.
     pub fn fee_rate(&mut self, fee_rate: FeeRate) -> &mut Self {
         self.params.fee_policy = Some(FeePolicy::FeeRate(fee_rate));
         self
@@ -277,43 +399,36 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// the "UTXOs" and the "unspendable" list, it will be spent.
     ///
     /// If a UTXO is inserted multiple times, only the final insertion will take effect.
+    ///
+    /// Without recipients nor [`TxBuilder::drain_to`], the inputs are sent to a change address.
     pub fn add_utxos(&mut self, outpoints: &[OutPoint]) -> Result<&mut Self, AddUtxoError> {
         // Canonicalize once, instead of once for every call to `get_utxo`.
-        let unspent: HashMap<OutPoint, LocalOutput> = self
+        let unspent: HashSet<OutPoint> = self
             .wallet
-            .list_unspent()
-            .map(|output| (output.outpoint, output))
+            .unspent_utxos()
+            .map(|utxo| utxo.outpoint())
             .collect();
+        for &outpoint in outpoints {
+            if !unspent.contains(&outpoint) {
+                return Err(AddUtxoError::UnknownUtxo(outpoint));
+            }
+        }
 
         // Ensure that only unique outpoints are added, but keep insertion order.
         let mut visited = <HashSet<OutPoint>>::new();
-
-        let utxos: Vec<WeightedUtxo> = outpoints
+        let utxos: Vec<RequiredUtxo> = outpoints
             .iter()
             .filter(|&&op| visited.insert(op))
-            .map(|&op| -> Result<_, AddUtxoError> {
-                let output = unspent
-                    .get(&op)
-                    .cloned()
-                    .ok_or(AddUtxoError::UnknownUtxo(op))?;
-                Ok(WeightedUtxo {
-                    satisfaction_weight: self
-                        .wallet
-                        .public_descriptor(output.keychain)
-                        .max_weight_to_satisfy()
-                        .expect("descriptor should be satisfiable"),
-                    utxo: Utxo::Local(output),
-                })
-            })
-            .collect::<Result<_, _>>()?;
+            .map(|&op| RequiredUtxo::Local(op))
+            .collect();
 
         // Remove already inserted UTXOs first.
         self.params
             .utxos
-            .retain(|wutxo| !visited.contains(&wutxo.utxo.outpoint()));
+            .retain(|utxo| !visited.contains(&utxo.outpoint()));
 
         // Update the removed UTXOs in their new positions.
-        self.params.utxos.extend_from_slice(&utxos);
+        self.params.utxos.extend(utxos);
 
         Ok(self)
     }
@@ -424,11 +539,11 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
 
         let mut existing_index: Option<usize> = None;
 
-        for (idx, wutxo) in self.params.utxos.iter().enumerate() {
-            if wutxo.utxo.outpoint() == outpoint {
-                match wutxo.utxo {
-                    Utxo::Local(..) => return Ok(self),
-                    Utxo::Foreign { .. } => {
+        for (idx, utxo) in self.params.utxos.iter().enumerate() {
+            if utxo.outpoint() == outpoint {
+                match utxo {
+                    RequiredUtxo::Local(..) => return Ok(self),
+                    RequiredUtxo::Foreign { .. } => {
                         existing_index = Some(idx);
                         break;
                     }
@@ -440,18 +555,56 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
             self.params.utxos.remove(idx);
         }
 
-        self.params.utxos.push(WeightedUtxo {
+        self.params.utxos.push(RequiredUtxo::Foreign {
+            outpoint,
+            psbt_input: Box::new(psbt_input),
             satisfaction_weight,
-            utxo: Utxo::Foreign {
-                outpoint,
-                sequence,
-                psbt_input: Box::new(psbt_input),
-            },
+            sequence,
         });
 
         Ok(self)
     }
 
+    /// Add a keyless pay-to-anchor (P2A) output to spend, typically to bump the fee of a
+    /// transaction we co-signed with a *child pays for parent* transaction.
+    ///
+    /// P2A outputs can be spent by anyone with an empty witness, so this works like
+    /// [`add_foreign_utxo`] with a satisfaction weight of zero. Since no signature commits to the
+    /// value of the anchor, `psbt_input` only needs a `witness_utxo`. The input is finalized by
+    /// [`Wallet::finalize_psbt`] (and [`Wallet::sign`]) with the empty witness.
+    ///
+    /// # Errors
+    ///
+    /// Besides the errors of [`add_foreign_utxo`], this returns
+    /// [`AddForeignUtxoError::NotPayToAnchor`] if the output is not a P2A output.
+    ///
+    /// [`add_foreign_utxo`]: Self::add_foreign_utxo
+    pub fn add_anchor_input(
+        &mut self,
+        outpoint: OutPoint,
+        psbt_input: psbt::Input,
+    ) -> Result<&mut Self, AddForeignUtxoError> {
+        let script_pubkey = psbt_input
+            .witness_utxo
+            .as_ref()
+            .or_else(|| {
+                psbt_input
+                    .non_witness_utxo
+                    .as_ref()
+                    .and_then(|tx| tx.output.get(outpoint.vout as usize))
+            })
+            .map(|txout| &txout.script_pubkey);
+        if script_pubkey.is_some_and(|spk| !is_p2a(spk)) {
+            return Err(AddForeignUtxoError::NotPayToAnchor(outpoint));
+        }
+        self.add_foreign_utxo_with_sequence(
+            outpoint,
+            psbt_input,
+            Weight::ZERO,
+            Sequence::ENABLE_RBF_NO_LOCKTIME,
+        )
+    }
+
     /// Only spend utxos added by [`add_utxo`].
     ///
     /// The wallet will **not** add additional utxos to the transaction even if they are needed to
@@ -491,23 +644,12 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     ///   `exclude_unconfirmed`).
     /// - Passing `6` will only allow outpoints from transactions with at least 6 confirmations.
     ///
-    /// If you chain this with other filtering methods, the final set of unspendable outpoints will
-    /// be the union of all filters.
+    /// If you call this several times, the highest `min_confirms` is used. The excluded outpoints
+    /// are reported as [`ExclusionReason::Unconfirmed`] by [`TxBuilder::finish_explained`].
+    ///
+    /// [`ExclusionReason::Unconfirmed`]: super::coin_selection::ExclusionReason::Unconfirmed
     pub fn exclude_below_confirmations(&mut self, min_confirms: u32) -> &mut Self {
-        let tip_height = self.wallet.latest_checkpoint().height();
-        let to_exclude = self
-            .wallet
-            .list_unspent()
-            .filter(|utxo| {
-                utxo.chain_position
-                    .confirmation_height_upper_bound()
-                    .map_or(0, |h| tip_height.saturating_add(1).saturating_sub(h))
-                    < min_confirms
-            })
-            .map(|utxo| utxo.outpoint);
-        for op in to_exclude {
-            self.params.unspendable.insert(op);
-        }
+        self.params.min_confirmations = self.params.min_confirmations.max(min_confirms);
         self
     }
 
@@ -556,6 +698,32 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         self
     }
 
+    /// Build a TRUC (*topologically restricted until confirmation*) transaction, as defined in
+    /// [BIP431].
+    ///
+    /// The transaction gets version `3`, overriding [`version`]. When it's created
+    /// [`TxBuilder::finish`] checks the TRUC rules against the wallet's transactions:
+    ///
+    /// - only outputs of unconfirmed TRUC transactions can be spent, and the coin selection only
+    ///   picks confirmed UTXOs;
+    /// - the transaction can have at most one unconfirmed ancestor, its parent;
+    /// - its virtual size is at most [`TRUC_MAX_VSIZE`], or [`TRUC_CHILD_MAX_VSIZE`] if it has
+    ///   an unconfirmed parent;
+    /// - if the parent already has an unconfirmed child, the transaction must pay enough to evict
+    ///   it (sibling eviction), like a [BIP125] replacement.
+    ///
+    /// The transactions returned by [`Wallet::build_fee_bump`] and [`Wallet::build_cpfp`] are in
+    /// TRUC mode when the original transaction or the parent is a TRUC transaction.
+    ///
+    /// [BIP431]: https://github.com/bitcoin/bips/blob/master/bip-0431.mediawiki
+    /// [BIP125]: https://github.com/bitcoin/bips/blob/master/bip-0125.mediawiki
+    /// [`version`]: Self::version
+    pub fn truc(&mut self) -> &mut Self {
+        self.params.truc = true;
+        self.params.version = Some(TRUC_VERSION);
+        self
+    }
+
     /// Do not spend change outputs
     ///
     /// This effectively adds all the change outputs to the "unspendable" list. See
@@ -576,6 +744,14 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         self
     }
 
+    /// Set the [`ChangeStrategy`] used to split the change into outputs.
+    ///
+    /// The default is a single change output.
+    pub fn change_strategy(&mut self, change_strategy: ChangeStrategy) -> &mut Self {
+        self.params.change_strategy = change_strategy;
+        self
+    }
+
     /// Set a specific [`ChangeSpendPolicy`]. See [`TxBuilder::do_not_spend_change`] and
     /// [`TxBuilder::only_spend_change`] for some shortcuts. This method assumes the presence
     /// of an internal keychain, otherwise it has no effect.
@@ -606,6 +782,8 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
 
     /// Spend all the available inputs. This respects filters like [`TxBuilder::unspendable`] and
     /// the change policy.
+    ///
+    /// Without recipients nor [`TxBuilder::drain_to`], the inputs are sent to a change address.
     pub fn drain_wallet(&mut self) -> &mut Self {
         self.params.drain_wallet = true;
         self
@@ -663,6 +841,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// Replace the recipients already added with a new list
     pub fn set_recipients(&mut self, recipients: Vec<(ScriptBuf, Amount)>) -> &mut Self {
         self.params.recipients = recipients;
+        self.params.silent_payments.clear();
         self
     }
 
@@ -676,6 +855,51 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         self
     }
 
+    /// Add a recipient paying a [BIP352] silent payment address.
+    ///
+    /// The output key is derived once the inputs are selected, from the private keys of the
+    /// P2WPKH, P2SH-P2WPKH, P2PKH and key path P2TR inputs, which are found among the signers of
+    /// the wallet. [`TxBuilder::finish`] returns [`CreateTxError::SilentPaymentMissingKey`] if a
+    /// key is missing and [`CreateTxError::SilentPaymentForeignInput`] if a foreign UTXO may
+    /// contribute to the output key. The output is marked with the `PSBT_OUT_SP_V0_INFO` field of
+    /// the PSBT.
+    ///
+    /// The recipient is counted like the other recipients, for example by
+    /// [`subtract_fee_from`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # use bdk_wallet::silent_payments::SilentPaymentAddress;
+    /// # let mut wallet = doctest_wallet!();
+    /// let address: SilentPaymentAddress = "tsp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc3wk4yh"
+    ///     .parse()?;
+    /// let mut tx_builder = wallet.build_tx();
+    /// tx_builder.add_silent_payment_recipient(address, Amount::from_sat(50_000));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [BIP352]: https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki
+    /// [`subtract_fee_from`]: Self::subtract_fee_from
+    /// [`CreateTxError::SilentPaymentMissingKey`]: crate::error::CreateTxError::SilentPaymentMissingKey
+    /// [`CreateTxError::SilentPaymentForeignInput`]: crate::error::CreateTxError::SilentPaymentForeignInput
+    pub fn add_silent_payment_recipient(
+        &mut self,
+        address: SilentPaymentAddress,
+        amount: Amount,
+    ) -> &mut Self {
+        // A taproot output of the same size stands in for the output until the inputs are known.
+        let placeholder = ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+            address.spend.x_only_public_key().0,
+        ));
+        self.params
+            .silent_payments
+            .push((self.params.recipients.len(), address));
+        self.add_recipient(placeholder, amount)
+    }
+
     /// Add data as an output, using OP_RETURN
     pub fn add_data<T: AsRef<PushBytes>>(&mut self, data: &T) -> &mut Self {
         let script = ScriptBuf::new_op_return(data);
@@ -683,6 +907,94 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         self
     }
 
+    /// Send everything left over to `script_pubkey`, after paying the other recipients and the
+    /// fee.
+    ///
+    /// All the available inputs are spent, like with [`drain_wallet`]: this respects
+    /// [`manually_selected_only`], the UTXOs added with [`add_utxos`] and the filters like
+    /// [`exclude_unconfirmed`]. Unlike with [`drain_to`] the output is never dropped: if what's
+    /// left for the max recipient is below the dust limit [`TxBuilder::finish`] returns
+    /// [`InsufficientFunds`]. This replaces any script previously set with [`drain_to`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+    /// #     .unwrap()
+    /// #     .assume_checked();
+    /// # let max_address = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+    /// #     .unwrap()
+    /// #     .assume_checked();
+    /// # let mut wallet = doctest_wallet!();
+    /// let mut tx_builder = wallet.build_tx();
+    /// tx_builder
+    ///     .add_recipient(to_address.script_pubkey(), Amount::from_sat(10_000))
+    ///     // Receives the rest of the wallet's coins.
+    ///     .add_max_recipient(max_address.script_pubkey())
+    ///     .exclude_unconfirmed();
+    /// let psbt = tx_builder.finish()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`drain_wallet`]: Self::drain_wallet
+    /// [`drain_to`]: Self::drain_to
+    /// [`manually_selected_only`]: Self::manually_selected_only
+    /// [`add_utxos`]: Self::add_utxos
+    /// [`exclude_unconfirmed`]: Self::exclude_unconfirmed
+    /// [`InsufficientFunds`]: crate::wallet::coin_selection::InsufficientFunds
+    pub fn add_max_recipient(&mut self, script_pubkey: impl Into<ScriptBuf>) -> &mut Self {
+        self.params.drain_to = Some(script_pubkey.into());
+        self.params.drain_wallet = true;
+        self.params.max_recipient = true;
+        self
+    }
+
+    /// Pay the fee of the transaction out of the recipients at the given indices, instead of
+    /// adding it on top of the amounts sent.
+    ///
+    /// The indices refer to the recipients in the order they were added. The fee is split equally
+    /// between the chosen recipients, the first one of them also paying for whatever can't be
+    /// divided equally. This is the same as Bitcoin Core's `subtractfeefromoutputs`.
+    ///
+    /// [`TxBuilder::finish`] returns [`CreateTxError::SubtractFeeIndexOutOfBounds`] if an index
+    /// doesn't refer to a recipient, and [`CreateTxError::SubtractFeeBelowDust`] if a recipient
+    /// can't pay its share of the fee or is left below the dust limit after paying it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+    /// #     .unwrap()
+    /// #     .assume_checked();
+    /// # let mut wallet = doctest_wallet!();
+    /// let mut tx_builder = wallet.build_tx();
+    /// tx_builder
+    ///     // The recipient receives 50_000 sats minus the fee.
+    ///     .add_recipient(to_address.script_pubkey(), Amount::from_sat(50_000))
+    ///     .subtract_fee_from([0])
+    ///     .fee_rate(FeeRate::from_sat_per_vb(5).expect("valid feerate"));
+    /// let psbt = tx_builder.finish()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`CreateTxError::SubtractFeeIndexOutOfBounds`]: crate::error::CreateTxError::SubtractFeeIndexOutOfBounds
+    /// [`CreateTxError::SubtractFeeBelowDust`]: crate::error::CreateTxError::SubtractFeeBelowDust
+    pub fn subtract_fee_from(
+        &mut self,
+        output_indices: impl IntoIterator<Item = usize>,
+    ) -> &mut Self {
+        self.params.subtract_fee_from.extend(output_indices);
+        self.params.subtract_fee_from.sort_unstable();
+        self.params.subtract_fee_from.dedup();
+        self
+    }
+
     /// Sets the address to *drain* excess coins to.
     ///
     /// Usually, when there are excess coins they are sent to a change address generated by the
@@ -693,7 +1005,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// with [`add_recipient`] (but it is perfectly fine to add recipients as well).
     ///
     /// If you choose not to set any recipients, you should provide the utxos that the
-    /// transaction should spend via [`add_utxos`].
+    /// transaction should spend via [`add_utxos`], or spend them all with [`drain_wallet`].
     ///
     /// # Example
     ///
@@ -760,7 +1072,142 @@ impl<Cs: CoinSelectionAlgorithm> TxBuilder<'_, Cs> {
     /// **WARNING**: To avoid change address reuse you must persist the changes resulting from one
     /// or more calls to this method before closing the wallet. See [`Wallet::reveal_next_address`].
     pub fn finish_with_aux_rand(self, rng: &mut impl RngCore) -> Result<Psbt, CreateTxError> {
-        self.wallet.create_tx(self.coin_selection, self.params, rng)
+        self.wallet
+            .create_tx(self.coin_selection, self.params, rng, None)
+    }
+
+    /// Finish building the transaction, explaining the coin selection.
+    ///
+    /// Uses the thread-local random number generator (rng).
+    ///
+    /// Returns the result of [`finish`] along with a [`CoinSelectionExplanation`], which tells
+    /// for each UTXO of the wallet whether it was selected, or why it couldn't be, and which
+    /// coin selection algorithm chose the inputs. The explanation is also returned when creating
+    /// the transaction fails, for example with [`InsufficientFunds`]. It's empty when the
+    /// transaction fails before coin selection.
+    ///
+    /// [`finish`]: Self::finish
+    /// [`InsufficientFunds`]: crate::wallet::coin_selection::InsufficientFunds
+    ///
+    /// **WARNING**: To avoid change address reuse you must persist the changes resulting from one
+    /// or more calls to this method before closing the wallet. See [`Wallet::reveal_next_address`].
+    #[cfg(feature = "std")]
+    pub fn finish_explained(self) -> (Result<Psbt, CreateTxError>, CoinSelectionExplanation) {
+        self.finish_explained_with_aux_rand(&mut bitcoin::key::rand::thread_rng())
+    }
+
+    /// Finish building the transaction, explaining the coin selection.
+    ///
+    /// Uses a provided random number generator (rng). See [`finish_explained`] for details.
+    ///
+    /// [`finish_explained`]: Self::finish_explained
+    pub fn finish_explained_with_aux_rand(
+        self,
+        rng: &mut impl RngCore,
+    ) -> (Result<Psbt, CreateTxError>, CoinSelectionExplanation) {
+        let mut explanation = CoinSelectionExplanation::default();
+        let psbt = self.wallet.create_tx(
+            self.coin_selection,
+            self.params,
+            rng,
+            Some(&mut explanation),
+        );
+        (psbt, explanation)
+    }
+
+    /// Finish building the transaction as a [BIP370] (version 2) PSBT.
+    ///
+    /// Uses the thread-local random number generator (rng).
+    ///
+    /// `modifiable` tells whether other parties can add inputs and outputs to the PSBT, see
+    /// [`PsbtV2::set_modifiable`]. The lock time of the transaction is used as the fallback lock
+    /// time.
+    ///
+    /// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+    ///
+    /// **WARNING**: To avoid change address reuse you must persist the changes resulting from one
+    /// or more calls to this method before closing the wallet. See [`Wallet::reveal_next_address`].
+    #[cfg(feature = "std")]
+    pub fn finish_v2(self, modifiable: TxModifiable) -> Result<PsbtV2, CreateTxError> {
+        self.finish_v2_with_aux_rand(modifiable, &mut bitcoin::key::rand::thread_rng())
+    }
+
+    /// Finish building the transaction as a [BIP370] (version 2) PSBT.
+    ///
+    /// Uses a provided random number generator (rng). See [`finish_v2`] for details.
+    ///
+    /// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+    /// [`finish_v2`]: Self::finish_v2
+    pub fn finish_v2_with_aux_rand(
+        self,
+        modifiable: TxModifiable,
+        rng: &mut impl RngCore,
+    ) -> Result<PsbtV2, CreateTxError> {
+        let mut psbt = PsbtV2::from_v0(self.finish_with_aux_rand(rng)?);
+        psbt.set_modifiable(modifiable);
+        Ok(psbt)
+    }
+
+    /// Plan the transaction without creating a PSBT or changing the wallet state.
+    ///
+    /// The coins are selected exactly like [`finish`] would, but the change address isn't
+    /// revealed and nothing is staged in the wallet. This is useful to preview the fee of a
+    /// transaction before deciding to create it.
+    ///
+    /// Uses the thread-local random number generator (rng), so the selected coins and the order
+    /// of the inputs and outputs can differ from those of a later call to [`finish`].
+    ///
+    /// [`finish`]: Self::finish
+    #[cfg(feature = "std")]
+    pub fn plan(&self) -> Result<TxPlan, CreateTxError> {
+        self.plan_with_aux_rand(&mut bitcoin::key::rand::thread_rng())
+    }
+
+    /// Plan the transaction without creating a PSBT or changing the wallet state.
+    ///
+    /// Uses a provided random number generator (rng). See [`plan`] for details.
+    ///
+    /// [`plan`]: Self::plan
+    pub fn plan_with_aux_rand(&self, rng: &mut impl RngCore) -> Result<TxPlan, CreateTxError> {
+        self.wallet
+            .plan_tx(&self.coin_selection, &self.params, rng, None)
+            .map(|(plan, _)| plan)
+    }
+}
+
+/// A transaction planned with [`TxBuilder::plan`], without creating a PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxPlan {
+    /// The unsigned transaction, with its inputs and outputs in their final order
+    pub unsigned_tx: Transaction,
+    /// The UTXOs spent by the transaction, in the order of its inputs
+    pub utxos: Vec<Utxo>,
+    /// Index of the change output, or of the output set with [`TxBuilder::drain_to`], if one is
+    /// created
+    ///
+    /// When the change is split into several outputs by a [`ChangeStrategy`], this is the last
+    /// one, holding what's left of the change. The output of [`TxBuilder::add_max_recipient`] is
+    /// a payment, not change.
+    pub change_vout: Option<usize>,
+    /// Indexes of all the change outputs, including [`change_vout`](Self::change_vout)
+    pub change_vouts: Vec<usize>,
+    /// Estimated weight of the transaction once signed
+    pub weight: Weight,
+    /// Fee paid by the transaction
+    pub fee: Amount,
+    /// Silent payment addresses paid by the transaction, with the index of their output
+    pub silent_payments: Vec<(usize, SilentPaymentAddress)>,
+}
+
+impl TxPlan {
+    /// Whether the transaction has a change output.
+    pub fn has_change(&self) -> bool {
+        self.change_vout.is_some()
+    }
+
+    /// Estimated fee rate of the transaction once signed.
+    pub fn fee_rate(&self) -> FeeRate {
+        self.fee / self.weight
     }
 }
 
@@ -799,6 +1246,8 @@ pub enum AddForeignUtxoError {
     InvalidOutpoint(OutPoint),
     /// Foreign utxo missing witness_utxo or non_witness_utxo
     MissingUtxo,
+    /// The output added with [`TxBuilder::add_anchor_input`] is not a pay-to-anchor output
+    NotPayToAnchor(OutPoint),
 }
 
 impl fmt::Display for AddForeignUtxoError {
@@ -818,6 +1267,9 @@ impl fmt::Display for AddForeignUtxoError {
                 outpoint.txid, outpoint.vout,
             ),
             Self::MissingUtxo => write!(f, "Foreign utxo missing witness_utxo or non_witness_utxo"),
+            Self::NotPayToAnchor(outpoint) => {
+                write!(f, "Output is not a pay-to-anchor output: {outpoint}")
+            }
         }
     }
 }
@@ -859,6 +1311,36 @@ impl core::fmt::Debug for TxOrdering {
     }
 }
 
+impl Serialize for TxOrdering {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TxOrdering::Shuffle => serializer.serialize_unit_variant("TxOrdering", 0, "Shuffle"),
+            TxOrdering::Untouched => {
+                serializer.serialize_unit_variant("TxOrdering", 1, "Untouched")
+            }
+            TxOrdering::Custom { .. } => Err(serde::ser::Error::custom(
+                "a custom ordering can't be serialized",
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TxOrdering {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "TxOrdering")]
+        enum SerializableOrdering {
+            Shuffle,
+            Untouched,
+        }
+
+        Ok(match SerializableOrdering::deserialize(deserializer)? {
+            SerializableOrdering::Shuffle => TxOrdering::Shuffle,
+            SerializableOrdering::Untouched => TxOrdering::Untouched,
+        })
+    }
+}
+
 impl TxOrdering {
     /// Sort transaction inputs and outputs by [`TxOrdering`] variant.
     ///
@@ -889,8 +1371,258 @@ impl TxOrdering {
     }
 }
 
+type ChangeSplit = dyn (Fn(&ChangeContext) -> Vec<Amount>) + Send + Sync;
+
+/// What a [`ChangeStrategy`] knows about a transaction when splitting its change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeContext {
+    /// Change left for a single change output, once its fee is paid
+    pub amount: Amount,
+    /// Fee paid for each additional change output
+    pub output_fee: Amount,
+    /// Smallest value of a change output that isn't dust
+    pub dust_threshold: Amount,
+    /// Amounts paid to the recipients of the transaction
+    pub payments: Vec<Amount>,
+    /// Values of the wallet UTXOs the transaction leaves unspent
+    pub unspent: Vec<Amount>,
+}
+
+/// Strategy to split the change of a transaction into one or more outputs
+///
+/// Every change output but the first one pays for its own weight, and no change output is created
+/// below the dust limit. The strategy only applies to the change sent back to the wallet, not to
+/// an output set with [`TxBuilder::drain_to`].
+#[derive(Clone, Default)]
+pub enum ChangeStrategy {
+    /// A single change output (default)
+    #[default]
+    Single,
+    /// Split the change into outputs of the given denominations
+    ///
+    /// As many outputs of the largest denomination as possible are created, then of the next
+    /// largest one, and so on. What's left of the change goes into a last output.
+    Denominations {
+        /// Values of the change outputs
+        denominations: Vec<Amount>,
+        /// Maximum number of change outputs, including the last one
+        max_outputs: usize,
+    },
+    /// Split the change into equal outputs worth at least the largest payment
+    ///
+    /// This makes it harder to tell the payment and the change apart by their values.
+    MatchPayment {
+        /// Maximum number of change outputs
+        max_outputs: usize,
+    },
+    /// Keep a pool of wallet UTXOs worth at least a given value
+    ///
+    /// Change outputs of `value` are created until the wallet has `count` unspent outputs worth
+    /// at least `value`, counting the ones the transaction leaves unspent. What's left of the
+    /// change goes into a last output.
+    MinPool {
+        /// Value of the UTXOs of the pool
+        value: Amount,
+        /// Number of UTXOs of the pool
+        count: usize,
+    },
+    /// Provide a custom function returning the values of the change outputs
+    ///
+    /// The values, plus the fee of every output but the first one, must not exceed
+    /// [`ChangeContext::amount`], and none of them may be below
+    /// [`ChangeContext::dust_threshold`]. What's left of the change goes to the miners.
+    Custom(Arc<ChangeSplit>),
+}
+
+impl core::fmt::Debug for ChangeStrategy {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ChangeStrategy::Single => write!(f, "Single"),
+            ChangeStrategy::Denominations {
+                denominations,
+                max_outputs,
+            } => f
+                .debug_struct("Denominations")
+                .field("denominations", denominations)
+                .field("max_outputs", max_outputs)
+                .finish(),
+            ChangeStrategy::MatchPayment { max_outputs } => f
+                .debug_struct("MatchPayment")
+                .field("max_outputs", max_outputs)
+                .finish(),
+            ChangeStrategy::MinPool { value, count } => f
+                .debug_struct("MinPool")
+                .field("value", value)
+                .field("count", count)
+                .finish(),
+            ChangeStrategy::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// [`ChangeStrategy`] without its custom variant, to derive its (de)serialization.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ChangeStrategy")]
+enum SerializableChangeStrategy {
+    Single,
+    Denominations {
+        denominations: Vec<Amount>,
+        max_outputs: usize,
+    },
+    MatchPayment {
+        max_outputs: usize,
+    },
+    MinPool {
+        value: Amount,
+        count: usize,
+    },
+}
+
+impl Serialize for ChangeStrategy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let strategy = match self.clone() {
+            ChangeStrategy::Single => SerializableChangeStrategy::Single,
+            ChangeStrategy::Denominations {
+                denominations,
+                max_outputs,
+            } => SerializableChangeStrategy::Denominations {
+                denominations,
+                max_outputs,
+            },
+            ChangeStrategy::MatchPayment { max_outputs } => {
+                SerializableChangeStrategy::MatchPayment { max_outputs }
+            }
+            ChangeStrategy::MinPool { value, count } => {
+                SerializableChangeStrategy::MinPool { value, count }
+            }
+            ChangeStrategy::Custom(_) => {
+                return Err(serde::ser::Error::custom(
+                    "a custom change strategy can't be serialized",
+                ))
+            }
+        };
+        strategy.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChangeStrategy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(
+            match SerializableChangeStrategy::deserialize(deserializer)? {
+                SerializableChangeStrategy::Single => ChangeStrategy::Single,
+                SerializableChangeStrategy::Denominations {
+                    denominations,
+                    max_outputs,
+                } => ChangeStrategy::Denominations {
+                    denominations,
+                    max_outputs,
+                },
+                SerializableChangeStrategy::MatchPayment { max_outputs } => {
+                    ChangeStrategy::MatchPayment { max_outputs }
+                }
+                SerializableChangeStrategy::MinPool { value, count } => {
+                    ChangeStrategy::MinPool { value, count }
+                }
+            },
+        )
+    }
+}
+
+impl ChangeStrategy {
+    /// Split the change described by `context` into the values of the change outputs.
+    ///
+    /// Returns `None` if a custom strategy returns an invalid split.
+    pub(crate) fn split(&self, context: &ChangeContext) -> Option<Vec<Amount>> {
+        let ChangeContext {
+            amount,
+            output_fee,
+            dust_threshold,
+            ..
+        } = *context;
+        // Split off outputs of the given values, skipping the ones that don't leave enough for a
+        // last output.
+        let split_off = |values: &mut dyn Iterator<Item = Amount>, max_outputs: usize| {
+            let mut outputs = vec![];
+            let mut left = amount;
+            for value in values {
+                if outputs.len() + 1 >= max_outputs {
+                    break;
+                }
+                match left.checked_sub(value + output_fee) {
+                    Some(rest) if value >= dust_threshold && rest >= dust_threshold => {
+                        outputs.push(value);
+                        left = rest;
+                    }
+                    _ => continue,
+                }
+            }
+            outputs.push(left);
+            outputs
+        };
+
+        let outputs = match self {
+            ChangeStrategy::Single => vec![amount],
+            ChangeStrategy::Denominations {
+                denominations,
+                max_outputs,
+            } => {
+                let mut denominations = denominations.clone();
+                denominations.sort_unstable_by(|a, b| b.cmp(a));
+                let mut values = denominations
+                    .into_iter()
+                    .flat_map(|value| core::iter::repeat_n(value, *max_outputs));
+                split_off(&mut values, *max_outputs)
+            }
+            ChangeStrategy::MatchPayment { max_outputs } => {
+                let payment = context.payments.iter().max().copied().unwrap_or_default();
+                // The largest number of outputs worth at least the payment each.
+                let count = (1..=(*max_outputs).max(1))
+                    .take_while(|&count| {
+                        let total = amount
+                            .checked_sub(output_fee * (count as u64 - 1))
+                            .unwrap_or_default();
+                        total / count as u64 >= payment.max(dust_threshold)
+                    })
+                    .last()
+                    .unwrap_or(1);
+                let total = amount - output_fee * (count as u64 - 1);
+                let value = total / count as u64;
+                let mut outputs = vec![value; count];
+                outputs[0] += total - value * count as u64;
+                outputs
+            }
+            ChangeStrategy::MinPool { value, count } => {
+                let pool = context
+                    .unspent
+                    .iter()
+                    .filter(|unspent| *unspent >= value)
+                    .count();
+                let missing = count.saturating_sub(pool);
+                split_off(&mut core::iter::repeat_n(*value, missing), missing + 1)
+            }
+            ChangeStrategy::Custom(split) => {
+                let outputs = split(context);
+                let total = outputs
+                    .iter()
+                    .try_fold(Amount::ZERO, |total, value| total.checked_add(*value))?
+                    .checked_add(output_fee * (outputs.len() as u64).saturating_sub(1))?;
+                if outputs.is_empty()
+                    || total > amount
+                    || outputs.iter().any(|value| *value < dust_threshold)
+                {
+                    return None;
+                }
+                outputs
+            }
+        };
+        Some(outputs)
+    }
+}
+
 /// Policy regarding the use of change outputs when creating a transaction
-#[derive(Default, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Clone, Copy)]
+#[derive(
+    Default, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Clone, Copy, Serialize, Deserialize,
+)]
 pub enum ChangeSpendPolicy {
     /// Use both change and non-change outputs (default)
     #[default]
@@ -911,6 +1643,39 @@ impl ChangeSpendPolicy {
     }
 }
 
+/// Filters on the UTXOs considered by [`Wallet::max_spendable`].
+///
+/// Locked outpoints and immature coinbase outputs are never considered.
+///
+/// [`Wallet::max_spendable`]: crate::Wallet::max_spendable
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UtxoFilters {
+    /// Whether change outputs can be spent
+    pub change_policy: ChangeSpendPolicy,
+    /// Minimum number of confirmations of the UTXOs, `0` includes unconfirmed ones
+    pub min_confirmations: u32,
+    /// UTXOs which must not be spent
+    pub unspendable: HashSet<OutPoint>,
+    /// Policy path of the external descriptor, see [`TxBuilder::policy_path`]
+    pub external_policy_path: Option<BTreeMap<String, Vec<usize>>>,
+    /// Policy path of the internal descriptor, see [`TxBuilder::policy_path`]
+    pub internal_policy_path: Option<BTreeMap<String, Vec<usize>>>,
+}
+
+impl UtxoFilters {
+    /// The parameters of a transaction spending the UTXOs accepted by the filters.
+    pub(crate) fn tx_params(&self) -> TxParams {
+        TxParams {
+            change_policy: self.change_policy,
+            unspendable: self.unspendable.clone(),
+            min_confirmations: self.min_confirmations,
+            external_policy_path: self.external_policy_path.clone(),
+            internal_policy_path: self.internal_policy_path.clone(),
+            ..Default::default()
+        }
+    }
+}
+
 #[cfg_attr(coverage_nightly, coverage(off))]
 #[cfg(test)]
 mod test {
@@ -1151,6 +1916,98 @@ mod test {
         assert_eq!(filtered[0].keychain, KeychainKind::Internal);
     }
 
+    fn change_context(amount: u64, payments: &[u64], unspent: &[u64]) -> ChangeContext {
+        ChangeContext {
+            amount: Amount::from_sat(amount),
+            output_fee: Amount::from_sat(100),
+            dust_threshold: Amount::from_sat(294),
+            payments: payments.iter().copied().map(Amount::from_sat).collect(),
+            unspent: unspent.iter().copied().map(Amount::from_sat).collect(),
+        }
+    }
+
+    fn sats(values: &[u64]) -> Vec<Amount> {
+        values.iter().copied().map(Amount::from_sat).collect()
+    }
+
+    #[test]
+    fn test_change_strategy_single() {
+        let context = change_context(35_500, &[10_000], &[]);
+        assert_eq!(
+            ChangeStrategy::Single.split(&context),
+            Some(sats(&[35_500]))
+        );
+    }
+
+    #[test]
+    fn test_change_strategy_denominations() {
+        let context = change_context(35_500, &[10_000], &[]);
+        let strategy = |max_outputs| ChangeStrategy::Denominations {
+            denominations: sats(&[5_000, 10_000]),
+            max_outputs,
+        };
+
+        // A 5_000 output would leave a dust remainder.
+        assert_eq!(
+            strategy(10).split(&context),
+            Some(sats(&[10_000, 10_000, 10_000, 5_200]))
+        );
+        assert_eq!(strategy(2).split(&context), Some(sats(&[10_000, 25_400])));
+        assert_eq!(strategy(1).split(&context), Some(sats(&[35_500])));
+        // The smaller denomination is used when the larger one doesn't fit.
+        let context = change_context(10_200, &[10_000], &[]);
+        assert_eq!(strategy(3).split(&context), Some(sats(&[5_000, 5_100])));
+    }
+
+    #[test]
+    fn test_change_strategy_match_payment() {
+        let strategy = ChangeStrategy::MatchPayment { max_outputs: 5 };
+
+        let context = change_context(100_000, &[30_000, 1_000], &[]);
+        assert_eq!(
+            strategy.split(&context),
+            Some(sats(&[33_268, 33_266, 33_266]))
+        );
+        // The change is smaller than the payment.
+        let context = change_context(20_000, &[30_000], &[]);
+        assert_eq!(strategy.split(&context), Some(sats(&[20_000])));
+        let strategy = ChangeStrategy::MatchPayment { max_outputs: 2 };
+        let context = change_context(100_000, &[30_000], &[]);
+        assert_eq!(strategy.split(&context), Some(sats(&[49_950, 49_950])));
+    }
+
+    #[test]
+    fn test_change_strategy_min_pool() {
+        let strategy = ChangeStrategy::MinPool {
+            value: Amount::from_sat(10_000),
+            count: 3,
+        };
+
+        let context = change_context(25_000, &[], &[50_000, 1_000]);
+        assert_eq!(
+            strategy.split(&context),
+            Some(sats(&[10_000, 10_000, 4_800]))
+        );
+        let context = change_context(25_000, &[], &[50_000, 10_000, 20_000]);
+        assert_eq!(strategy.split(&context), Some(sats(&[25_000])));
+    }
+
+    #[test]
+    fn test_change_strategy_custom() {
+        let context = change_context(25_000, &[], &[]);
+        let strategy = |values: Vec<u64>| {
+            ChangeStrategy::Custom(Arc::new(move |_: &ChangeContext| sats(&values)))
+        };
+
+        assert_eq!(
+            strategy(vec![12_000, 12_000]).split(&context),
+            Some(sats(&[12_000, 12_000]))
+        );
+        assert_eq!(strategy(vec![]).split(&context), None);
+        assert_eq!(strategy(vec![24_950, 100]).split(&context), None);
+        assert_eq!(strategy(vec![12_500, 12_500]).split(&context), None);
+    }
+
     #[test]
     fn test_exclude_unconfirmed() {
         use bdk_chain::BlockId;
@@ -1300,17 +2157,13 @@ mod test {
     #[test]
     fn not_duplicated_utxos_in_required_list() {
         let (mut wallet1, _) = get_funded_wallet_wpkh();
-        let utxo1 @ LocalOutput { outpoint, .. } = wallet1.list_unspent().next().unwrap();
+        let LocalOutput { outpoint, .. } = wallet1.list_unspent().next().unwrap();
         let mut builder = wallet1.build_tx();
-        let fake_weighted_utxo = WeightedUtxo {
-            satisfaction_weight: Weight::from_wu(107),
-            utxo: Utxo::Local(utxo1.clone()),
-        };
 
         for _ in 0..3 {
             builder.add_utxo(outpoint).expect("should add");
         }
-        assert_eq!(vec![fake_weighted_utxo], builder.params.utxos);
+        assert_eq!(vec![RequiredUtxo::Local(outpoint)], builder.params.utxos);
     }
 
     // This test demonstrates that `add_utxo` only considers the final insertion.
@@ -1407,10 +2260,11 @@ mod test {
             .is_ok());
 
         assert_eq!(builder.params.utxos.len(), 1);
-        assert_eq!(
-            builder.params.utxos[0].satisfaction_weight,
-            modified_satisfaction_weight
-        );
+        assert!(matches!(
+            builder.params.utxos[0],
+            RequiredUtxo::Foreign { satisfaction_weight, .. }
+                if satisfaction_weight == modified_satisfaction_weight
+        ));
     }
 
     // Test that local outputs have precedence over utxos added via `add_foreign_utxo`
@@ -1425,7 +2279,7 @@ mod test {
         let mut builder = wallet.build_tx();
         builder.add_utxo(outpoint).unwrap();
 
-        assert_eq!(builder.params.utxos[0].utxo.outpoint(), outpoint);
+        assert_eq!(builder.params.utxos[0].outpoint(), outpoint);
 
         builder
             .add_foreign_utxo(
@@ -1439,10 +2293,7 @@ mod test {
             .unwrap();
 
         assert_eq!(builder.params.utxos.len(), 1);
-        assert!(matches!(
-            &builder.params.utxos[0].utxo,
-            Utxo::Local(output) if output.outpoint == outpoint,
-        ));
+        assert_eq!(builder.params.utxos[0], RequiredUtxo::Local(outpoint));
 
         // case 2: add local after foreign, expect foreign is removed
         builder.params = TxParams::default();
@@ -1458,13 +2309,11 @@ mod test {
             )
             .unwrap();
 
-        assert_eq!(builder.params.utxos[0].utxo.outpoint(), outpoint);
+        assert_eq!(builder.params.utxos[0].outpoint(), outpoint);
 
         builder.add_utxo(outpoint).unwrap();
 
         assert_eq!(builder.params.utxos.len(), 1);
-        assert!(
-            matches!(&builder.params.utxos[0].utxo, Utxo::Local(output) if output.outpoint == outpoint)
-        );
+        assert_eq!(builder.params.utxos[0], RequiredUtxo::Local(outpoint));
     }
 }
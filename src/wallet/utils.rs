@@ -12,7 +12,8 @@
 use alloc::sync::Arc;
 use bitcoin::secp256k1::{All, Secp256k1};
 use bitcoin::{
-    absolute, relative, Amount, FeeRate, Script, Sequence, SignedAmount, Transaction, Txid,
+    absolute, relative, Amount, FeeRate, Script, ScriptBuf, Sequence, SignedAmount, Transaction,
+    Txid,
 };
 use chain::{ChainPosition, ConfirmationBlockTime};
 use miniscript::{MiniscriptKey, Satisfier, ToPublicKey};
@@ -55,6 +56,11 @@ impl After {
     }
 }
 
+/// Whether `script` is a pay-to-anchor (P2A) output script, which is spent with an empty witness.
+pub(crate) fn is_p2a(script: &Script) -> bool {
+    *script == *ScriptBuf::new_p2a()
+}
+
 pub(crate) fn check_nsequence_rbf(sequence: Sequence, csv: Sequence) -> bool {
     // The nSequence value must enable relative timelocks
     if !sequence.is_relative_lock_time() {
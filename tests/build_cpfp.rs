@@ -0,0 +1,248 @@
+use std::str::FromStr;
+
+use assert_matches::assert_matches;
+use bdk_chain::ConfirmationBlockTime;
+use bdk_wallet::error::BuildCpfpError;
+use bdk_wallet::test_utils::*;
+use bdk_wallet::tx_builder::AddForeignUtxoError;
+use bdk_wallet::{KeychainKind, SignOptions};
+use bitcoin::{
+    absolute, hashes::Hash, psbt, transaction, Address, Amount, FeeRate, OutPoint, ScriptBuf,
+    Transaction, TxIn, TxOut, Txid,
+};
+
+mod common;
+
+#[test]
+fn test_cpfp_incoming_tx() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent_txid = common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(30_000)],
+        Amount::from_sat(100),
+    )
+    .compute_txid();
+    let target = FeeRate::from_sat_per_vb_u32(10);
+
+    let mut psbt = wallet
+        .build_cpfp(parent_txid, target)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let finalized = wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+    assert!(finalized);
+    let child = psbt.extract_tx().expect("failed to extract tx");
+
+    assert_eq!(child.input.len(), 1);
+    assert_eq!(child.input[0].previous_output.txid, parent_txid);
+    assert_eq!(child.output.len(), 1);
+    assert!(wallet.is_mine(child.output[0].script_pubkey.clone()));
+    assert!(common::package_fee_rate(&wallet, &child, &[parent_txid]) >= target);
+    // The child alone pays more than the target to make up for the parent.
+    assert!(wallet.calculate_fee_rate(&child).unwrap() > target);
+}
+
+#[test]
+fn test_cpfp_reveals_change_on_finish() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent_txid = common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(30_000)],
+        Amount::from_sat(100),
+    )
+    .compute_txid();
+    let target = FeeRate::from_sat_per_vb_u32(10);
+    let change_index = wallet.derivation_index(KeychainKind::Internal);
+
+    // Dropping the builder leaves the change keychain untouched.
+    drop(wallet.build_cpfp(parent_txid, target).unwrap());
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::Internal),
+        change_index
+    );
+
+    let psbt = wallet
+        .build_cpfp(parent_txid, target)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let (keychain, index) = wallet
+        .derivation_of_spk(psbt.unsigned_tx.output[0].script_pubkey.clone())
+        .unwrap();
+    assert_eq!(keychain, KeychainKind::Internal);
+    assert_eq!(wallet.derivation_index(KeychainKind::Internal), Some(index));
+}
+
+#[test]
+fn test_cpfp_pays_for_unconfirmed_ancestors() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let grandparent_txid = common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(30_000)],
+        Amount::from_sat(100),
+    )
+    .compute_txid();
+
+    // The parent spends the unconfirmed grandparent output at a low fee rate.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_utxo(OutPoint::new(grandparent_txid, 0))
+        .unwrap()
+        .manually_selected_only()
+        .add_recipient(
+            Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+                .unwrap()
+                .assume_checked()
+                .script_pubkey(),
+            Amount::from_sat(10_000),
+        );
+    let parent = builder.finish().unwrap().unsigned_tx;
+    let parent_txid = parent.compute_txid();
+    insert_tx(&mut wallet, parent);
+
+    let target = FeeRate::from_sat_per_vb_u32(20);
+    let mut psbt = wallet
+        .build_cpfp(parent_txid, target)
+        .unwrap()
+        .finish()
+        .unwrap();
+    wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+    let child = psbt.extract_tx().expect("failed to extract tx");
+
+    assert!(child
+        .input
+        .iter()
+        .any(|txin| txin.previous_output.txid == parent_txid));
+    assert!(common::package_fee_rate(&wallet, &child, &[grandparent_txid, parent_txid]) >= target);
+}
+
+#[test]
+fn test_cpfp_no_spendable_output() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder.drain_wallet().drain_to(addr.script_pubkey());
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+
+    let res = wallet.build_cpfp(txid, FeeRate::from_sat_per_vb_u32(10));
+    assert_matches!(res, Err(BuildCpfpError::NoSpendableOutput(id)) if id == txid);
+}
+
+#[test]
+fn test_cpfp_locked_output_is_not_spendable() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent_txid = common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(30_000)],
+        Amount::from_sat(100),
+    )
+    .compute_txid();
+    wallet.lock_outpoint(OutPoint::new(parent_txid, 0));
+
+    let res = wallet.build_cpfp(parent_txid, FeeRate::from_sat_per_vb_u32(10));
+    assert_matches!(res, Err(BuildCpfpError::NoSpendableOutput(_)));
+}
+
+#[test]
+fn test_cpfp_confirmed_tx() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent_txid = common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(30_000)],
+        Amount::from_sat(100),
+    )
+    .compute_txid();
+    let anchor = ConfirmationBlockTime {
+        block_id: wallet.latest_checkpoint().block_id(),
+        confirmation_time: 42_000,
+    };
+    insert_anchor(&mut wallet, parent_txid, anchor);
+
+    let res = wallet.build_cpfp(parent_txid, FeeRate::from_sat_per_vb_u32(10));
+    assert_matches!(res, Err(BuildCpfpError::TransactionConfirmed(id)) if id == parent_txid);
+
+    let res = wallet.build_cpfp(Txid::all_zeros(), FeeRate::from_sat_per_vb_u32(10));
+    assert_matches!(res, Err(BuildCpfpError::TransactionNotFound(_)));
+}
+
+#[test]
+fn test_cpfp_keyless_anchor() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let foreign_prevout = OutPoint::new(Txid::all_zeros(), 0);
+    let foreign_spk = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    wallet.insert_txout(
+        foreign_prevout,
+        TxOut {
+            value: Amount::from_sat(30_000),
+            script_pubkey: foreign_spk.clone(),
+        },
+    );
+    // A zero-fee transaction we co-signed, paying nothing to the wallet but with an anchor.
+    let parent = Transaction {
+        version: transaction::Version(3),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: foreign_prevout,
+            ..Default::default()
+        }],
+        output: vec![
+            TxOut {
+                value: Amount::from_sat(30_000),
+                script_pubkey: foreign_spk,
+            },
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::new_p2a(),
+            },
+        ],
+    };
+    let parent_txid = parent.compute_txid();
+    insert_tx(&mut wallet, parent);
+
+    let target = FeeRate::from_sat_per_vb_u32(10);
+    let mut psbt = wallet
+        .build_cpfp(parent_txid, target)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let finalized = wallet.sign(&mut psbt, SignOptions::default()).unwrap();
+    assert!(finalized);
+    let child = psbt.extract_tx().expect("failed to extract tx");
+
+    let anchor = child
+        .input
+        .iter()
+        .find(|txin| txin.previous_output == OutPoint::new(parent_txid, 1))
+        .expect("the child must spend the anchor");
+    assert!(anchor.witness.is_empty());
+    assert!(anchor.script_sig.is_empty());
+    assert_eq!(child.version, transaction::Version(3));
+    assert!(common::package_fee_rate(&wallet, &child, &[parent_txid]) >= target);
+}
+
+#[test]
+fn test_add_anchor_input_not_p2a() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+    let psbt_input = psbt::Input {
+        witness_utxo: Some(TxOut {
+            value: Amount::from_sat(330),
+            script_pubkey: wallet
+                .next_unused_address(KeychainKind::External)
+                .script_pubkey(),
+        }),
+        ..Default::default()
+    };
+    let mut builder = wallet.build_tx();
+    assert_matches!(
+        builder.add_anchor_input(outpoint, psbt_input),
+        Err(AddForeignUtxoError::NotPayToAnchor(op)) if op == outpoint
+    );
+}
@@ -3,8 +3,9 @@ use std::str::FromStr;
 use assert_matches::assert_matches;
 use bdk_chain::{ChainPosition, ConfirmationBlockTime};
 use bdk_wallet::coin_selection::LargestFirstCoinSelection;
-use bdk_wallet::error::CreateTxError;
+use bdk_wallet::error::{CheckReplacementError, CreateTxError};
 use bdk_wallet::psbt::PsbtUtils;
+use bdk_wallet::replacement::ReplacementViolation;
 use bdk_wallet::test_utils::*;
 use bdk_wallet::KeychainKind;
 use bitcoin::{
@@ -992,3 +993,425 @@ fn test_bump_fee_pay_to_anchor_foreign_utxo() {
     let tx = &psbt.unsigned_tx;
     assert!(tx.input.iter().any(|txin| txin.previous_output == outpoint));
 }
+
+#[test]
+fn test_bump_fee_merge_transactions() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(40_000));
+    let addr1 = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let addr2 = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked();
+
+    let mut builder = wallet.build_tx().coin_selection(LargestFirstCoinSelection);
+    builder.add_recipient(addr1.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder.finish().unwrap();
+    let fee1 = check_fee!(wallet, psbt);
+    let tx1 = psbt.extract_tx().expect("failed to extract tx");
+    let txid1 = tx1.compute_txid();
+    insert_tx(&mut wallet, tx1.clone());
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr2.script_pubkey(), Amount::from_sat(20_000))
+        .exclude_unconfirmed();
+    let psbt = builder.finish().unwrap();
+    let fee2 = check_fee!(wallet, psbt);
+    let tx2 = psbt.extract_tx().expect("failed to extract tx");
+    let txid2 = tx2.compute_txid();
+    insert_tx(&mut wallet, tx2.clone());
+
+    let feerate = FeeRate::from_sat_per_vb_u32(5);
+    let mut builder = wallet.build_batch_fee_bump(&[txid1, txid2]).unwrap();
+    builder.fee_rate(feerate);
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+    assert!(fee > fee1 + fee2);
+
+    let tx = &psbt.unsigned_tx;
+    for txin in tx1.input.iter().chain(&tx2.input) {
+        assert!(tx
+            .input
+            .iter()
+            .any(|input| input.previous_output == txin.previous_output));
+    }
+    assert_eq!(tx.input.len(), 2);
+    assert_eq!(tx.output.len(), 3);
+    for (addr, value) in [(&addr1, 10_000), (&addr2, 20_000)] {
+        assert_eq!(
+            tx.output
+                .iter()
+                .find(|txout| txout.script_pubkey == addr.script_pubkey())
+                .unwrap()
+                .value,
+            Amount::from_sat(value)
+        );
+    }
+
+    assert_fee_rate!(psbt, fee, feerate, @add_signature);
+}
+
+#[test]
+fn test_bump_fee_merge_chained_transactions() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr1 = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let addr2 = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked();
+
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr1.script_pubkey(), Amount::from_sat(10_000));
+    let tx1 = builder.finish().unwrap().unsigned_tx;
+    let txid1 = tx1.compute_txid();
+    insert_tx(&mut wallet, tx1.clone());
+
+    // The second tx spends the unconfirmed change of the first one.
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr2.script_pubkey(), Amount::from_sat(20_000));
+    let tx2 = builder.finish().unwrap().unsigned_tx;
+    let txid2 = tx2.compute_txid();
+    assert!(tx2
+        .input
+        .iter()
+        .all(|txin| txin.previous_output.txid == txid1));
+    insert_tx(&mut wallet, tx2);
+
+    let mut builder = wallet.build_batch_fee_bump(&[txid1, txid2]).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb_u32(5));
+    let psbt = builder.finish().unwrap();
+    check_fee!(wallet, psbt);
+
+    let tx = &psbt.unsigned_tx;
+    assert_eq!(tx.input.len(), 1);
+    assert_eq!(tx.input[0].previous_output, tx1.input[0].previous_output);
+    assert!(tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == addr1.script_pubkey()));
+    assert!(tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == addr2.script_pubkey()));
+}
+
+#[test]
+fn test_bump_fee_merge_pays_absolute_fee() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+
+    // Each tx spends the change of the previous one.
+    let mut txids = vec![];
+    for _ in 0..2 {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(5));
+        let tx = builder.finish().unwrap().unsigned_tx;
+        txids.push(tx.compute_txid());
+        insert_tx(&mut wallet, tx);
+    }
+
+    let previous_fee: Amount = txids
+        .iter()
+        .map(|&txid| {
+            let tx = wallet.get_tx(txid).unwrap().tx_node.tx;
+            wallet.calculate_fee(&tx).unwrap()
+        })
+        .sum();
+
+    // The fee rate is high enough, but the merged tx is much smaller than the two original
+    // ones so the fee rate alone doesn't pay as much as they did in total.
+    let mut builder = wallet.build_batch_fee_bump(&txids).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_kwu(1875)); // 7.5 sat/vb
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    assert_eq!(psbt.unsigned_tx.output.len(), 3);
+
+    let fee = check_fee!(wallet, psbt);
+    assert!(fee > previous_fee);
+    assert!(wallet.check_replacement(&psbt).unwrap().is_valid());
+}
+
+#[test]
+fn test_bump_fee_merge_mixed_versions() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(40_000));
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+
+    let mut txids = vec![];
+    for truc in [false, true] {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+            .exclude_unconfirmed();
+        if truc {
+            builder.truc();
+        }
+        let tx = builder.finish().unwrap().unsigned_tx;
+        txids.push(tx.compute_txid());
+        insert_tx(&mut wallet, tx);
+    }
+
+    assert_matches!(
+        wallet.build_batch_fee_bump(&txids),
+        Err(bdk_wallet::error::BuildFeeBumpError::MixedVersions)
+    );
+}
+
+#[test]
+fn test_bump_fee_merge_no_transactions() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    assert_matches!(
+        wallet.build_batch_fee_bump(&[]),
+        Err(bdk_wallet::error::BuildFeeBumpError::NoTransactions)
+    );
+}
+
+#[test]
+fn test_cancel_tx() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(2));
+    let psbt = builder.finish().unwrap();
+    let original_fee = check_fee!(wallet, psbt);
+    let original_fee_rate = psbt.fee_rate().unwrap();
+
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx.clone());
+
+    let mut psbt = wallet.build_cancel_tx(txid).unwrap().finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+    let fee_rate = psbt.fee_rate().unwrap();
+    let cancel_tx = &psbt.unsigned_tx;
+
+    assert_eq!(
+        cancel_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>(),
+        tx.input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(cancel_tx.output.len(), 1);
+    assert_eq!(
+        wallet
+            .derivation_of_spk(cancel_tx.output[0].script_pubkey.clone())
+            .map(|(keychain, _)| keychain),
+        Some(KeychainKind::Internal)
+    );
+    assert!(fee_rate.to_sat_per_kwu() >= original_fee_rate.to_sat_per_kwu() + 250);
+
+    wallet.sign(&mut psbt, Default::default()).unwrap();
+    let signed = psbt.extract_tx().expect("failed to extract tx");
+    assert!(fee >= original_fee + FeeRate::BROADCAST_MIN * signed.weight());
+}
+
+#[test]
+fn test_cancel_tx_higher_fee_rate() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+
+    let mut builder = wallet.build_cancel_tx(txid).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb_u32(50));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    let fee = check_fee!(wallet, psbt);
+    assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb_u32(50), @add_signature);
+}
+
+#[test]
+#[should_panic(expected = "TransactionConfirmed")]
+fn test_cancel_tx_confirmed() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+
+    let anchor = ConfirmationBlockTime {
+        block_id: wallet.latest_checkpoint().get(42).unwrap().block_id(),
+        confirmation_time: 42_000,
+    };
+    insert_anchor(&mut wallet, txid, anchor);
+
+    wallet.build_cancel_tx(txid).unwrap();
+}
+
+#[test]
+fn test_check_replacement_valid_fee_bump() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx.clone());
+
+    let mut builder = wallet.build_fee_bump(txid).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb_u32(5));
+    let psbt = builder.finish().unwrap();
+
+    let report = wallet.check_replacement(&psbt).unwrap();
+    assert!(report.is_valid(), "{:?}", report.violations);
+    assert_eq!(report.conflicts, vec![txid]);
+    assert_eq!(report.evicted, vec![txid]);
+    assert_eq!(report.fee, psbt.fee_amount().unwrap());
+    assert_eq!(report.evicted_fee, wallet.calculate_fee(&tx).unwrap());
+
+    // The report accounts for the size of the signatures.
+    let mut signed = psbt.clone();
+    wallet.sign(&mut signed, Default::default()).unwrap();
+    let signed_weight = signed.extract_tx().expect("failed to extract tx").weight();
+    assert!(report.weight >= signed_weight);
+    assert!(report.weight - signed_weight <= Weight::from_wu(4));
+}
+
+#[test]
+fn test_check_replacement_not_signaling() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(2))
+        .set_exact_sequence(Sequence(0xFFFFFFFE));
+    let original = builder.finish().unwrap().unsigned_tx;
+
+    // A second spend of the same wallet output, paying a lower fee.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(1));
+    let psbt = builder.finish().unwrap();
+    insert_tx(&mut wallet, original.clone());
+
+    let report = wallet.check_replacement(&psbt).unwrap();
+    assert_eq!(report.conflicts, vec![original.compute_txid()]);
+    assert!(!report.is_valid());
+    assert!(report
+        .violations
+        .contains(&ReplacementViolation::NotSignaling(original.compute_txid())));
+    assert!(report
+        .violations
+        .iter()
+        .any(|violation| matches!(violation, ReplacementViolation::AbsoluteFeeTooLow { .. })));
+    assert!(report
+        .violations
+        .iter()
+        .any(|violation| matches!(violation, ReplacementViolation::IncrementalFeeTooLow { .. })));
+}
+
+#[test]
+fn test_check_replacement_evicts_descendants() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(2));
+    let parent = builder.finish().unwrap().unsigned_tx;
+    let parent_txid = parent.compute_txid();
+    insert_tx(&mut wallet, parent);
+
+    // The child spends the change of the parent at a high fee rate.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(20));
+    let child = builder.finish().unwrap().unsigned_tx;
+    let child_txid = child.compute_txid();
+    insert_tx(&mut wallet, child);
+
+    // An incoming unconfirmed output that no original transaction spends.
+    let unconfirmed = receive_output(&mut wallet, Amount::from_sat(5_000), ReceiveTo::Mempool(0));
+
+    let mut builder = wallet.build_fee_bump(parent_txid).unwrap();
+    builder
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5))
+        .add_utxo(unconfirmed)
+        .unwrap();
+    let psbt = builder.finish().unwrap();
+
+    let report = wallet.check_replacement(&psbt).unwrap();
+    assert_eq!(report.conflicts, vec![parent_txid]);
+    assert_eq!(report.evicted.len(), 2);
+    assert!(report.evicted.contains(&child_txid));
+    assert!(report
+        .violations
+        .contains(&ReplacementViolation::NewUnconfirmedInput(unconfirmed)));
+    // The fee bump pays for the fee of the evicted child too.
+    assert_eq!(report.violations.len(), 1);
+}
+
+#[test]
+fn test_check_replacement_spends_sibling_output() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let parent = builder.finish().unwrap().unsigned_tx;
+    let parent_txid = parent.compute_txid();
+    insert_tx(&mut wallet, parent);
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .add_utxo(OutPoint::new(parent_txid, 0))
+        .unwrap()
+        .manually_selected_only();
+    let original = builder.finish().unwrap().unsigned_tx;
+    let txid = original.compute_txid();
+    insert_tx(&mut wallet, original);
+
+    // The replacement spends the other unconfirmed output of the same parent.
+    let mut builder = wallet.build_fee_bump(txid).unwrap();
+    builder
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5))
+        .add_utxo(OutPoint::new(parent_txid, 1))
+        .unwrap();
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 2);
+
+    let report = wallet.check_replacement(&psbt).unwrap();
+    assert!(report.is_valid(), "{:?}", report.violations);
+}
+
+#[test]
+fn test_check_replacement_no_conflicts() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let psbt = builder.finish().unwrap();
+
+    assert_matches!(
+        wallet.check_replacement(&psbt),
+        Err(CheckReplacementError::NoConflicts)
+    );
+}
@@ -0,0 +1,168 @@
+use assert_matches::assert_matches;
+use bdk_wallet::error::SweepError;
+use bdk_wallet::test_utils::*;
+use bdk_wallet::{KeychainKind, Wallet, SWEEP_DERIVATION_RANGE};
+use bitcoin::{
+    absolute, psbt, transaction, Amount, FeeRate, OutPoint, ScriptBuf, Transaction, TxIn, TxOut,
+};
+
+mod common;
+
+const WIF: &str = "cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW";
+const XPRV: &str = "tprv8ZgxMBicQKsPd3krDUsBAmtnRsK3rb8u5yi1zhQgMhF1tR8MW7xfE4rnrbbsrbPR52e7rKapu6ztw1jXveJSCGHEriUGZV7mCe88duLp5pj";
+
+/// The script pubkey of `descriptor` at derivation index `index`.
+fn script_pubkey(descriptor: &str, index: u32) -> ScriptBuf {
+    let (descriptor, _) = common::parse_descriptor(descriptor);
+    descriptor
+        .at_derivation_index(index)
+        .unwrap()
+        .script_pubkey()
+}
+
+/// A foreign UTXO paying `value` to `script_pubkey`, with the previous transaction if
+/// `non_witness` or the previous output otherwise.
+fn foreign_utxo(
+    script_pubkey: ScriptBuf,
+    value: u64,
+    non_witness: bool,
+) -> (OutPoint, psbt::Input) {
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::from_consensus(value as u32),
+        input: vec![TxIn::default()],
+        output: vec![TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey,
+        }],
+    };
+    let outpoint = OutPoint::new(tx.compute_txid(), 0);
+    let psbt_input = if non_witness {
+        psbt::Input {
+            non_witness_utxo: Some(tx),
+            ..Default::default()
+        }
+    } else {
+        psbt::Input {
+            witness_utxo: Some(tx.output[0].clone()),
+            ..Default::default()
+        }
+    };
+    (outpoint, psbt_input)
+}
+
+#[test]
+fn test_sweep_wif() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let signers = wallet.get_signers(KeychainKind::External).ids().len();
+    let address = wallet.next_unused_address(KeychainKind::Internal);
+    let utxos = [
+        foreign_utxo(script_pubkey(&format!("pkh({WIF})"), 0), 10_000, true),
+        foreign_utxo(script_pubkey(&format!("wpkh({WIF})"), 0), 20_000, false),
+        foreign_utxo(script_pubkey(&format!("sh(wpkh({WIF}))"), 0), 30_000, false),
+        foreign_utxo(script_pubkey(&format!("tr({WIF})"), 0), 40_000, false),
+    ];
+    let fee_rate = FeeRate::from_sat_per_vb_u32(5);
+    let psbt = wallet.build_sweep(WIF, utxos.clone(), fee_rate).unwrap();
+
+    // All the inputs are signed and finalized.
+    assert_eq!(psbt.inputs.len(), 4);
+    assert!(psbt
+        .inputs
+        .iter()
+        .all(|input| input.final_script_sig.is_some() || input.final_script_witness.is_some()));
+    let fee = psbt.fee().unwrap();
+    let tx = psbt.extract_tx().unwrap();
+    assert!(fee >= fee_rate * tx.weight());
+    for (outpoint, _) in &utxos {
+        assert!(tx
+            .input
+            .iter()
+            .any(|txin| txin.previous_output == *outpoint));
+    }
+
+    // Everything goes to the next unused change address of the wallet.
+    assert_eq!(tx.output.len(), 1);
+    assert_eq!(tx.output[0].value, Amount::from_sat(100_000) - fee);
+    assert_eq!(tx.output[0].script_pubkey, address.script_pubkey());
+
+    // The key is not added to the wallet.
+    assert_eq!(
+        wallet.get_signers(KeychainKind::External).ids().len(),
+        signers
+    );
+}
+
+#[test]
+fn test_sweep_descriptor() {
+    let mut wallet = Wallet::create_single(get_test_tr_single_sig())
+        .network(bitcoin::Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let descriptor = format!("wpkh({XPRV}/84'/1'/0'/0/*)");
+    let utxos = [
+        foreign_utxo(script_pubkey(&descriptor, 0), 20_000, false),
+        foreign_utxo(script_pubkey(&descriptor, 7), 30_000, false),
+    ];
+    let psbt = wallet
+        .build_sweep(&descriptor, utxos, FeeRate::from_sat_per_vb_u32(2))
+        .unwrap();
+    assert!(psbt
+        .inputs
+        .iter()
+        .all(|input| input.final_script_witness.is_some()));
+    assert!(wallet.is_mine(psbt.unsigned_tx.output[0].script_pubkey.clone()));
+}
+
+#[test]
+fn test_sweep_errors() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let fee_rate = FeeRate::from_sat_per_vb_u32(2);
+
+    assert_matches!(
+        wallet.build_sweep(WIF, [], fee_rate),
+        Err(SweepError::NoUtxos)
+    );
+    assert_matches!(
+        wallet.build_sweep("not a key", [], fee_rate),
+        Err(SweepError::Descriptor(_))
+    );
+
+    // Outputs of an uncompressed key are only looked for as P2PKH.
+    let uncompressed = "91gGn1HgSap6CbU12F6z3pJri26xzp7Ay1VW6NHCoEayNXwRpu2";
+    let (outpoint, psbt_input) =
+        foreign_utxo(script_pubkey(&format!("wpkh({WIF})"), 0), 20_000, false);
+    assert_matches!(
+        wallet.build_sweep(uncompressed, [(outpoint, psbt_input.clone())], fee_rate),
+        Err(SweepError::UnknownScript(op)) if op == outpoint
+    );
+    let descriptor = format!("wpkh({XPRV}/0/*)");
+    assert_matches!(
+        wallet.build_sweep(&descriptor, [(outpoint, psbt_input)], fee_rate),
+        Err(SweepError::UnknownScript(op)) if op == outpoint
+    );
+
+    // A sweep which can't pay for its fee doesn't reveal a change address.
+    let change_index = wallet.derivation_index(KeychainKind::Internal);
+    let (outpoint, psbt_input) =
+        foreign_utxo(script_pubkey(&format!("wpkh({WIF})"), 0), 100, false);
+    assert_matches!(
+        wallet.build_sweep(WIF, [(outpoint, psbt_input)], fee_rate),
+        Err(SweepError::CreateTx(_))
+    );
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::Internal),
+        change_index
+    );
+
+    // Outputs of a ranged descriptor are only looked for up to `SWEEP_DERIVATION_RANGE`.
+    let (outpoint, psbt_input) = foreign_utxo(
+        script_pubkey(&descriptor, SWEEP_DERIVATION_RANGE),
+        20_000,
+        false,
+    );
+    assert_matches!(
+        wallet.build_sweep(&descriptor, [(outpoint, psbt_input)], fee_rate),
+        Err(SweepError::UnknownScript(op)) if op == outpoint
+    );
+}
@@ -0,0 +1,197 @@
+use std::str::FromStr;
+
+use assert_matches::assert_matches;
+use bdk_wallet::error::CreateTxError;
+use bdk_wallet::test_utils::*;
+use bdk_wallet::{KeychainKind, Wallet, TRUC_VERSION};
+use bitcoin::{Address, Amount, FeeRate, OutPoint, Transaction, Txid};
+
+fn foreign_address() -> Address {
+    Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked()
+}
+
+/// Create an unconfirmed TRUC transaction sending `amount` to the wallet's own external keychain
+/// and insert it in the wallet.
+fn send_to_self_truc(wallet: &mut Wallet, amount: Amount) -> Transaction {
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), amount).truc();
+    let tx = builder.finish().unwrap().unsigned_tx;
+    insert_tx(wallet, tx.clone());
+    tx
+}
+
+/// The outpoint of `tx` paying to `wallet`'s change keychain.
+fn change_outpoint(wallet: &Wallet, tx: &Transaction) -> OutPoint {
+    let txid = tx.compute_txid();
+    let vout = tx
+        .output
+        .iter()
+        .position(|txout| {
+            wallet
+                .derivation_of_spk(txout.script_pubkey.clone())
+                .is_some_and(|(keychain, _)| keychain == KeychainKind::Internal)
+        })
+        .unwrap();
+    OutPoint::new(txid, vout as u32)
+}
+
+#[test]
+fn test_truc_sets_version() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(foreign_address().script_pubkey(), Amount::from_sat(10_000))
+        .version(2)
+        .truc();
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.version, TRUC_VERSION);
+}
+
+#[test]
+fn test_truc_non_truc_unconfirmed_input() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let outpoint = receive_output(&mut wallet, Amount::from_sat(20_000), ReceiveTo::Mempool(0));
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(foreign_address().script_pubkey(), Amount::from_sat(10_000))
+        .add_utxo(outpoint)
+        .unwrap()
+        .manually_selected_only()
+        .truc();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::TrucNonTrucUnconfirmedInput(op)) if op == outpoint
+    );
+}
+
+#[test]
+fn test_truc_cpfp_child() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent = send_to_self_truc(&mut wallet, Amount::from_sat(20_000));
+
+    let psbt = wallet
+        .build_cpfp(parent.compute_txid(), FeeRate::from_sat_per_vb_u32(10))
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert_eq!(psbt.unsigned_tx.version, TRUC_VERSION);
+    // The child only spends outputs of the parent.
+    assert!(psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .all(|txin| txin.previous_output.txid == parent.compute_txid()));
+}
+
+#[test]
+fn test_truc_too_many_unconfirmed_ancestors() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let grandparent = send_to_self_truc(&mut wallet, Amount::from_sat(20_000));
+
+    // The parent spends the unconfirmed grandparent, which is allowed.
+    let outpoint = change_outpoint(&wallet, &grandparent);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(foreign_address().script_pubkey(), Amount::from_sat(5_000))
+        .add_utxo(outpoint)
+        .unwrap()
+        .manually_selected_only()
+        .truc();
+    let parent = builder.finish().unwrap().unsigned_tx;
+    insert_tx(&mut wallet, parent.clone());
+
+    let outpoint = change_outpoint(&wallet, &parent);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(foreign_address().script_pubkey(), Amount::from_sat(5_000))
+        .add_utxo(outpoint)
+        .unwrap()
+        .manually_selected_only()
+        .truc();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::TrucTooManyUnconfirmedAncestors)
+    );
+}
+
+#[test]
+fn test_truc_child_too_large() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent = send_to_self_truc(&mut wallet, Amount::from_sat(20_000));
+
+    let outpoint = change_outpoint(&wallet, &parent);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_utxo(outpoint)
+        .unwrap()
+        .manually_selected_only()
+        .truc();
+    for _ in 0..40 {
+        builder.add_recipient(foreign_address().script_pubkey(), Amount::from_sat(600));
+    }
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::TrucTooLarge { max: 1_000, .. })
+    );
+}
+
+#[test]
+fn test_truc_sibling_eviction() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent = send_to_self_truc(&mut wallet, Amount::from_sat(20_000));
+    let parent_txid = parent.compute_txid();
+    let change = change_outpoint(&wallet, &parent);
+    let received = OutPoint::new(parent_txid, 1 - change.vout);
+
+    // The first child spends the change of the parent.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(foreign_address().script_pubkey(), Amount::from_sat(10_000))
+        .add_utxo(change)
+        .unwrap()
+        .manually_selected_only()
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5))
+        .truc();
+    let sibling = builder.finish().unwrap().unsigned_tx;
+    let sibling_fee = wallet.calculate_fee(&sibling).unwrap();
+    insert_tx(&mut wallet, sibling);
+
+    // A second child must pay enough to evict the first one.
+    let build_child = |wallet: &mut Wallet, fee_rate: FeeRate| {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(foreign_address().script_pubkey(), Amount::from_sat(10_000))
+            .add_utxo(received)
+            .unwrap()
+            .manually_selected_only()
+            .fee_rate(fee_rate)
+            .truc();
+        builder.finish()
+    };
+    assert_matches!(
+        build_child(&mut wallet, FeeRate::from_sat_per_vb_u32(5)),
+        Err(CreateTxError::FeeTooLow { required }) if required > sibling_fee
+    );
+    let psbt = build_child(&mut wallet, FeeRate::from_sat_per_vb_u32(20)).unwrap();
+    assert!(psbt.fee().unwrap() > sibling_fee);
+}
+
+#[test]
+fn test_truc_fee_bump() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(foreign_address().script_pubkey(), Amount::from_sat(10_000))
+        .truc();
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let txid: Txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+
+    let mut builder = wallet.build_fee_bump(txid).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb_u32(5));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.version, TRUC_VERSION);
+}
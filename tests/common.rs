@@ -1,6 +1,15 @@
 #![allow(unused)]
 
+use std::str::FromStr;
+
+use bdk_wallet::test_utils::insert_tx;
+use bdk_wallet::{KeychainKind, Wallet};
+use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{
+    absolute, transaction, Address, Amount, FeeRate, OutPoint, Transaction, TxIn, TxOut, Txid,
+    Weight,
+};
 use miniscript::{descriptor::KeyMap, Descriptor, DescriptorPublicKey};
 
 /// The satisfaction size of P2WPKH is 108 WU =
@@ -17,6 +26,57 @@ pub fn parse_descriptor(s: &str) -> (Descriptor<DescriptorPublicKey>, KeyMap) {
         .expect("failed to parse descriptor")
 }
 
+/// Insert an unconfirmed tx paying `values` to the wallet and `fee` to miners, from a foreign
+/// input.
+pub fn receive_unconfirmed(wallet: &mut Wallet, values: &[Amount], fee: Amount) -> Transaction {
+    let prevout = OutPoint::new(Txid::all_zeros(), 0);
+    let foreign_spk = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    wallet.insert_txout(
+        prevout,
+        TxOut {
+            value: values.iter().copied().sum::<Amount>() + fee,
+            script_pubkey: foreign_spk,
+        },
+    );
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: prevout,
+            ..Default::default()
+        }],
+        output: values
+            .iter()
+            .map(|value| TxOut {
+                value: *value,
+                script_pubkey: wallet
+                    .next_unused_address(KeychainKind::External)
+                    .script_pubkey(),
+            })
+            .collect(),
+    };
+    insert_tx(wallet, tx.clone());
+    tx
+}
+
+/// Package fee rate of `child` and the given unconfirmed ancestors.
+pub fn package_fee_rate(wallet: &Wallet, child: &Transaction, ancestors: &[Txid]) -> FeeRate {
+    let (fee, weight) = ancestors
+        .iter()
+        .map(|txid| wallet.get_tx(*txid).unwrap().tx_node.tx)
+        .chain(core::iter::once(child.clone().into()))
+        .fold((Amount::ZERO, Weight::ZERO), |(fee, weight), tx| {
+            (
+                fee + wallet.calculate_fee(&tx).unwrap(),
+                weight + tx.weight(),
+            )
+        });
+    fee / weight
+}
+
 /// Validate and return the transaction fee from a PSBT.
 /// Panics if extraction fails, fee calculation fails, or if calculated fee doesn't match PSBT's
 /// fee.
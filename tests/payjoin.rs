@@ -0,0 +1,324 @@
+use std::str::FromStr;
+
+use assert_matches::assert_matches;
+use bdk_wallet::error::{ContributePayjoinError, ProcessPayjoinProposalError};
+use bdk_wallet::payjoin::PayjoinParams;
+use bdk_wallet::test_utils::*;
+use bdk_wallet::{KeychainKind, SignOptions, Wallet};
+use bitcoin::{absolute, transaction, Amount, FeeRate, Psbt, ScriptBuf, Transaction, TxOut};
+
+/// A wallet receiving payjoins, with a different key than [`get_funded_wallet_wpkh`].
+fn receiver_wallet() -> Wallet {
+    let (wallet, _) =
+        get_funded_wallet_single("wpkh(cVbZ8ovhye9AoAHFsqobCf7LxbXDAECy9Kb8TZdfsDYMZGBUyCnm)");
+    wallet
+}
+
+/// In-process stand-in for the receiver endpoint, exchanging base64 PSBTs.
+fn receiver_endpoint(receiver: &Wallet, original: &str) -> Result<String, ContributePayjoinError> {
+    let original = Psbt::from_str(original).unwrap();
+    receiver
+        .contribute_payjoin(&original, None, SignOptions::default())
+        .map(|proposal| proposal.to_string())
+}
+
+/// The script of the address the sender pays to.
+fn payee(receiver: &Wallet) -> ScriptBuf {
+    receiver
+        .peek_address(KeychainKind::External, 0)
+        .script_pubkey()
+}
+
+/// Create and sign the original PSBT of the sender, paying 20_000 sat to `receiver` and the
+/// `others` outputs.
+fn original_psbt_with_outputs(
+    sender: &mut Wallet,
+    receiver: &Wallet,
+    others: &[(ScriptBuf, Amount)],
+) -> Psbt {
+    let mut builder = sender.build_tx();
+    builder
+        .add_recipient(payee(receiver), Amount::from_sat(20_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(2));
+    for (script_pubkey, amount) in others {
+        builder.add_recipient(script_pubkey.clone(), *amount);
+    }
+    let mut psbt = builder.finish().unwrap();
+    assert!(sender.sign(&mut psbt, SignOptions::default()).unwrap());
+    psbt
+}
+
+/// Create and sign the original PSBT of the sender, paying 20_000 sat to `receiver`.
+fn original_psbt(sender: &mut Wallet, receiver: &Wallet) -> Psbt {
+    original_psbt_with_outputs(sender, receiver, &[])
+}
+
+#[test]
+fn test_payjoin() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let receiver = receiver_wallet();
+    let original = original_psbt(&mut sender, &receiver);
+
+    let proposal = receiver_endpoint(&receiver, &original.to_string()).unwrap();
+    let proposal = Psbt::from_str(&proposal).unwrap();
+    let params = PayjoinParams {
+        min_fee_rate: FeeRate::from_sat_per_vb_u32(2),
+        ..Default::default()
+    };
+    let psbt = sender
+        .process_payjoin_proposal(
+            &original,
+            proposal,
+            &payee(&receiver),
+            &params,
+            SignOptions::default(),
+        )
+        .unwrap();
+    let tx = psbt.extract_tx().expect("payjoin must be finalized");
+
+    assert_eq!(tx.input.len(), 2);
+    let (sent, received) = sender.sent_and_received(&tx);
+    let (original_sent, original_received) =
+        sender.sent_and_received(&original.clone().extract_tx().unwrap());
+    // The sender pays the same as in the original transaction.
+    assert_eq!(sent - received, original_sent - original_received);
+    // The receiver gets the payment on top of its contributed input, minus the fee of its input.
+    let (receiver_sent, receiver_received) = receiver.sent_and_received(&tx);
+    assert!(receiver_received - receiver_sent < Amount::from_sat(20_000));
+    assert!(receiver_received - receiver_sent > Amount::from_sat(19_500));
+}
+
+#[test]
+fn test_payjoin_fee_contribution_too_high() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let receiver = receiver_wallet();
+    let original = original_psbt(&mut sender, &receiver);
+
+    let proposal = receiver_endpoint(&receiver, &original.to_string()).unwrap();
+    let mut proposal = Psbt::from_str(&proposal).unwrap();
+    // The receiver takes 1_000 sat from the change of the sender.
+    let change = proposal
+        .unsigned_tx
+        .output
+        .iter_mut()
+        .find(|txout| sender.is_mine(txout.script_pubkey.clone()))
+        .unwrap();
+    change.value -= Amount::from_sat(1_000);
+
+    let params = PayjoinParams {
+        max_additional_fee_contribution: Amount::from_sat(500),
+        ..Default::default()
+    };
+    assert_matches!(
+        sender.process_payjoin_proposal(
+            &original,
+            proposal,
+            &payee(&receiver),
+            &params,
+            SignOptions::default()
+        ),
+        Err(ProcessPayjoinProposalError::FeeContributionTooHigh { contribution, max })
+            if contribution == Amount::from_sat(1_000) && max == Amount::from_sat(500)
+    );
+}
+
+#[test]
+fn test_payjoin_fee_contribution_above_input_fee() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let receiver = receiver_wallet();
+    let original = original_psbt(&mut sender, &receiver);
+
+    let proposal = receiver_endpoint(&receiver, &original.to_string()).unwrap();
+    let mut proposal = Psbt::from_str(&proposal).unwrap();
+    // The receiver takes 1_000 sat from the change of the sender, more than the fee of its input.
+    let change = proposal
+        .unsigned_tx
+        .output
+        .iter_mut()
+        .find(|txout| sender.is_mine(txout.script_pubkey.clone()))
+        .unwrap();
+    change.value -= Amount::from_sat(1_000);
+
+    let params = PayjoinParams {
+        max_additional_fee_contribution: Amount::from_sat(10_000),
+        ..Default::default()
+    };
+    assert_matches!(
+        sender.process_payjoin_proposal(
+            &original,
+            proposal,
+            &payee(&receiver),
+            &params,
+            SignOptions::default()
+        ),
+        Err(ProcessPayjoinProposalError::FeeContributionAboveInputFee { contribution, input_fee })
+            if contribution == Amount::from_sat(1_000) && input_fee < contribution
+    );
+}
+
+#[test]
+fn test_payjoin_output_changed() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let receiver = receiver_wallet();
+    let third_party = ScriptBuf::from_hex("0014aa00000000000000000000000000000000000000").unwrap();
+    let original = original_psbt_with_outputs(
+        &mut sender,
+        &receiver,
+        &[(third_party.clone(), Amount::from_sat(5_000))],
+    );
+
+    let proposal = receiver_endpoint(&receiver, &original.to_string()).unwrap();
+    let proposal = Psbt::from_str(&proposal).unwrap();
+
+    // The output to a third party can never be lowered.
+    let mut lowered = proposal.clone();
+    lowered
+        .unsigned_tx
+        .output
+        .iter_mut()
+        .find(|txout| txout.script_pubkey == third_party)
+        .unwrap()
+        .value -= Amount::from_sat(1_000);
+    assert_matches!(
+        sender.process_payjoin_proposal(
+            &original,
+            lowered,
+            &payee(&receiver),
+            &PayjoinParams::default(),
+            SignOptions::default()
+        ),
+        Err(ProcessPayjoinProposalError::OutputChanged(script)) if script == third_party
+    );
+
+    // The output to the receiver can be substituted unless it's disabled.
+    let mut substituted = proposal;
+    substituted
+        .unsigned_tx
+        .output
+        .iter_mut()
+        .find(|txout| txout.script_pubkey == payee(&receiver))
+        .unwrap()
+        .script_pubkey =
+        ScriptBuf::from_hex("0014bb00000000000000000000000000000000000000").unwrap();
+    assert!(sender
+        .process_payjoin_proposal(
+            &original,
+            substituted.clone(),
+            &payee(&receiver),
+            &PayjoinParams::default(),
+            SignOptions::default()
+        )
+        .is_ok());
+    let params = PayjoinParams {
+        disable_output_substitution: true,
+        ..Default::default()
+    };
+    assert_matches!(
+        sender.process_payjoin_proposal(
+            &original,
+            substituted,
+            &payee(&receiver),
+            &params,
+            SignOptions::default()
+        ),
+        Err(ProcessPayjoinProposalError::PayeeOutputChanged(script)) if script == payee(&receiver)
+    );
+}
+
+#[test]
+fn test_payjoin_receiver_input_not_finalized() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let receiver = receiver_wallet();
+    let original = original_psbt(&mut sender, &receiver);
+
+    let proposal = receiver_endpoint(&receiver, &original.to_string()).unwrap();
+    let mut proposal = Psbt::from_str(&proposal).unwrap();
+    for input in &mut proposal.inputs {
+        input.final_script_witness = None;
+    }
+    assert_matches!(
+        sender.process_payjoin_proposal(
+            &original,
+            proposal,
+            &payee(&receiver),
+            &PayjoinParams::default(),
+            SignOptions::default()
+        ),
+        Err(ProcessPayjoinProposalError::ReceiverInputNotFinalized(_))
+    );
+}
+
+#[test]
+fn test_payjoin_new_sender_input() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let receiver = receiver_wallet();
+    // The sender has a second UTXO that the original transaction doesn't spend.
+    receive_output_in_latest_block(&mut sender, Amount::from_sat(30_000));
+    let original = original_psbt(&mut sender, &receiver);
+
+    // A malicious receiver adds it to the proposal, pretending it's its own.
+    let unspent = sender
+        .list_unspent()
+        .find(|utxo| {
+            !original
+                .unsigned_tx
+                .input
+                .iter()
+                .any(|txin| txin.previous_output == utxo.outpoint)
+        })
+        .unwrap();
+    let mut proposal = original.clone();
+    proposal.unsigned_tx.input.push(bitcoin::TxIn {
+        previous_output: unspent.outpoint,
+        ..Default::default()
+    });
+    let mut psbt_input = sender.get_psbt_input(unspent, None, false).unwrap();
+    psbt_input.final_script_witness = Some(Default::default());
+    proposal.inputs.push(psbt_input);
+
+    assert_matches!(
+        sender.process_payjoin_proposal(
+            &original,
+            proposal,
+            &payee(&receiver),
+            &PayjoinParams::default(),
+            SignOptions::default()
+        ),
+        Err(ProcessPayjoinProposalError::NewSenderInput(_))
+    );
+}
+
+#[test]
+fn test_payjoin_receiver_checks_original() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let receiver = receiver_wallet();
+    let addr = receiver.peek_address(KeychainKind::External, 0);
+    let mut builder = sender.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(20_000));
+    let unsigned = builder.finish().unwrap();
+
+    assert_matches!(
+        receiver_endpoint(&receiver, &unsigned.to_string()),
+        Err(ContributePayjoinError::OriginalNotFinalized)
+    );
+}
+
+#[test]
+fn test_payjoin_receiver_rejects_no_input() {
+    let receiver = receiver_wallet();
+    let original = Psbt::from_unsigned_tx(Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: payee(&receiver),
+        }],
+    })
+    .unwrap();
+
+    assert_matches!(
+        receiver_endpoint(&receiver, &original.to_string()),
+        Err(ContributePayjoinError::NoSenderInput)
+    );
+}
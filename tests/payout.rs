@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+use bdk_wallet::payout::PayoutBatcher;
+use bdk_wallet::test_utils::*;
+use bdk_wallet::Wallet;
+use bitcoin::{Address, Amount, FeeRate, ScriptBuf, Weight};
+
+fn foreign_spk() -> ScriptBuf {
+    Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey()
+}
+
+/// A wallet with `count` confirmed UTXOs of 50_000 sat.
+fn wallet_with_utxos(count: usize) -> Wallet {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    for _ in 1..count {
+        receive_output_in_latest_block(&mut wallet, Amount::from_sat(50_000));
+    }
+    wallet
+}
+
+#[test]
+fn test_payout_batcher_max_outputs() {
+    let mut wallet = wallet_with_utxos(5);
+    let mut batcher = PayoutBatcher::new();
+    batcher
+        .max_outputs(2)
+        .fee_rate(FeeRate::from_sat_per_vb_u32(2));
+    for id in 1..=5 {
+        batcher.add(id, foreign_spk(), Amount::from_sat(5_000));
+    }
+
+    let report = batcher.build(&mut wallet);
+    assert!(report.error.is_none());
+    assert!(report.deferred.is_empty());
+    assert!(batcher.pending().is_empty());
+    let ids: Vec<Vec<i32>> = report.batches.iter().map(|b| b.ids.clone()).collect();
+    assert_eq!(ids, vec![vec![1, 2], vec![3, 4], vec![5]]);
+
+    // Every batch pays its requests and no UTXO is spent twice.
+    let mut spent = Vec::new();
+    for batch in &report.batches {
+        let tx = &batch.psbt.unsigned_tx;
+        let paid = tx
+            .output
+            .iter()
+            .filter(|txout| txout.script_pubkey == foreign_spk())
+            .count();
+        assert_eq!(paid, batch.ids.len());
+        for txin in &tx.input {
+            assert!(!spent.contains(&txin.previous_output));
+            spent.push(txin.previous_output);
+        }
+    }
+}
+
+#[test]
+fn test_payout_batcher_max_weight() {
+    let mut wallet = wallet_with_utxos(2);
+    let mut batcher = PayoutBatcher::new();
+    // One input, a change output and two payouts fit, four payouts don't.
+    batcher
+        .max_weight(Weight::from_vb_unchecked(200))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(2));
+    for id in ["a", "b", "c", "d"] {
+        batcher.add(id, foreign_spk(), Amount::from_sat(5_000));
+    }
+
+    let report = batcher.build(&mut wallet);
+    assert!(report.error.is_none());
+    let ids: Vec<Vec<&str>> = report.batches.iter().map(|b| b.ids.clone()).collect();
+    assert_eq!(ids, vec![vec!["a", "b"], vec!["c", "d"]]);
+}
+
+#[test]
+fn test_payout_batcher_insufficient_funds() {
+    let mut wallet = wallet_with_utxos(1);
+    let mut batcher = PayoutBatcher::new();
+    batcher
+        .add(1, foreign_spk(), Amount::from_sat(30_000))
+        .add(2, foreign_spk(), Amount::from_sat(30_000))
+        .add(3, foreign_spk(), Amount::from_sat(10_000));
+
+    let report = batcher.build(&mut wallet);
+    assert!(report.error.is_none());
+    assert_eq!(report.batches.len(), 1);
+    assert_eq!(report.batches[0].ids, vec![1]);
+    // The requests that couldn't be paid stay in the queue, in order.
+    assert_eq!(report.deferred, vec![2, 3]);
+    let pending: Vec<i32> = batcher.pending().iter().map(|r| r.id).collect();
+    assert_eq!(pending, vec![2, 3]);
+}
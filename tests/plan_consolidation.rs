@@ -0,0 +1,144 @@
+use bdk_wallet::test_utils::*;
+use bdk_wallet::{KeychainKind, UtxoFilters, Wallet};
+use bitcoin::{Amount, FeeRate, OutPoint};
+
+/// A funded wallet which also received confirmed outputs of the given values.
+fn wallet_with_outputs(values: &[u64]) -> (Wallet, Vec<OutPoint>) {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let outpoints = values
+        .iter()
+        .map(|value| receive_output_in_latest_block(&mut wallet, Amount::from_sat(*value)))
+        .collect();
+    (wallet, outpoints)
+}
+
+#[test]
+fn test_plan_consolidation() {
+    let (mut wallet, outpoints) = wallet_with_outputs(&[3_000, 1_000, 4_000, 2_000, 5_000]);
+    let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+    let filters = UtxoFilters::default();
+    let change_index = wallet.derivation_index(KeychainKind::Internal);
+
+    // Planning doesn't reveal the change address.
+    let builder =
+        wallet.plan_consolidation(fee_rate, FeeRate::from_sat_per_vb_u32(20), 4, &filters);
+    assert!(builder.is_some());
+    drop(builder);
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::Internal),
+        change_index
+    );
+
+    let psbt = wallet
+        .plan_consolidation(fee_rate, FeeRate::from_sat_per_vb_u32(20), 4, &filters)
+        .expect("consolidation is worth it")
+        .finish()
+        .unwrap();
+
+    // The four smallest outputs are consolidated into a single change output.
+    let mut spent = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .collect::<Vec<_>>();
+    spent.sort();
+    let mut expected = vec![outpoints[0], outpoints[1], outpoints[2], outpoints[3]];
+    expected.sort();
+    assert_eq!(spent, expected);
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    let (keychain, _) = wallet
+        .derivation_of_spk(psbt.unsigned_tx.output[0].script_pubkey.clone())
+        .unwrap();
+    assert_eq!(keychain, KeychainKind::Internal);
+    let fee = psbt.fee().unwrap();
+    assert_eq!(
+        psbt.unsigned_tx.output[0].value,
+        Amount::from_sat(10_000) - fee
+    );
+    assert!(fee >= fee_rate * psbt.unsigned_tx.weight());
+}
+
+#[test]
+fn test_plan_consolidation_locked_and_unconfirmed() {
+    let (mut wallet, outpoints) = wallet_with_outputs(&[1_000, 2_000, 3_000]);
+    let unconfirmed = receive_output(&mut wallet, Amount::from_sat(1_500), ReceiveTo::Mempool(0));
+    wallet.lock_outpoint(outpoints[0]);
+
+    let psbt = wallet
+        .plan_consolidation(
+            FeeRate::from_sat_per_vb_u32(1),
+            FeeRate::from_sat_per_vb_u32(20),
+            10,
+            &UtxoFilters::default(),
+        )
+        .unwrap()
+        .finish()
+        .unwrap();
+    let spent = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .collect::<Vec<_>>();
+    assert!(!spent.contains(&outpoints[0]));
+    assert!(spent.contains(&unconfirmed));
+
+    // The unconfirmed output is filtered out before picking the smallest ones.
+    let filters = UtxoFilters {
+        min_confirmations: 1,
+        ..Default::default()
+    };
+    let psbt = wallet
+        .plan_consolidation(
+            FeeRate::from_sat_per_vb_u32(1),
+            FeeRate::from_sat_per_vb_u32(20),
+            2,
+            &filters,
+        )
+        .unwrap()
+        .finish()
+        .unwrap();
+    let mut spent = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .collect::<Vec<_>>();
+    spent.sort();
+    let mut expected = vec![outpoints[1], outpoints[2]];
+    expected.sort();
+    assert_eq!(spent, expected);
+}
+
+#[test]
+fn test_plan_consolidation_fee_rates() {
+    let (mut wallet, outpoints) = wallet_with_outputs(&[1_000, 2_000, 3_000]);
+    let low = FeeRate::from_sat_per_vb_u32(2);
+    let high = FeeRate::from_sat_per_vb_u32(20);
+
+    let filters = UtxoFilters::default();
+
+    // Fees won't go down.
+    assert!(wallet.plan_consolidation(high, low, 10, &filters).is_none());
+    assert!(wallet
+        .plan_consolidation(high, high, 10, &filters)
+        .is_none());
+    // A single input costs more to spend later than it saves.
+    assert!(wallet.plan_consolidation(low, high, 1, &filters).is_none());
+    assert!(wallet.plan_consolidation(low, high, 0, &filters).is_none());
+    // Outputs worth less than the fee to spend them aren't consolidated.
+    let psbt = wallet
+        .plan_consolidation(high, FeeRate::from_sat_per_vb_u32(100), 2, &filters)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let spent = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .collect::<Vec<_>>();
+    assert_eq!(spent.len(), 2);
+    assert!(!spent.contains(&outpoints[0]));
+}
@@ -1,4 +1,7 @@
-use bdk_wallet::bitcoin::{Amount, FeeRate, Psbt, TxIn};
+use assert_matches::assert_matches;
+use bdk_wallet::bitcoin::hashes::Hash;
+use bdk_wallet::bitcoin::{absolute, Amount, FeeRate, OutPoint, Psbt, Sequence, TxIn, TxOut, Txid};
+use bdk_wallet::psbt::v2::{PsbtV2, PsbtV2Error, TxModifiable};
 use bdk_wallet::test_utils::*;
 use bdk_wallet::{psbt, KeychainKind, SignOptions};
 use core::str::FromStr;
@@ -221,3 +224,151 @@ fn test_psbt_multiple_internalkey_signers() {
     let verify_res = secp.verify_schnorr(&signature, &message, &xonlykey);
     assert!(verify_res.is_ok(), "The wrong internal key was used");
 }
+
+#[test]
+fn test_psbt_v2_roundtrip() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder
+        .finish_v2(TxModifiable::INPUTS | TxModifiable::OUTPUTS)
+        .unwrap();
+
+    let bytes = psbt.serialize();
+    assert!(Psbt::deserialize(&bytes).is_err(), "not a version 0 PSBT");
+    let decoded = PsbtV2::deserialize(&bytes).unwrap();
+    assert_eq!(decoded, psbt);
+    assert_eq!(
+        decoded.modifiable(),
+        TxModifiable::INPUTS | TxModifiable::OUTPUTS
+    );
+    assert_eq!(
+        decoded.fallback_lock_time(),
+        Some(psbt.unsigned_tx.lock_time)
+    );
+    assert_eq!(PsbtV2::from_str(&psbt.to_string()).unwrap(), psbt);
+    assert_matches!(
+        PsbtV2::deserialize(&psbt.clone().into_v0().serialize()),
+        Err(PsbtV2Error::UnsupportedVersion(0))
+    );
+}
+
+#[test]
+fn test_psbt_v2_interactive() {
+    let (mut alice, _) = get_funded_wallet_wpkh();
+    let (bob, _) =
+        get_funded_wallet_single("wpkh(cVbZ8ovhye9AoAHFsqobCf7LxbXDAECy9Kb8TZdfsDYMZGBUyCnm)");
+
+    // Alice pays herself and lets Bob add his input and output.
+    let alice_addr = alice.peek_address(KeychainKind::External, 0);
+    let mut builder = alice.build_tx();
+    builder.add_recipient(alice_addr.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder
+        .finish_v2(TxModifiable::INPUTS | TxModifiable::OUTPUTS)
+        .unwrap();
+
+    let mut psbt = PsbtV2::from_str(&psbt.to_string()).unwrap();
+    let utxo = bob.list_unspent().next().unwrap();
+    let bob_addr = bob.peek_address(KeychainKind::External, 1);
+    psbt.add_input(
+        utxo.outpoint,
+        Sequence::ENABLE_RBF_NO_LOCKTIME,
+        None,
+        bob.get_psbt_input(utxo.clone(), None, false).unwrap(),
+    )
+    .unwrap()
+    .add_output(
+        TxOut {
+            value: utxo.txout.value - Amount::from_sat(1_000),
+            script_pubkey: bob_addr.script_pubkey(),
+        },
+        Default::default(),
+    )
+    .unwrap();
+
+    // Once Alice signs with `SIGHASH_ALL` nothing can be added anymore.
+    let mut psbt = PsbtV2::from_str(&psbt.to_string()).unwrap();
+    let finalized = alice.sign(&mut psbt, SignOptions::default()).unwrap();
+    assert!(!finalized);
+    assert_eq!(psbt.modifiable(), TxModifiable::NONE);
+    assert_matches!(
+        psbt.add_output(utxo.txout.clone(), Default::default()),
+        Err(PsbtV2Error::NotModifiable)
+    );
+
+    let mut psbt = PsbtV2::from_str(&psbt.to_string()).unwrap();
+    let finalized = bob.sign(&mut psbt, SignOptions::default()).unwrap();
+    assert!(finalized);
+    let tx = psbt.into_v0().extract_tx().expect("failed to extract tx");
+    assert_eq!(tx.input.len(), 2);
+    assert_eq!(tx.output.len(), 3);
+}
+
+#[test]
+fn test_psbt_v2_required_lock_time() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let mut psbt = builder.finish_v2(TxModifiable::INPUTS).unwrap();
+    let fallback = psbt.unsigned_tx.lock_time;
+
+    let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+    let height = absolute::LockTime::from_height(100).unwrap();
+    psbt.add_input(outpoint, Sequence::ZERO, Some(height), Default::default())
+        .unwrap();
+    assert_ne!(fallback, height);
+    assert_eq!(psbt.unsigned_tx.lock_time, height);
+
+    let decoded = PsbtV2::deserialize(&psbt.serialize()).unwrap();
+    assert_eq!(decoded.unsigned_tx.lock_time, height);
+
+    let time = absolute::LockTime::from_time(1_700_000_000).unwrap();
+    assert_matches!(
+        psbt.add_input(outpoint, Sequence::ZERO, Some(time), Default::default()),
+        Err(PsbtV2Error::LockTimeConflict)
+    );
+}
+
+#[test]
+fn test_psbt_v2_required_lock_time_out_of_range() {
+    use bdk_wallet::bitcoin::psbt::{raw, Input};
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder.finish_v2(TxModifiable::INPUTS).unwrap();
+    let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+
+    // A required height of 500000000 or more, or a required time below 500000000, would be
+    // read as the other kind of lock time.
+    for (type_value, lock_time, field) in [
+        (0x12, 500_000_000u32, "PSBT_IN_REQUIRED_HEIGHT_LOCKTIME"),
+        (0x11, 499_999_999u32, "PSBT_IN_REQUIRED_TIME_LOCKTIME"),
+    ] {
+        let key = raw::Key {
+            type_value,
+            key: vec![],
+        };
+        let mut input = Input::default();
+        input
+            .unknown
+            .insert(key.clone(), lock_time.to_le_bytes().to_vec());
+        assert_matches!(
+            psbt.clone()
+                .add_input(outpoint, Sequence::ZERO, None, input),
+            Err(PsbtV2Error::InvalidField(f)) if f == field
+        );
+
+        let mut invalid = psbt.clone();
+        invalid.inputs[0]
+            .unknown
+            .insert(key, lock_time.to_le_bytes().to_vec());
+        assert_matches!(
+            PsbtV2::deserialize(&invalid.serialize()),
+            Err(PsbtV2Error::InvalidField(f)) if f == field
+        );
+    }
+}
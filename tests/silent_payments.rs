@@ -0,0 +1,539 @@
+use std::str::FromStr;
+
+use assert_matches::assert_matches;
+use bdk_wallet::error::{ApplyBlockError, CreateTxError};
+use bdk_wallet::silent_payments::{
+    SilentPaymentAddress, SilentPaymentAddressError, PSBT_OUT_SP_V0_INFO,
+};
+use bdk_wallet::test_utils::*;
+use bdk_wallet::{KeychainKind, LoadError, LoadMismatch, SignOptions, Wallet};
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::{TweakedPublicKey, XOnlyPublicKey};
+use bitcoin::secp256k1::{Message, PublicKey, Scalar, Secp256k1, SecretKey};
+use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::{
+    absolute, psbt, taproot, transaction, Amount, Block, FeeRate, Network, NetworkKind, OutPoint,
+    Psbt, ScriptBuf, TapSighashType, Transaction, TxIn, TxMerkleNode, TxOut, WPubkeyHash,
+};
+
+const SCAN_KEY: &str = "0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c";
+const SPEND_KEY: &str = "9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3";
+
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> Scalar {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    Scalar::from_be_bytes(sha256::Hash::from_engine(engine).to_byte_array()).unwrap()
+}
+
+/// Address of the recipient, with its spend key tweaked by `label` if any.
+fn recipient_address(label: Option<u8>) -> SilentPaymentAddress {
+    let secp = Secp256k1::new();
+    let scan = SecretKey::from_str(SCAN_KEY).unwrap();
+    let mut spend = SecretKey::from_str(SPEND_KEY).unwrap();
+    if let Some(label) = label {
+        spend = spend
+            .add_tweak(&Scalar::from_be_bytes([label; 32]).unwrap())
+            .unwrap();
+    }
+    SilentPaymentAddress::new(
+        scan.public_key(&secp),
+        spend.public_key(&secp),
+        NetworkKind::Test,
+    )
+}
+
+/// The public key of an input as seen by the receiver.
+fn input_public_key(psbt_input: &psbt::Input) -> PublicKey {
+    let script_pubkey = &psbt_input.witness_utxo.as_ref().unwrap().script_pubkey;
+    if script_pubkey.is_p2tr() {
+        let mut key = vec![0x02];
+        key.extend(&script_pubkey.as_bytes()[2..]);
+        PublicKey::from_slice(&key).unwrap()
+    } else {
+        *psbt_input.bip32_derivation.keys().next().unwrap()
+    }
+}
+
+/// Script of the `k`-th output paying `address`, derived with the scan key of the recipient.
+fn receiver_output_script(psbt: &Psbt, address: &SilentPaymentAddress, k: u32) -> ScriptBuf {
+    let secp = Secp256k1::new();
+    let input_keys: Vec<PublicKey> = psbt.inputs.iter().map(input_public_key).collect();
+    let input_keys_sum = PublicKey::combine_keys(&input_keys.iter().collect::<Vec<_>>()).unwrap();
+    let smallest_outpoint = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| serialize(&txin.previous_output))
+        .min()
+        .unwrap();
+    let input_hash = tagged_hash(
+        "BIP0352/Inputs",
+        &[&smallest_outpoint, &input_keys_sum.serialize()],
+    );
+    let scan = SecretKey::from_str(SCAN_KEY).unwrap();
+    let ecdh_shared_secret = input_keys_sum
+        .mul_tweak(&secp, &input_hash)
+        .unwrap()
+        .mul_tweak(&secp, &Scalar::from(scan))
+        .unwrap();
+    let tweak = tagged_hash(
+        "BIP0352/SharedSecret",
+        &[&ecdh_shared_secret.serialize(), &k.to_be_bytes()],
+    );
+    let output_key = address.spend.add_exp_tweak(&secp, &tweak).unwrap();
+    ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+        output_key.x_only_public_key().0,
+    ))
+}
+
+/// A wallet receiving silent payments with the scan and spend keys of [`recipient_address`].
+fn receiver_wallet() -> Wallet {
+    Wallet::create_single(get_test_tr_single_sig())
+        .network(Network::Regtest)
+        .silent_payments(
+            SecretKey::from_str(SCAN_KEY).unwrap(),
+            SecretKey::from_str(SPEND_KEY).unwrap(),
+        )
+        .create_wallet_no_persist()
+        .unwrap()
+}
+
+/// Pay `amount` to `address` from a funded wallet, returning the transaction and the outputs it
+/// spends.
+fn pay(address: SilentPaymentAddress, amount: Amount) -> (Transaction, Vec<(OutPoint, TxOut)>) {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let mut builder = sender.build_tx();
+    builder.add_silent_payment_recipient(address, amount);
+    let mut psbt = builder.finish().unwrap();
+    assert!(sender.sign(&mut psbt, SignOptions::default()).unwrap());
+    let prevouts = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .zip(&psbt.inputs)
+        .map(|(txin, psbt_input)| {
+            let prevout = psbt_input.witness_utxo.clone().unwrap();
+            (txin.previous_output, prevout)
+        })
+        .collect();
+    (psbt.extract_tx().unwrap(), prevouts)
+}
+
+/// A block containing `txdata` on top of the wallet's tip, with its height.
+fn next_block(wallet: &Wallet, txdata: Vec<Transaction>) -> (Block, u32) {
+    let tip = wallet.latest_checkpoint();
+    let coinbase = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            ..Default::default()
+        }],
+        output: vec![],
+    };
+    let block = Block {
+        header: Header {
+            version: Default::default(),
+            prev_blockhash: tip.hash(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: tip.height() + 1,
+            bits: Default::default(),
+            nonce: 0,
+        },
+        txdata: [coinbase].into_iter().chain(txdata).collect(),
+    };
+    (block, tip.height() + 1)
+}
+
+/// Apply a block containing `txdata` on top of the wallet's tip, given the outputs it spends
+/// which aren't in the wallet.
+fn apply_block(wallet: &mut Wallet, txdata: Vec<Transaction>, prevouts: Vec<(OutPoint, TxOut)>) {
+    let (block, height) = next_block(wallet, txdata);
+    wallet
+        .apply_block_with_prevouts(&block, height, prevouts)
+        .unwrap();
+}
+
+fn send_silent_payment(wallet: &mut Wallet, address: &SilentPaymentAddress) -> Psbt {
+    let mut builder = wallet.build_tx();
+    builder.add_silent_payment_recipient(*address, Amount::from_sat(20_000));
+    builder.finish().unwrap()
+}
+
+#[test]
+fn test_silent_payment_address() {
+    let s = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+    let address = SilentPaymentAddress::from_str(s).unwrap();
+    assert_eq!(address.network, NetworkKind::Main);
+    assert_eq!(
+        address,
+        SilentPaymentAddress {
+            network: NetworkKind::Main,
+            ..recipient_address(None)
+        }
+    );
+    assert_eq!(address.to_string(), s);
+    assert_eq!(
+        s.to_uppercase().parse::<SilentPaymentAddress>().unwrap(),
+        address
+    );
+
+    let address = recipient_address(None);
+    assert!(address.to_string().starts_with("tsp1q"));
+    assert_eq!(
+        address.to_string().parse::<SilentPaymentAddress>().unwrap(),
+        address
+    );
+
+    assert_matches!(
+        "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwq"
+            .parse::<SilentPaymentAddress>(),
+        Err(SilentPaymentAddressError::Bech32(_))
+    );
+}
+
+#[test]
+fn test_send_silent_payment() {
+    let address = recipient_address(None);
+    for descriptor in [
+        get_test_wpkh(),
+        get_test_pkh(),
+        "sh(wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW))",
+        get_test_tr_single_sig(),
+        get_test_tr_single_sig_xprv(),
+        get_test_tr_with_taptree_xprv(),
+    ] {
+        let (mut wallet, _) = get_funded_wallet_single(descriptor);
+        let mut psbt = send_silent_payment(&mut wallet, &address);
+
+        let vout = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .position(|txout| txout.value == Amount::from_sat(20_000))
+            .unwrap();
+        // Legacy inputs only have a `non_witness_utxo`.
+        for (psbt_input, txin) in psbt.inputs.iter_mut().zip(&psbt.unsigned_tx.input) {
+            if psbt_input.witness_utxo.is_none() {
+                let prev_tx = psbt_input.non_witness_utxo.as_ref().unwrap();
+                psbt_input.witness_utxo =
+                    Some(prev_tx.output[txin.previous_output.vout as usize].clone());
+            }
+        }
+        assert_eq!(
+            psbt.unsigned_tx.output[vout].script_pubkey,
+            receiver_output_script(&psbt, &address, 0),
+            "{descriptor}"
+        );
+        let info = psbt.outputs[vout]
+            .unknown
+            .iter()
+            .find(|(key, _)| key.type_value == PSBT_OUT_SP_V0_INFO)
+            .map(|(_, value)| value.clone())
+            .unwrap();
+        assert_eq!(info, address.psbt_output_info());
+
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    }
+}
+
+#[test]
+fn test_bump_fee_silent_payment() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let address = recipient_address(None);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_silent_payment_recipient(address, Amount::from_sat(45_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(1));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    let original_script = receiver_output_script(&psbt, &address, 0);
+    let txid = psbt.unsigned_tx.compute_txid();
+    insert_tx(&mut wallet, psbt.unsigned_tx);
+
+    // The fee bump needs a new input, which changes the output key.
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(25_000));
+    let mut builder = wallet.build_fee_bump(txid).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb_u32(50));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 2);
+
+    let vout = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .position(|txout| txout.value == Amount::from_sat(45_000))
+        .unwrap();
+    let script_pubkey = &psbt.unsigned_tx.output[vout].script_pubkey;
+    assert_ne!(*script_pubkey, original_script);
+    assert_eq!(*script_pubkey, receiver_output_script(&psbt, &address, 0));
+    assert!(psbt.outputs[vout]
+        .unknown
+        .keys()
+        .any(|key| key.type_value == PSBT_OUT_SP_V0_INFO));
+}
+
+#[test]
+fn test_cancel_silent_payment() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_silent_payment_recipient(recipient_address(None), Amount::from_sat(20_000))
+        .add_silent_payment_recipient(recipient_address(Some(1)), Amount::from_sat(10_000));
+    let psbt = builder.finish().unwrap();
+    let txid = psbt.unsigned_tx.compute_txid();
+    insert_tx(&mut wallet, psbt.unsigned_tx);
+
+    // The whole amount goes back to the wallet.
+    let psbt = wallet.build_cancel_tx(txid).unwrap().finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(
+        wallet
+            .derivation_of_spk(psbt.unsigned_tx.output[0].script_pubkey.clone())
+            .map(|(keychain, _)| keychain),
+        Some(KeychainKind::Internal)
+    );
+    assert!(psbt.outputs[0]
+        .unknown
+        .keys()
+        .all(|key| key.type_value != PSBT_OUT_SP_V0_INFO));
+}
+
+#[test]
+fn test_send_silent_payments_same_scan_key() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let address = recipient_address(None);
+    let labeled_address = recipient_address(Some(1));
+    let mut builder = wallet.build_tx();
+    builder
+        .add_silent_payment_recipient(address, Amount::from_sat(20_000))
+        .add_silent_payment_recipient(labeled_address, Amount::from_sat(10_000));
+    let psbt = builder.finish().unwrap();
+
+    let script_of = |amount| {
+        psbt.unsigned_tx
+            .output
+            .iter()
+            .find(|txout| txout.value == Amount::from_sat(amount))
+            .map(|txout| txout.script_pubkey.clone())
+            .unwrap()
+    };
+    // Outputs paying the same scan key are numbered in the order they were added.
+    assert_eq!(
+        script_of(20_000),
+        receiver_output_script(&psbt, &address, 0)
+    );
+    assert_eq!(
+        script_of(10_000),
+        receiver_output_script(&psbt, &labeled_address, 1)
+    );
+}
+
+#[test]
+fn test_send_silent_payment_foreign_input() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let (wallet2, _) =
+        get_funded_wallet_single("wpkh(cVbZ8ovhye9AoAHFsqobCf7LxbXDAECy9Kb8TZdfsDYMZGBUyCnm)");
+    let utxo = wallet2.list_unspent().next().unwrap();
+    let psbt_input = wallet2.get_psbt_input(utxo.clone(), None, false).unwrap();
+    let foreign_utxo_satisfaction = wallet2
+        .public_descriptor(KeychainKind::External)
+        .max_weight_to_satisfy()
+        .unwrap();
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_silent_payment_recipient(recipient_address(None), Amount::from_sat(20_000))
+        .add_foreign_utxo(utxo.outpoint, psbt_input, foreign_utxo_satisfaction)
+        .unwrap();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::SilentPaymentForeignInput(outpoint)) if outpoint == utxo.outpoint
+    );
+}
+
+#[test]
+fn test_send_silent_payment_missing_key() {
+    let (wallet, _) = get_funded_wallet_single(get_test_wpkh());
+    let public_descriptor = wallet.public_descriptor(KeychainKind::External).to_string();
+    let (mut wallet, _) = get_funded_wallet_single(&public_descriptor);
+
+    let mut builder = wallet.build_tx();
+    builder.add_silent_payment_recipient(recipient_address(None), Amount::from_sat(20_000));
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::SilentPaymentMissingKey(_))
+    );
+}
+
+#[test]
+fn test_receive_silent_payment() {
+    let secp = Secp256k1::new();
+    let mut receiver = receiver_wallet();
+    let address = receiver.silent_payment_address(None).unwrap();
+    assert_eq!(address, recipient_address(None));
+
+    let (tx, prevouts) = pay(address, Amount::from_sat(20_000));
+    apply_block(&mut receiver, vec![tx.clone()], prevouts);
+
+    // Silent payment outputs don't belong to a keychain.
+    assert_eq!(receiver.list_unspent().count(), 0);
+    let utxos: Vec<_> = receiver.list_silent_payment_unspent().collect();
+    assert_eq!(utxos.len(), 1);
+    let utxo = &utxos[0];
+    assert_eq!(utxo.outpoint.txid, tx.compute_txid());
+    assert_eq!(utxo.txout.value, Amount::from_sat(20_000));
+    assert!(utxo.chain_position.is_confirmed());
+    assert_eq!(receiver.balance().confirmed, Amount::from_sat(20_000));
+
+    let output = receiver.silent_payment_output(utxo.outpoint).unwrap();
+    assert_eq!(output, utxo.output);
+    assert_eq!(output.label, None);
+    let output_key = SecretKey::from_str(SPEND_KEY)
+        .unwrap()
+        .add_tweak(&Scalar::from(output.tweak))
+        .unwrap();
+    assert_eq!(
+        utxo.txout.script_pubkey,
+        ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+            output_key.x_only_public_key(&secp).0
+        ))
+    );
+
+    // Spend the output to a foreign script.
+    let mut builder = receiver.build_tx();
+    builder
+        .drain_wallet()
+        .drain_to(ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()));
+    let mut psbt = builder.finish().unwrap();
+    assert!(receiver.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().unwrap();
+
+    let sighash = SighashCache::new(&tx)
+        .taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(core::slice::from_ref(&utxo.txout)),
+            TapSighashType::Default,
+        )
+        .unwrap();
+    let signature = taproot::Signature::from_slice(&tx.input[0].witness[0]).unwrap();
+    let output_key = XOnlyPublicKey::from_slice(&utxo.txout.script_pubkey.as_bytes()[2..]).unwrap();
+    secp.verify_schnorr(&signature.signature, &Message::from(sighash), &output_key)
+        .unwrap();
+
+    // The spending transaction is found in the next block.
+    apply_block(&mut receiver, vec![tx], vec![]);
+    assert_eq!(receiver.list_silent_payment_unspent().count(), 0);
+    assert!(receiver
+        .list_silent_payment_output()
+        .all(|utxo| utxo.is_spent));
+    assert_eq!(receiver.balance().total(), Amount::ZERO);
+}
+
+#[test]
+fn test_receive_labeled_silent_payment() {
+    let mut receiver = receiver_wallet();
+    let address = receiver.silent_payment_address(Some(1)).unwrap();
+    assert_ne!(address, receiver.silent_payment_address(None).unwrap());
+    assert_eq!(
+        receiver.staged().unwrap().silent_payments.labels,
+        [1].into()
+    );
+
+    // A wallet that didn't give out the label doesn't find the payment.
+    let mut other_receiver = receiver_wallet();
+    let (tx, prevouts) = pay(address, Amount::from_sat(20_000));
+    apply_block(&mut other_receiver, vec![tx], prevouts);
+    assert_eq!(other_receiver.list_silent_payment_unspent().count(), 0);
+
+    let (tx, prevouts) = pay(address, Amount::from_sat(20_000));
+    apply_block(&mut receiver, vec![tx], prevouts);
+    let utxo = receiver.list_silent_payment_unspent().next().unwrap();
+    assert_eq!(utxo.output.label, Some(1));
+
+    let mut builder = receiver.build_tx();
+    builder
+        .drain_wallet()
+        .drain_to(ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()));
+    let mut psbt = builder.finish().unwrap();
+    assert!(receiver.sign(&mut psbt, SignOptions::default()).unwrap());
+}
+
+#[test]
+fn test_receive_silent_payment_missing_prevout() {
+    let mut receiver = receiver_wallet();
+    let address = receiver.silent_payment_address(None).unwrap();
+    let (tx, prevouts) = pay(address, Amount::from_sat(20_000));
+    let (block, height) = next_block(&receiver, vec![tx]);
+
+    // The block can't be scanned without the outputs it spends, and is not applied.
+    assert_matches!(
+        receiver.apply_block_with_prevouts(&block, height, vec![]),
+        Err(ApplyBlockError::MissingPrevout(outpoint)) if outpoint == prevouts[0].0
+    );
+    assert_eq!(receiver.latest_checkpoint().height(), height - 1);
+
+    // A block applied without them isn't scanned for payments.
+    let mut other_receiver = receiver_wallet();
+    other_receiver.apply_block(&block, height).unwrap();
+    assert_eq!(other_receiver.list_silent_payment_unspent().count(), 0);
+
+    receiver
+        .apply_block_with_prevouts(&block, height, prevouts)
+        .unwrap();
+    assert_eq!(receiver.list_silent_payment_unspent().count(), 1);
+}
+
+#[test]
+fn test_load_silent_payments() {
+    let mut receiver = receiver_wallet();
+    let address = receiver.silent_payment_address(None).unwrap();
+    let (tx, prevouts) = pay(address, Amount::from_sat(20_000));
+    apply_block(&mut receiver, vec![tx], prevouts);
+    let utxo = receiver.list_silent_payment_unspent().next().unwrap();
+    let changeset = receiver.take_staged().unwrap();
+
+    let scan_key = SecretKey::from_str(SCAN_KEY).unwrap();
+    let spend_key = SecretKey::from_str(SPEND_KEY).unwrap();
+    let mut loaded = Wallet::load()
+        .silent_payments(scan_key, spend_key)
+        .load_wallet_no_persist(changeset.clone())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        loaded.list_silent_payment_unspent().collect::<Vec<_>>(),
+        vec![utxo.clone()]
+    );
+    assert_eq!(
+        loaded.silent_payment_output(utxo.outpoint),
+        receiver.silent_payment_output(utxo.outpoint)
+    );
+    assert_eq!(loaded.silent_payment_address(None), Some(address));
+
+    // Without the private keys, the outputs are listed but can't be spent.
+    let mut loaded = Wallet::load()
+        .load_wallet_no_persist(changeset.clone())
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.list_silent_payment_unspent().count(), 1);
+    let mut builder = loaded.build_tx();
+    builder
+        .drain_wallet()
+        .drain_to(ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()));
+    let mut psbt = builder.finish().unwrap();
+    assert!(!loaded.sign(&mut psbt, SignOptions::default()).unwrap());
+
+    assert_matches!(
+        Wallet::load()
+            .silent_payments(spend_key, scan_key)
+            .load_wallet_no_persist(changeset),
+        Err(LoadError::Mismatch(LoadMismatch::SilentPaymentKeys { .. }))
+    );
+}
@@ -10,14 +10,17 @@ use bdk_wallet::psbt::PsbtUtils;
 use bdk_wallet::signer::{SignOptions, SignerError};
 use bdk_wallet::test_utils::*;
 use bdk_wallet::KeychainKind;
-use bdk_wallet::{AddressInfo, Balance, PersistedWallet, Update, Wallet, WalletTx};
+use bdk_wallet::{
+    AddUtxoError, AddressInfo, Balance, ChangeSpendPolicy, PersistedWallet, RequiredUtxo, TxParams,
+    Update, UtxoFilters, Wallet, WalletTx,
+};
 use bitcoin::constants::COINBASE_MATURITY;
 use bitcoin::hashes::Hash;
 use bitcoin::script::PushBytesBuf;
 use bitcoin::sighash::{EcdsaSighashType, TapSighashType};
 use bitcoin::taproot::TapNodeHash;
 use bitcoin::{
-    absolute, transaction, Address, Amount, BlockHash, FeeRate, Network, OutPoint, ScriptBuf,
+    absolute, transaction, Address, Amount, BlockHash, FeeRate, Network, OutPoint, Psbt, ScriptBuf,
     Sequence, SignedAmount, Transaction, TxIn, TxOut, Txid,
 };
 use rand::rngs::StdRng;
@@ -513,6 +516,226 @@ fn test_create_tx_drain_to_no_drain_wallet_no_utxos() {
     builder.finish().unwrap();
 }
 
+#[test]
+fn test_create_tx_drain_wallet_to_change() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let mut builder = wallet.build_tx();
+    builder.drain_wallet();
+    let psbt = builder.finish().unwrap();
+
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    let (keychain, _) = wallet
+        .derivation_of_spk(psbt.unsigned_tx.output[0].script_pubkey.clone())
+        .unwrap();
+    assert_eq!(keychain, KeychainKind::Internal);
+}
+
+#[test]
+fn test_create_tx_max_recipient() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let max_addr = wallet.next_unused_address(KeychainKind::External);
+    let unconfirmed = receive_output(&mut wallet, Amount::from_sat(25_000), ReceiveTo::Mempool(0));
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(20_000))
+        .add_max_recipient(max_addr.script_pubkey())
+        .exclude_unconfirmed()
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5));
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    assert!(psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .all(|txin| txin.previous_output != unconfirmed));
+    let outputs = &psbt.unsigned_tx.output;
+    assert_eq!(outputs.len(), 2);
+    let max_output = outputs
+        .iter()
+        .find(|x| x.script_pubkey == max_addr.script_pubkey())
+        .unwrap();
+    assert_eq!(max_output.value, Amount::from_sat(30_000) - fee);
+    assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb_u32(5), @add_signature);
+}
+
+#[test]
+fn test_plan_max_recipient_is_not_change() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let max_addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(20_000))
+        .add_max_recipient(max_addr.script_pubkey());
+    let plan = builder.plan().unwrap();
+
+    assert_eq!(plan.unsigned_tx.output.len(), 2);
+    assert!(!plan.has_change());
+    assert!(plan.change_vouts.is_empty());
+}
+
+#[test]
+fn test_create_tx_max_recipient_manually_selected_only() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let max_addr = wallet.next_unused_address(KeychainKind::External);
+    let utxo = receive_output_in_latest_block(&mut wallet, Amount::from_sat(25_000));
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .add_max_recipient(max_addr.script_pubkey())
+        .add_utxo(utxo)
+        .unwrap()
+        .manually_selected_only();
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    assert_eq!(psbt.unsigned_tx.input[0].previous_output, utxo);
+    let max_output = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|x| x.script_pubkey == max_addr.script_pubkey())
+        .unwrap();
+    assert_eq!(max_output.value, Amount::from_sat(15_000) - fee);
+}
+
+#[test]
+fn test_create_tx_max_recipient_below_dust() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let max_addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(49_800))
+        .add_max_recipient(max_addr.script_pubkey());
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::CoinSelection(
+            coin_selection::InsufficientFunds { .. }
+        ))
+    );
+}
+
+#[test]
+fn test_max_spendable() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let confirmed = receive_output_in_latest_block(&mut wallet, Amount::from_sat(25_000));
+    let unconfirmed = receive_output(&mut wallet, Amount::from_sat(25_000), ReceiveTo::Mempool(0));
+    let fee_rate = FeeRate::from_sat_per_vb_u32(5);
+
+    let filters = UtxoFilters {
+        min_confirmations: 1,
+        ..Default::default()
+    };
+    let max = wallet.max_spendable(&addr.script_pubkey(), fee_rate, &filters);
+    assert_eq!(max.utxos.len(), 2);
+    assert!(max.utxos.iter().any(|utxo| utxo.outpoint() == confirmed));
+    assert!(max.utxos.iter().all(|utxo| utxo.outpoint() != unconfirmed));
+    assert_eq!(max.amount + max.fee, Amount::from_sat(75_000));
+
+    // Same as the transaction sending the max amount.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_max_recipient(addr.script_pubkey())
+        .exclude_unconfirmed()
+        .fee_rate(fee_rate);
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(psbt.unsigned_tx.output[0].value, max.amount);
+    assert_eq!(psbt.fee_amount(), Some(max.fee));
+
+    // Locked outpoints are not spent.
+    wallet.lock_outpoint(confirmed);
+    let max = wallet.max_spendable(&addr.script_pubkey(), fee_rate, &Default::default());
+    assert_eq!(max.utxos.len(), 2);
+    assert!(max.utxos.iter().any(|utxo| utxo.outpoint() == unconfirmed));
+}
+
+#[test]
+fn test_max_spendable_respects_policy() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_a_or_b_plus_csv());
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let fee_rate = FeeRate::from_sat_per_vb_u32(5);
+    let root_id = wallet.policies(KeychainKind::External).unwrap().unwrap().id;
+    let filters = |child: usize| UtxoFilters {
+        external_policy_path: Some(vec![(root_id.clone(), vec![child])].into_iter().collect()),
+        ..Default::default()
+    };
+
+    // The policy requires a path.
+    let max = wallet.max_spendable(&addr.script_pubkey(), fee_rate, &Default::default());
+    assert_eq!(max.amount, Amount::ZERO);
+    assert!(max.utxos.is_empty());
+
+    // child #0 is just the key "A"
+    let max = wallet.max_spendable(&addr.script_pubkey(), fee_rate, &filters(0));
+    assert_eq!(max.utxos.len(), 1);
+
+    // child #1 is or(pk(B),older(144)), the UTXO only has one confirmation
+    let max = wallet.max_spendable(&addr.script_pubkey(), fee_rate, &filters(1));
+    assert!(max.utxos.is_empty());
+
+    // A transaction mined in the next block spends the UTXO at its 144th confirmation.
+    let tip = wallet.latest_checkpoint().height();
+    insert_checkpoint(
+        &mut wallet,
+        BlockId {
+            height: tip + 143,
+            hash: BlockHash::all_zeros(),
+        },
+    );
+    let max = wallet.max_spendable(&addr.script_pubkey(), fee_rate, &filters(1));
+    assert_eq!(max.utxos.len(), 1);
+}
+
+#[test]
+fn test_max_spendable_skips_uneconomical_utxos() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(1_000));
+
+    let max = wallet.max_spendable(
+        &addr.script_pubkey(),
+        FeeRate::from_sat_per_vb_u32(20),
+        &Default::default(),
+    );
+    assert_eq!(max.utxos.len(), 1);
+    assert_eq!(max.utxos[0].txout().value, Amount::from_sat(50_000));
+
+    // Nothing left above the dust limit.
+    let max = wallet.max_spendable(
+        &addr.script_pubkey(),
+        FeeRate::from_sat_per_vb_u32(20),
+        &UtxoFilters {
+            change_policy: ChangeSpendPolicy::OnlyChange,
+            ..Default::default()
+        },
+    );
+    assert_eq!(max.amount, Amount::ZERO);
+    assert!(max.utxos.is_empty());
+}
+
 #[test]
 fn test_create_tx_default_fee_rate() {
     let (mut wallet, _) = get_funded_wallet_wpkh();
@@ -553,6 +776,143 @@ fn test_legacy_create_tx_custom_fee_rate() {
     assert_fee_rate_legacy!(psbt, fee, FeeRate::from_sat_per_vb_u32(5), @add_signature);
 }
 
+/// Assert that the package made of `parent` and the signed tx of `psbt` pays `fee_rate`.
+fn assert_package_fee_rate(wallet: &Wallet, parent: &Transaction, psbt: &Psbt, fee_rate: FeeRate) {
+    let mut tx = psbt.clone().extract_tx().expect("failed to extract tx");
+    for txin in &mut tx.input {
+        txin.witness.push([0x00; common::P2WPKH_FAKE_SIG_SIZE]);
+        txin.witness.push([0x00; common::P2WPKH_FAKE_PK_SIZE]);
+    }
+    let package_fee_rate =
+        common::package_fee_rate(wallet, &tx, &[parent.compute_txid()]).to_sat_per_kwu();
+    let fee_rate = fee_rate.to_sat_per_kwu();
+    let half_default = FeeRate::BROADCAST_MIN.to_sat_per_kwu() / 2;
+    assert!(
+        package_fee_rate >= fee_rate && package_fee_rate - fee_rate < half_default,
+        "Expected package fee rate of {fee_rate:?}, the package has {package_fee_rate:?}"
+    );
+}
+
+#[test]
+fn test_create_tx_pays_for_low_fee_ancestors() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let parent = common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(50_000)],
+        Amount::from_sat(100),
+    );
+    let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+
+    let addr = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(fee_rate);
+    let psbt = builder.finish().unwrap();
+
+    assert_package_fee_rate(&wallet, &parent, &psbt, fee_rate);
+}
+
+#[test]
+fn test_create_tx_subtract_fee_pays_for_low_fee_ancestors() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let parent = common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(50_000)],
+        Amount::from_sat(100),
+    );
+    let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+
+    let addr = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .subtract_fee_from([0])
+        .fee_rate(fee_rate);
+    let psbt = builder.finish().unwrap();
+
+    // The recipient pays the bump fee along with the fee of the transaction.
+    let recipient = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|txout| txout.script_pubkey == addr.script_pubkey())
+        .unwrap();
+    assert_eq!(
+        recipient.value,
+        Amount::from_sat(10_000) - psbt.fee().unwrap()
+    );
+    assert_package_fee_rate(&wallet, &parent, &psbt, fee_rate);
+}
+
+#[test]
+fn test_create_tx_pays_for_shared_ancestors_once() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let parent = common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(30_000), Amount::from_sat(30_000)],
+        Amount::from_sat(100),
+    );
+    let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+
+    // Both outputs of the parent are needed.
+    let addr = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(45_000))
+        .fee_rate(fee_rate);
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 2);
+
+    assert_package_fee_rate(&wallet, &parent, &psbt, fee_rate);
+}
+
+#[test]
+fn test_create_tx_high_fee_ancestors() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    common::receive_unconfirmed(
+        &mut wallet,
+        &[Amount::from_sat(50_000)],
+        Amount::from_sat(10_000),
+    );
+    let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+
+    // The parent pays enough for itself, the child only pays for its own weight.
+    let addr = Address::from_str("bcrt1q3qtze4ys45tgdvguj66zrk4fu6hq3a3v9pfly5")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(fee_rate);
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    assert_fee_rate!(psbt, fee, fee_rate, @add_signature);
+}
+
 #[test]
 fn test_create_tx_absolute_fee() {
     let (mut wallet, _) = get_funded_wallet_wpkh();
@@ -659,6 +1019,329 @@ fn test_legacy_create_tx_absolute_high_fee() {
     let _ = builder.finish().unwrap();
 }
 
+#[test]
+fn test_plan_tx_does_not_change_wallet() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let _ = wallet.take_staged();
+    let change_index = wallet.derivation_index(KeychainKind::Internal);
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5));
+    let plan = builder
+        .plan_with_aux_rand(&mut StdRng::seed_from_u64(42))
+        .unwrap();
+    drop(builder);
+
+    assert!(wallet.staged().is_none());
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::Internal),
+        change_index
+    );
+
+    // The plan matches the transaction created with the same parameters.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5));
+    let psbt = builder
+        .finish_with_aux_rand(&mut StdRng::seed_from_u64(42))
+        .unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    assert_eq!(plan.unsigned_tx, psbt.unsigned_tx);
+    assert_eq!(plan.fee, fee);
+    assert!(plan.has_change());
+    let change = &plan.unsigned_tx.output[plan.change_vout.unwrap()];
+    assert_eq!(change.value, Amount::from_sat(25_000) - fee);
+    assert_eq!(
+        plan.utxos
+            .iter()
+            .map(|utxo| utxo.outpoint())
+            .collect::<Vec<_>>(),
+        plan.unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>()
+    );
+    assert_fee_rate!(psbt, plan.fee, plan.fee_rate(), @add_signature);
+    assert_ne!(
+        wallet.derivation_index(KeychainKind::Internal),
+        change_index
+    );
+}
+
+#[test]
+fn test_plan_tx_no_change() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(49_800));
+    let plan = builder.plan().unwrap();
+
+    assert!(!plan.has_change());
+    assert_eq!(plan.unsigned_tx.output.len(), 1);
+    assert_eq!(plan.fee, Amount::from_sat(200));
+}
+
+#[test]
+fn test_build_tx_with_saved_params() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let (mut other_wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let utxo = wallet.list_unspent().next().unwrap().outpoint;
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_data(&PushBytesBuf::try_from(vec![0xaa; 10]).unwrap())
+        .add_utxo(utxo)
+        .unwrap()
+        .fee_rate(FeeRate::from_sat_per_vb_u32(3))
+        .nlocktime(absolute::LockTime::from_height(1_000).unwrap())
+        .do_not_spend_change();
+    let saved = serde_json::to_string(builder.params()).unwrap();
+    let psbt = builder
+        .finish_with_aux_rand(&mut StdRng::seed_from_u64(7))
+        .unwrap();
+
+    let params: TxParams = serde_json::from_str(&saved).unwrap();
+    let replayed = other_wallet
+        .build_tx_with_params(params)
+        .unwrap()
+        .finish_with_aux_rand(&mut StdRng::seed_from_u64(7))
+        .unwrap();
+    assert_eq!(psbt, replayed);
+}
+
+#[test]
+fn test_build_tx_with_params_spent_utxo() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let utxo = wallet.list_unspent().next().unwrap().outpoint;
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_utxo(utxo)
+        .unwrap();
+    assert_eq!(builder.params().utxos, vec![RequiredUtxo::Local(utxo)]);
+    let saved = serde_json::to_string(builder.params()).unwrap();
+    // Only the outpoint of the wallet UTXO is saved.
+    assert!(!saved.contains("chain_position"));
+    let psbt = builder.finish().unwrap();
+    insert_tx(&mut wallet, psbt.unsigned_tx);
+
+    let params: TxParams = serde_json::from_str(&saved).unwrap();
+    assert!(matches!(
+        wallet.build_tx_with_params(params),
+        Err(AddUtxoError::UnknownUtxo(outpoint)) if outpoint == utxo
+    ));
+}
+
+#[test]
+fn test_tx_params_custom_ordering_not_serializable() {
+    use bdk_wallet::tx_builder::TxOrdering;
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let mut builder = wallet.build_tx();
+    builder.ordering(TxOrdering::Custom {
+        input_sort: Arc::new(|a, b| a.previous_output.cmp(&b.previous_output)),
+        output_sort: Arc::new(|a, b| a.value.cmp(&b.value)),
+    });
+    assert!(serde_json::to_string(builder.params()).is_err());
+
+    builder.ordering(TxOrdering::Untouched);
+    let saved = serde_json::to_string(builder.params()).unwrap();
+    let params: TxParams = serde_json::from_str(&saved).unwrap();
+    assert_eq!(serde_json::to_string(&params).unwrap(), saved);
+}
+
+#[test]
+fn test_create_tx_change_strategy() {
+    use bdk_wallet::tx_builder::ChangeStrategy;
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(15_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5))
+        .change_strategy(ChangeStrategy::Denominations {
+            denominations: vec![Amount::from_sat(10_000)],
+            max_outputs: 10,
+        });
+    let plan = builder
+        .plan_with_aux_rand(&mut StdRng::seed_from_u64(42))
+        .unwrap();
+    let psbt = builder
+        .finish_with_aux_rand(&mut StdRng::seed_from_u64(42))
+        .unwrap();
+    let fee = check_fee!(wallet, psbt);
+    assert_eq!(plan.unsigned_tx, psbt.unsigned_tx);
+
+    // Three outputs of 10_000 and what's left of the 35_000 of change.
+    assert_eq!(plan.change_vouts.len(), 4);
+    assert!(plan.change_vouts.contains(&plan.change_vout.unwrap()));
+    let change = plan
+        .change_vouts
+        .iter()
+        .map(|vout| &psbt.unsigned_tx.output[*vout])
+        .collect::<Vec<_>>();
+    assert_eq!(
+        change
+            .iter()
+            .filter(|txout| txout.value == Amount::from_sat(10_000))
+            .count(),
+        3
+    );
+    assert_eq!(
+        change.iter().map(|txout| txout.value).sum::<Amount>(),
+        Amount::from_sat(35_000) - fee
+    );
+    assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb_u32(5), @add_signature);
+
+    // Each change output pays a different change address, all of them marked as used.
+    let mut indexes = change
+        .iter()
+        .map(|txout| {
+            let (keychain, index) = wallet
+                .derivation_of_spk(txout.script_pubkey.clone())
+                .unwrap();
+            assert_eq!(keychain, KeychainKind::Internal);
+            index
+        })
+        .collect::<Vec<_>>();
+    indexes.sort();
+    assert_eq!(indexes, vec![0, 1, 2, 3]);
+    assert_eq!(wallet.next_unused_address(KeychainKind::Internal).index, 4);
+}
+
+#[test]
+fn test_create_tx_invalid_change_split() {
+    use bdk_wallet::tx_builder::ChangeStrategy;
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(15_000))
+        .change_strategy(ChangeStrategy::Custom(Arc::new(|context| {
+            vec![context.amount, context.amount]
+        })));
+    assert!(serde_json::to_string(builder.params()).is_err());
+    assert_matches!(builder.finish(), Err(CreateTxError::InvalidChangeSplit));
+}
+
+#[test]
+fn test_create_tx_explained() {
+    use bdk_wallet::coin_selection::{ExclusionReason, LargestFirstCoinSelection, UtxoStatus};
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let funding = OutPoint::new(txid, 0);
+    let locked = receive_output_in_latest_block(&mut wallet, Amount::from_sat(30_000));
+    let unspendable = receive_output_in_latest_block(&mut wallet, Amount::from_sat(20_000));
+    let uneconomical = receive_output_in_latest_block(&mut wallet, Amount::from_sat(500));
+    let spare = receive_output_in_latest_block(&mut wallet, Amount::from_sat(10_000));
+    let unconfirmed = receive_output(&mut wallet, Amount::from_sat(40_000), ReceiveTo::Mempool(0));
+    wallet.lock_outpoint(locked);
+
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+    let mut builder = wallet.build_tx().coin_selection(LargestFirstCoinSelection);
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_unspendable(unspendable)
+        .exclude_unconfirmed()
+        .fee_rate(fee_rate);
+    let (psbt, explanation) = builder.finish_explained();
+    let psbt = psbt.unwrap();
+
+    assert_eq!(explanation.algorithms, ["LargestFirstCoinSelection"]);
+    assert_eq!(explanation.fee_rate, fee_rate);
+    assert!(explanation.target_amount > Amount::from_sat(25_000));
+    let status = |outpoint| {
+        explanation
+            .utxos
+            .iter()
+            .find(|utxo| utxo.outpoint == outpoint)
+            .unwrap()
+            .status
+    };
+    assert_eq!(status(funding), UtxoStatus::Selected);
+    assert_eq!(status(spare), UtxoStatus::NotSelected);
+    assert_eq!(
+        status(locked),
+        UtxoStatus::Excluded(ExclusionReason::Locked)
+    );
+    assert_eq!(
+        status(unspendable),
+        UtxoStatus::Excluded(ExclusionReason::Unspendable)
+    );
+    assert_eq!(
+        status(uneconomical),
+        UtxoStatus::Excluded(ExclusionReason::NegativeEffectiveValue)
+    );
+    assert_eq!(
+        status(unconfirmed),
+        UtxoStatus::Excluded(ExclusionReason::Unconfirmed)
+    );
+    assert_eq!(
+        explanation
+            .selected()
+            .map(|utxo| utxo.outpoint)
+            .collect::<Vec<_>>(),
+        psbt.unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(explanation.excluded().count(), 4);
+
+    // The explanation of the spare UTXO's effective value.
+    let spare = explanation
+        .utxos
+        .iter()
+        .find(|utxo| utxo.outpoint == spare)
+        .unwrap();
+    assert_eq!(spare.value, Amount::from_sat(10_000));
+    assert_eq!(
+        spare.effective_value,
+        SignedAmount::from_sat(10_000 - 10 * 68)
+    );
+}
+
+#[test]
+fn test_create_tx_explained_insufficient_funds() {
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(100_000));
+    let (psbt, explanation) = builder.finish_explained();
+
+    assert_matches!(
+        psbt,
+        Err(CreateTxError::CoinSelection(
+            coin_selection::InsufficientFunds { .. }
+        ))
+    );
+    assert!(explanation.algorithms.is_empty());
+    assert_eq!(explanation.utxos.len(), 1);
+    assert_eq!(explanation.utxos[0].outpoint, OutPoint::new(txid, 0));
+    assert_eq!(
+        explanation.utxos[0].status,
+        coin_selection::UtxoStatus::NotSelected
+    );
+}
+
 #[test]
 fn test_create_tx_add_change() {
     use bdk_wallet::tx_builder::TxOrdering;
@@ -709,6 +1392,106 @@ fn test_create_tx_drain_to_dust_amount() {
     builder.finish().unwrap();
 }
 
+#[test]
+fn test_create_tx_subtract_fee_from_recipient() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .subtract_fee_from([0])
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5));
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    let outputs = &psbt.unsigned_tx.output;
+    assert_eq!(outputs.len(), 2);
+    let recipient = outputs
+        .iter()
+        .find(|txout| txout.script_pubkey == addr.script_pubkey())
+        .unwrap();
+    let change = outputs
+        .iter()
+        .find(|txout| txout.script_pubkey != addr.script_pubkey())
+        .unwrap();
+    assert_eq!(recipient.value, Amount::from_sat(25_000) - fee);
+    assert_eq!(change.value, Amount::from_sat(25_000));
+    assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb_u32(5), @add_signature);
+}
+
+#[test]
+fn test_create_tx_subtract_fee_from_whole_balance() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(50_000))
+        .subtract_fee_from([0]);
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(
+        psbt.unsigned_tx.output[0].value,
+        Amount::from_sat(50_000) - fee
+    );
+    assert_fee_rate!(psbt, fee, FeeRate::BROADCAST_MIN, @add_signature);
+}
+
+#[test]
+fn test_create_tx_subtract_fee_split_between_recipients() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr1 = wallet.next_unused_address(KeychainKind::External);
+    let addr2 = wallet.next_unused_address(KeychainKind::External);
+    let addr3 = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .ordering(bdk_wallet::TxOrdering::Untouched)
+        .add_recipient(addr1.script_pubkey(), Amount::from_sat(10_000))
+        .add_recipient(addr2.script_pubkey(), Amount::from_sat(10_000))
+        .add_recipient(addr3.script_pubkey(), Amount::from_sat(10_000))
+        .subtract_fee_from([2, 0, 2])
+        .fee_rate(FeeRate::from_sat_per_vb_u32(3));
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    let outputs = &psbt.unsigned_tx.output;
+    let paid = |index: usize| Amount::from_sat(10_000) - outputs[index].value;
+    assert_eq!(outputs[1].value, Amount::from_sat(10_000));
+    assert_eq!(paid(0) + paid(2), fee);
+    assert_eq!(paid(0) - paid(2), fee - (fee / 2) * 2);
+    assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb_u32(3), @add_signature);
+}
+
+#[test]
+fn test_create_tx_subtract_fee_below_dust() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(600))
+        .subtract_fee_from([0])
+        .fee_rate(FeeRate::from_sat_per_vb_u32(5));
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::SubtractFeeBelowDust { index: 0, .. })
+    );
+}
+
+#[test]
+fn test_create_tx_subtract_fee_invalid_index() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .subtract_fee_from([1]);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::SubtractFeeIndexOutOfBounds(err)) if err.index == 1 && err.len == 1
+    );
+}
+
 #[test]
 fn test_create_tx_ordering_respected() {
     let (mut wallet, _) = get_funded_wallet_wpkh();